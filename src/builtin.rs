@@ -0,0 +1,471 @@
+//! Built-in implementations (Z14K4), looked up by their id in a registry that can be extended, or overridden, with new ones.
+
+use std::{collections::BTreeMap, fmt::Debug, rc::Rc, sync::Arc};
+
+use crate::{
+    DataEntry, EvaluationContext, EvaluationErrorKind, Runner, Scope, Step, Zid,
+    parse_tool::{PotentialReference, WfFunction, parse_boolean},
+};
+
+/// A built-in implementation, run natively on its arguments
+pub trait Builtin: Send + Sync {
+    /// The keys of the arguments, in declaration order
+    fn argument_keys(&self) -> &[Zid];
+
+    /// The arguments that must only be evaluated when the built-in needs them, whatever the evaluation strategy
+    fn lazy_arguments(&self) -> &[Zid] {
+        &[]
+    }
+
+    /// Run with the other arguments already evaluated. The step may be a value, or an evaluation run in place of the call.
+    fn apply(
+        &self,
+        runner: &Runner,
+        arguments: BuiltinArguments,
+        context: &EvaluationContext,
+    ) -> Result<Step, EvaluationErrorKind>;
+}
+
+/// The arguments a built-in is applied to
+pub struct BuiltinArguments {
+    values: BTreeMap<Zid, DataEntry>,
+    scope: Rc<Scope>,
+}
+
+impl BuiltinArguments {
+    pub(crate) fn new(values: BTreeMap<Zid, DataEntry>, scope: Rc<Scope>) -> Self {
+        Self { values, scope }
+    }
+
+    /// Take the value of an argument that isn’t lazy
+    pub fn take(&mut self, key: Zid) -> Result<DataEntry, EvaluationErrorKind> {
+        self.values
+            .remove(&key)
+            .ok_or(EvaluationErrorKind::UnboundArgument(key))
+    }
+
+    /// The evaluation of a lazy argument. It isn’t memoized, as it is meant to be evaluated in place of the call, once, so that a call in it is a tail call.
+    pub fn lazy(&self, key: Zid) -> Result<Step, EvaluationErrorKind> {
+        let thunk = self.scope.get(&key)?;
+        Ok(match thunk.get_expression() {
+            Some((expression, scope)) if thunk.get_value().is_none() => {
+                Step::Evaluate(expression.clone(), scope.clone())
+            }
+            _ => Step::Force(thunk.clone()),
+        })
+    }
+}
+
+type BuiltinFunction = dyn Fn(&Runner, BuiltinArguments, &EvaluationContext) -> Result<Step, EvaluationErrorKind>
+    + Send
+    + Sync;
+
+/// A built-in made of a function
+pub struct FnBuiltin {
+    argument_keys: Vec<Zid>,
+    lazy_arguments: Vec<Zid>,
+    apply: Box<BuiltinFunction>,
+}
+
+impl FnBuiltin {
+    pub fn new(
+        argument_keys: impl Into<Vec<Zid>>,
+        apply: impl Fn(
+            &Runner,
+            BuiltinArguments,
+            &EvaluationContext,
+        ) -> Result<Step, EvaluationErrorKind>
+        + Send
+        + Sync
+        + 'static,
+    ) -> Self {
+        Self {
+            argument_keys: argument_keys.into(),
+            lazy_arguments: Vec::new(),
+            apply: Box::new(apply),
+        }
+    }
+
+    pub fn with_lazy_arguments(mut self, lazy_arguments: impl Into<Vec<Zid>>) -> Self {
+        self.lazy_arguments = lazy_arguments.into();
+        self
+    }
+}
+
+impl Builtin for FnBuiltin {
+    fn argument_keys(&self) -> &[Zid] {
+        &self.argument_keys
+    }
+
+    fn lazy_arguments(&self) -> &[Zid] {
+        &self.lazy_arguments
+    }
+
+    fn apply(
+        &self,
+        runner: &Runner,
+        arguments: BuiltinArguments,
+        context: &EvaluationContext,
+    ) -> Result<Step, EvaluationErrorKind> {
+        (self.apply)(runner, arguments, context)
+    }
+}
+
+/// The built-ins by id, and the ids run with another implementation instead. Clones share the registered built-ins.
+#[derive(Clone)]
+pub struct BuiltinRegistry {
+    builtins: BTreeMap<Zid, Arc<dyn Builtin>>,
+    aliases: BTreeMap<Zid, Zid>,
+}
+
+impl Debug for BuiltinRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BuiltinRegistry")
+            .field("builtins", &self.builtins.keys().collect::<Vec<_>>())
+            .field("aliases", &self.aliases)
+            .finish()
+    }
+}
+
+impl Default for BuiltinRegistry {
+    /// The built-ins of the orchestrator this interpreter supports
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        // If
+        registry.register(
+            zid!(902),
+            FnBuiltin::new(
+                [zid!(802, 1), zid!(802, 2), zid!(802, 3)],
+                |_, mut arguments, _| {
+                    let condition = parse_boolean(&arguments.take(zid!(802, 1))?)
+                        .map_err(|e| e.trace_str("parsing condition"))?;
+                    arguments.lazy(if condition {
+                        zid!(802, 2)
+                    } else {
+                        zid!(802, 3)
+                    })
+                },
+            )
+            .with_lazy_arguments([zid!(802, 2), zid!(802, 3)]),
+        );
+        // Reify
+        registry.register(
+            zid!(905),
+            FnBuiltin::new([zid!(805, 1)], |runner, mut arguments, _| {
+                Ok(Step::Value(
+                    arguments
+                        .take(zid!(805, 1))?
+                        .reify(runner)
+                        .map_err(|e| e.trace_str("running built-in reify"))?,
+                ))
+            }),
+        );
+        // Is empty (typed) list
+        registry.register(
+            zid!(913),
+            FnBuiltin::new([zid!(813, 1)], |runner, mut arguments, _| {
+                let list = arguments.take(zid!(813, 1))?;
+                // <= 1 cause typed list store the type as the first index
+                Ok(Step::Value(
+                    runner.get_bool(list.get_array()?.len() <= 1)?.clone(),
+                ))
+            }),
+        );
+        // Filter
+        registry.register(
+            zid!(972),
+            FnBuiltin::new([zid!(872, 1), zid!(872, 2)], |_, mut arguments, _| {
+                let function = arguments.take(zid!(872, 1))?;
+                let list = Runner::take_array(arguments.take(zid!(872, 2))?)
+                    .map_err(|e| e.trace_str("evaluating the list to filter"))?;
+
+                // the type of the elements is kept
+                let mut list = list.into_iter();
+                let kept = list.next().into_iter().collect();
+                Ok(Runner::fold_list(
+                    function,
+                    list.enumerate(),
+                    kept,
+                    "filtering element",
+                    |_, element| vec![element.clone()],
+                    |mut kept, element, keep| {
+                        if parse_boolean(&keep)? {
+                            kept.push(element);
+                        }
+                        Ok(kept)
+                    },
+                    DataEntry::Array,
+                ))
+            }),
+        );
+        // Map
+        registry.register(
+            zid!(973),
+            FnBuiltin::new([zid!(873, 1), zid!(873, 2)], |runner, mut arguments, _| {
+                let function = arguments.take(zid!(873, 1))?;
+                let list = Runner::take_array(arguments.take(zid!(873, 2))?)
+                    .map_err(|e| e.trace_str("evaluating the list to map"))?;
+
+                // the type of the elements is the return type of the function, if it is a simple one
+                let function_parsed = PotentialReference::<WfFunction>::new(&function)
+                    .evaluate(runner)
+                    .map_err(|e| e.trace_str("getting the function to map with"))?;
+                let element_type = match function_parsed.return_type.get_entry().normalize() {
+                    DataEntry::String(return_type) => DataEntry::String(return_type),
+                    _ => DataEntry::String("Z1".to_string()),
+                };
+
+                let mut list = list.into_iter();
+                list.next();
+                Ok(Runner::fold_list(
+                    function,
+                    list.enumerate(),
+                    vec![element_type],
+                    "mapping element",
+                    |_, element| vec![element.clone()],
+                    |mut result, _, value| {
+                        result.push(value);
+                        Ok(result)
+                    },
+                    DataEntry::Array,
+                ))
+            }),
+        );
+        // Reduce, from the left. The function is called with the accumulator then the element.
+        registry.register(
+            zid!(976),
+            FnBuiltin::new(
+                [zid!(876, 1), zid!(876, 2), zid!(876, 3)],
+                |_, mut arguments, _| {
+                    let function = arguments.take(zid!(876, 1))?;
+                    let list = Runner::take_array(arguments.take(zid!(876, 2))?)
+                        .map_err(|e| e.trace_str("evaluating the list to reduce"))?;
+                    let initial_value = arguments.take(zid!(876, 3))?;
+
+                    let mut list = list.into_iter();
+                    list.next();
+                    Ok(Runner::fold_list(
+                        function,
+                        list.enumerate(),
+                        initial_value,
+                        "reducing element",
+                        |accumulator, element| vec![accumulator.clone(), element.clone()],
+                        |_, _, accumulator| Ok(accumulator),
+                        |accumulator| accumulator,
+                    ))
+                },
+            ),
+        );
+        // boolean equality
+        registry.register(
+            zid!(944),
+            FnBuiltin::new([zid!(844, 1), zid!(844, 2)], |runner, mut arguments, _| {
+                let boolean1 = parse_boolean(&arguments.take(zid!(844, 1))?)
+                    .map_err(|e| e.trace_str("parsing first boolean"))?;
+                let boolean2 = parse_boolean(&arguments.take(zid!(844, 2))?)
+                    .map_err(|e| e.trace_str("parsing second boolean"))?;
+
+                Ok(Step::Value(runner.get_bool(boolean1 == boolean2)?.clone()))
+            }),
+        );
+        // list equality
+        registry.register(
+            zid!(989),
+            FnBuiltin::new(
+                [zid!(889, 1), zid!(889, 2)],
+                |runner, mut arguments, context| {
+                    let list1 = arguments.take(zid!(889, 1))?;
+                    list1
+                        .get_array()
+                        .map_err(|e| e.trace_str("evaluating first list"))?;
+                    let list2 = arguments.take(zid!(889, 2))?;
+                    list2
+                        .get_array()
+                        .map_err(|e| e.trace_str("evaluating second list"))?;
+
+                    Ok(Step::Value(
+                        runner
+                            .get_bool(
+                                runner
+                                    .values_equal(&list1, &list2, context)
+                                    .map_err(|e| e.trace_str("comparing lists"))?,
+                            )?
+                            .clone(),
+                    ))
+                },
+            ),
+        );
+        // string equality
+        registry.register(
+            zid!(966),
+            FnBuiltin::new(
+                [zid!(866, 1), zid!(866, 2)],
+                |runner, mut arguments, context| {
+                    let string1 = arguments.take(zid!(866, 1))?;
+                    let string2 = arguments.take(zid!(866, 2))?;
+
+                    Ok(Step::Value(
+                        runner
+                            .get_bool(
+                                runner
+                                    .values_equal(&string1, &string2, context)
+                                    .map_err(|e| e.trace_str("comparing strings"))?,
+                            )?
+                            .clone(),
+                    ))
+                },
+            ),
+        );
+        registry
+    }
+}
+
+impl BuiltinRegistry {
+    /// A registry without any built-in
+    pub fn empty() -> Self {
+        Self {
+            builtins: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+        }
+    }
+
+    /// Add a built-in, replacing the one with the same id and any alias of it
+    pub fn register(&mut self, id: Zid, builtin: impl Builtin + 'static) {
+        self.aliases.remove(&id);
+        self.builtins.insert(id, Arc::new(builtin));
+    }
+
+    /// Run the implementation with that id in place of the built-in, replacing it
+    pub fn alias(&mut self, id: Zid, implementation: Zid) {
+        self.builtins.remove(&id);
+        self.aliases.insert(id, implementation);
+    }
+
+    pub fn remove(&mut self, id: &Zid) {
+        self.builtins.remove(id);
+        self.aliases.remove(id);
+    }
+
+    pub fn get(&self, id: &Zid) -> Option<&dyn Builtin> {
+        self.builtins.get(id).map(|builtin| builtin.as_ref())
+    }
+
+    pub fn get_alias(&self, id: &Zid) -> Option<Zid> {
+        self.aliases.get(id).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        BuiltinRegistry, DataEntry, EvaluationErrorKind, FnBuiltin, Runner, RunnerOption, Step,
+        Zid,
+        parse_tool::{WfFunctionCall, WfParse, raw_string_to_object_string},
+        test_fixture::{add_builtin, add_function, fixture_datas},
+    };
+
+    #[test]
+    fn test_custom_builtin() {
+        let mut builtins = BuiltinRegistry::default();
+        // overriding boolean equality
+        builtins.register(
+            zid!(944),
+            FnBuiltin::new([zid!(844, 1), zid!(844, 2)], |_, mut arguments, _| {
+                arguments.take(zid!(844, 1))?;
+                arguments.take(zid!(844, 2))?;
+                Ok(Step::Value(raw_string_to_object_string(
+                    "overridden".to_string(),
+                )))
+            }),
+        );
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{
+                "Z1K1": "Z7",
+                "Z7K1": "Z844",
+                "Z844K1": { "Z1K1": "Z40", "Z40K1": "Z41" },
+                "Z844K2": { "Z1K1": "Z40", "Z40K1": "Z42" }
+            }"#,
+        )
+        .unwrap();
+        let runner = Runner::new(Arc::new(fixture_datas())).with_builtins(builtins);
+        assert_eq!(
+            runner
+                .run_function_call(
+                    &WfFunctionCall::parse(&call).unwrap(),
+                    &RunnerOption::default()
+                )
+                .unwrap(),
+            raw_string_to_object_string("overridden".to_string())
+        );
+    }
+
+    #[test]
+    fn test_string_equality() {
+        let mut datas = fixture_datas();
+        add_function(
+            &mut datas,
+            "Z866",
+            "string equality",
+            &["Z866K1", "Z866K2"],
+            "Z40",
+            &["Z966"],
+        );
+        add_builtin(&mut datas, "Z966", "Z866");
+        let runner = Runner::new(Arc::new(datas));
+        let equal = |left: &str, right: &str| {
+            let call = serde_json::from_str::<DataEntry>(&format!(
+                r#"{{ "Z1K1": "Z7", "Z7K1": "Z866", "Z866K1": {left}, "Z866K2": {right} }}"#
+            ))
+            .unwrap();
+            runner
+                .run_function_call(
+                    &WfFunctionCall::parse(&call).unwrap(),
+                    &RunnerOption::default(),
+                )
+                .unwrap()
+        };
+        // a string literal and a Z6 are the same string
+        assert_eq!(
+            equal(r#""abc""#, r#"{ "Z1K1": "Z6", "Z6K1": "abc" }"#),
+            *runner.get_true().unwrap()
+        );
+        assert_eq!(equal(r#""abc""#, r#""abd""#), *runner.get_false().unwrap());
+    }
+
+    #[test]
+    fn test_filter_invalid_boolean() {
+        // filter(K1 -> Z43, [true]): the function returns something that isn’t a boolean
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{
+                "Z1K1": "Z7",
+                "Z7K1": "Z872",
+                "Z872K1": {
+                    "Z1K1": "Z8",
+                    "Z8K1": ["Z17", { "Z1K1": "Z17", "Z17K1": "Z40", "Z17K2": "K1", "Z17K3": { "Z1K1": "Z12", "Z12K1": ["Z11"] } }],
+                    "Z8K2": "Z40",
+                    "Z8K3": ["Z20"],
+                    "Z8K4": ["Z14", {
+                        "Z1K1": "Z14",
+                        "Z14K2": { "Z1K1": "Z40", "Z40K1": "Z43" }
+                    }]
+                },
+                "Z872K2": ["Z40", { "Z1K1": "Z40", "Z40K1": "Z41" }]
+            }"#,
+        )
+        .unwrap();
+        let runner = Runner::new(Arc::new(fixture_datas()));
+        let error = runner
+            .run_function_call(
+                &WfFunctionCall::parse(&call).unwrap(),
+                &RunnerOption::default(),
+            )
+            .unwrap_err();
+        assert!(
+            matches!(error.root(), EvaluationErrorKind::InvalidBoolean(value) if value == "Z43"),
+            "{:?}",
+            error
+        );
+    }
+}
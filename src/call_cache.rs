@@ -0,0 +1,351 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    sync::Mutex,
+};
+
+use crate::{
+    DataEntry, GlobalDatas, Zid,
+    disk_cache::{DiskCache, DiskLookup},
+};
+
+thread_local! {
+    /// The persistent objects read by the evaluations running on this thread, to know which ones a cached result depends on
+    static READ_LOG: RefCell<Vec<Zid>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn record_read(zid: Zid) {
+    READ_LOG.with_borrow_mut(|log| log.push(zid));
+}
+
+pub fn read_log_position() -> usize {
+    READ_LOG.with_borrow(|log| log.len())
+}
+
+pub fn reads_since(position: usize) -> BTreeSet<Zid> {
+    READ_LOG.with_borrow(|log| {
+        log.get(position..)
+            .unwrap_or_default()
+            .iter()
+            .copied()
+            .collect()
+    })
+}
+
+pub fn truncate_read_log(position: usize) {
+    READ_LOG.with_borrow_mut(|log| log.truncate(position));
+}
+
+/// A function call, with its evaluated arguments in their canonical form
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CallKey {
+    pub function: Zid,
+    /// Different implementations are kept apart, so that testing an implementation doesn’t reuse the results of another
+    pub implementation: Option<Zid>,
+    pub arguments: BTreeMap<Zid, DataEntry>,
+}
+
+impl CallKey {
+    pub fn new(
+        function: Zid,
+        implementation: Option<Zid>,
+        arguments: &BTreeMap<Zid, DataEntry>,
+    ) -> Self {
+        Self {
+            function,
+            implementation,
+            arguments: arguments
+                .iter()
+                .map(|(key, value)| (*key, value.normalize()))
+                .collect(),
+        }
+    }
+}
+
+/// The result of a function call, with the persistent objects read to compute it
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedCall {
+    pub value: DataEntry,
+    pub dependencies: BTreeSet<Zid>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    /// Hits found on disk, included in hits
+    pub disk_hits: u64,
+    /// Disk entries ignored as the persistent objects they depend on changed, included in misses
+    pub stale_disk_entries: u64,
+    pub disk_write_errors: u64,
+}
+
+#[derive(Debug, Default)]
+struct CallCacheState {
+    results: HashMap<CallKey, CachedCall>,
+    /// Keys in insertion order, the oldest being evicted first
+    insertion_order: VecDeque<CallKey>,
+    stats: CallCacheStats,
+}
+
+/// Results of the function calls already performed, shared by all the evaluations of a Runner
+#[derive(Debug)]
+pub struct CallCache {
+    max_entries: usize,
+    /// Functions whose result may change between calls with the same arguments
+    impure_functions: HashSet<Zid>,
+    /// Functions whose code implementations are known to give the same result for the same arguments
+    pure_code_functions: HashSet<Zid>,
+    disk_cache: Option<DiskCache>,
+    state: Mutex<CallCacheState>,
+}
+
+impl CallCache {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            impure_functions: HashSet::new(),
+            pure_code_functions: HashSet::new(),
+            disk_cache: None,
+            state: Mutex::new(CallCacheState::default()),
+        }
+    }
+
+    /// Never cache this function. The functions calling it, directly or not, won’t be cached either.
+    pub fn with_impure_function(mut self, function: Zid) -> Self {
+        self.impure_functions.insert(function);
+        self
+    }
+
+    /// Also look for results in, and write them to, this disk cache
+    pub fn with_disk_cache(mut self, disk_cache: DiskCache) -> Self {
+        self.disk_cache = Some(disk_cache);
+        self
+    }
+
+    /// Cache the results of this function when it runs code. Code may read the clock or draw random numbers, so it is treated as impure otherwise.
+    pub fn with_pure_code_function(mut self, function: Zid) -> Self {
+        self.pure_code_functions.insert(function);
+        self
+    }
+
+    /// Whether the function may give different results for the same arguments, when it runs code or not
+    pub fn is_impure(&self, function: &Zid, runs_code: bool) -> bool {
+        self.impure_functions.contains(function)
+            || (runs_code && !self.pure_code_functions.contains(function))
+    }
+
+    /// Whether the results need the persistent objects they depend on
+    pub fn has_disk_cache(&self) -> bool {
+        self.disk_cache.is_some()
+    }
+
+    pub fn get(&self, key: &CallKey, datas: &GlobalDatas) -> Option<CachedCall> {
+        {
+            let mut state = self.state.lock().unwrap();
+            if let Some(call) = state.results.get(key).cloned() {
+                state.stats.hits += 1;
+                return Some(call);
+            }
+        }
+
+        let lookup = match &self.disk_cache {
+            Some(disk_cache) => disk_cache.get(key, datas),
+            None => DiskLookup::Missing,
+        };
+        let mut state = self.state.lock().unwrap();
+        match lookup {
+            DiskLookup::Hit(call) => {
+                state.stats.hits += 1;
+                state.stats.disk_hits += 1;
+                drop(state);
+                self.insert_in_memory(key.clone(), call.clone());
+                Some(call)
+            }
+            DiskLookup::Stale => {
+                state.stats.misses += 1;
+                state.stats.stale_disk_entries += 1;
+                None
+            }
+            DiskLookup::Missing => {
+                state.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, key: CallKey, call: CachedCall, datas: &GlobalDatas) {
+        if let Some(disk_cache) = &self.disk_cache
+            && disk_cache.insert(&key, &call, datas).is_err()
+        {
+            self.state.lock().unwrap().stats.disk_write_errors += 1;
+        }
+        self.insert_in_memory(key, call);
+    }
+
+    fn insert_in_memory(&self, key: CallKey, call: CachedCall) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.results.contains_key(&key) {
+            return;
+        }
+        while state.results.len() >= self.max_entries {
+            let Some(oldest) = state.insertion_order.pop_front() else {
+                break;
+            };
+            state.results.remove(&oldest);
+            state.stats.evictions += 1;
+        }
+        state.insertion_order.push_back(key.clone());
+        state.results.insert(key, call);
+    }
+
+    pub fn stats(&self) -> CallCacheStats {
+        let state = self.state.lock().unwrap();
+        CallCacheStats {
+            entries: state.results.len(),
+            ..state.stats
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.results.clear();
+        state.insertion_order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+    };
+
+    use serde_json::Value;
+
+    use crate::{
+        CancellationToken, CodeExecutor, CodeLimits, DataEntry, EvaluationErrorKind, GlobalDatas,
+        ProgrammingLanguage, Runner, RunnerOption, Zid,
+        parse_tool::{WfFunctionCall, WfParse, raw_string_to_object_string},
+        test_fixture::{add_persistent, fixture_datas},
+    };
+
+    use super::{CachedCall, CallCache, CallKey};
+
+    /// Return how many times code was run before
+    #[derive(Debug, Default)]
+    struct CountingExecutor(AtomicUsize);
+
+    impl CodeExecutor for CountingExecutor {
+        fn supports(&self, _: ProgrammingLanguage) -> bool {
+            true
+        }
+
+        fn execute(
+            &self,
+            _: ProgrammingLanguage,
+            _: &str,
+            _: &str,
+            _: &[Value],
+            _: &CodeLimits,
+            _: &CancellationToken,
+        ) -> Result<Value, EvaluationErrorKind> {
+            Ok(Value::String(
+                self.0.fetch_add(1, Ordering::SeqCst).to_string(),
+            ))
+        }
+    }
+
+    #[test]
+    fn test_call_cache_eviction() {
+        let datas = GlobalDatas::default();
+        let cache = CallCache::new(2);
+        let key = |value: &str| {
+            CallKey::new(
+                zid!(10001),
+                None,
+                &BTreeMap::from([(zid!(10001, 1), DataEntry::String(value.to_string()))]),
+            )
+        };
+        for value in ["Z41", "Z42", "Z41", "Z10"] {
+            if cache.get(&key(value), &datas).is_none() {
+                cache.insert(
+                    key(value),
+                    CachedCall {
+                        value: DataEntry::String(value.to_string()),
+                        dependencies: BTreeSet::new(),
+                    },
+                    &datas,
+                );
+            }
+        }
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 3));
+        assert_eq!((stats.entries, stats.evictions), (2, 1));
+        // the oldest entry was evicted
+        assert!(cache.get(&key("Z41"), &datas).is_none());
+        assert!(cache.get(&key("Z10"), &datas).is_some());
+    }
+
+    #[test]
+    fn test_code_is_impure() {
+        let mut datas = fixture_datas();
+        add_persistent(
+            &mut datas,
+            "Z10095",
+            "counter",
+            r#"{
+                "Z1K1": "Z8",
+                "Z8K1": ["Z17", { "Z1K1": "Z17", "Z17K1": "Z6", "Z17K2": "Z10095K1", "Z17K3": { "Z1K1": "Z12", "Z12K1": ["Z11"] } }],
+                "Z8K2": "Z6",
+                "Z8K3": ["Z20"],
+                "Z8K4": ["Z14", "Z100951"],
+                "Z8K5": "Z10095"
+            }"#,
+        );
+        add_persistent(
+            &mut datas,
+            "Z100951",
+            "Z100951",
+            r#"{
+                "Z1K1": "Z14",
+                "Z14K1": "Z10095",
+                "Z14K3": { "Z1K1": "Z16", "Z16K1": "Z600", "Z16K2": "function Z10095( Z10095K1 ) { return String( counter++ ); }" }
+            }"#,
+        );
+        let datas = Arc::new(datas);
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{ "Z1K1": "Z7", "Z7K1": "Z10095", "Z10095K1": "a" }"#,
+        )
+        .unwrap();
+        let call = WfFunctionCall::parse(&call).unwrap();
+
+        for (call_cache, expected) in [
+            (CallCache::new(100), ["0", "1"]),
+            (
+                CallCache::new(100).with_pure_code_function(zid!(10095)),
+                ["0", "0"],
+            ),
+        ] {
+            let runner = Runner::new(datas.clone()).with_call_cache(call_cache);
+            let option = RunnerOption {
+                code_executor: Some(Arc::new(CountingExecutor::default())),
+                ..Default::default()
+            };
+            for expected in expected {
+                assert_eq!(
+                    runner.run_function_call(&call, &option).unwrap(),
+                    raw_string_to_object_string(expected.to_string())
+                );
+            }
+        }
+    }
+}
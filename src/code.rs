@@ -0,0 +1,578 @@
+//! Run code implementations (Z14K3). Arguments and results are converted the way the function evaluator of Wikifunctions does by default: strings and booleans become native ones, typed lists become arrays, and other objects are passed as they are.
+//! Types with type converters (Z46 to code, Z64 from code) are converted by them instead, inside the engine, so that they can use native values that JSON can’t carry.
+
+use std::{collections::BTreeMap, fmt::Display, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    DataEntry, EvaluationContext, EvaluationErrorKind, Runner, Zid,
+    parse_tool::{
+        PotentialReference, WfCode, WfType, WfTypeConverter, parse_string_permissive,
+        parse_zid_string, raw_string_to_object_string,
+    },
+};
+
+/// A language code implementations are written in (a Z61)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProgrammingLanguage {
+    JavaScript,
+    Python,
+}
+
+impl ProgrammingLanguage {
+    /// Parse a reference to a Z61, or a Z61 itself
+    pub fn parse(entry: &DataEntry) -> Result<Self, EvaluationErrorKind> {
+        let code = match entry {
+            DataEntry::String(reference) => match reference.as_str() {
+                "Z600" => return Ok(Self::JavaScript),
+                "Z610" => return Ok(Self::Python),
+                _ => reference.as_str(),
+            },
+            _ => parse_string_permissive(entry.get_map_entry(&zid!(61, 1))?)?,
+        };
+        if code.starts_with("javascript") {
+            Ok(Self::JavaScript)
+        } else if code.starts_with("python") {
+            Ok(Self::Python)
+        } else {
+            Err(EvaluationErrorKind::Unimplemented(format!(
+                "the programming language {}",
+                code
+            )))
+        }
+    }
+
+    /// Code defining a function that calls the implementation through the converters, then its name. The sources of the converters must be added too.
+    fn entry_point(
+        &self,
+        function_id: Zid,
+        arguments: &[Conversion],
+        result: &Conversion,
+    ) -> (String, &'static str) {
+        let arguments = arguments
+            .iter()
+            .map(|argument| argument.to_literal(*self))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let result = result.to_literal(*self);
+        let code = match self {
+            Self::JavaScript => format!(
+                r#"function WfConvert( value, conversion ) {{
+	if ( conversion === null ) {{
+		return value;
+	}}
+	if ( Array.isArray( conversion ) ) {{
+		return value.map( ( element ) => WfConvert( element, conversion[ 0 ] ) );
+	}}
+	return globalThis[ conversion ]( value );
+}}
+function WfEntryPoint( ...args ) {{
+	const conversions = [ {arguments} ];
+	return WfConvert( {function_id}( ...args.map( ( arg, pos ) => WfConvert( arg, conversions[ pos ] ) ) ), {result} );
+}}
+"#
+            ),
+            Self::Python => format!(
+                r#"def WfConvert(value, conversion):
+    if conversion is None:
+        return value
+    if isinstance(conversion, list):
+        return [WfConvert(element, conversion[0]) for element in value]
+    return globals()[conversion](value)
+def WfEntryPoint(*args):
+    conversions = [{arguments}]
+    return WfConvert({function_id}(*[WfConvert(arg, conversion) for arg, conversion in zip(args, conversions)]), {result})
+"#
+            ),
+        };
+        (code, "WfEntryPoint")
+    }
+
+    /// Whether the embedded engine for this language is enabled
+    pub fn is_supported(&self) -> bool {
+        match self {
+            Self::JavaScript => cfg!(feature = "javascript"),
+            Self::Python => cfg!(feature = "python"),
+        }
+    }
+}
+
+/// Whether a type converter turns ZObjects into native values (Z46), or native values back into ZObjects (Z64)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConverterDirection {
+    ToCode,
+    FromCode,
+}
+
+impl Display for ConverterDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::ToCode => "to",
+            Self::FromCode => "from",
+        })
+    }
+}
+
+/// How a value is converted, following its declared type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conversion {
+    /// The default serialization
+    Default,
+    /// The type converter with this id, with the ZObject in canonical form
+    Converter(Zid),
+    /// Each element of a typed list
+    List(Box<Conversion>),
+}
+
+impl Conversion {
+    fn is_default(&self) -> bool {
+        match self {
+            Self::Default => true,
+            Self::Converter(_) => false,
+            Self::List(element) => element.is_default(),
+        }
+    }
+
+    /// As read by the entry point: null, the name of the converter, or a list of the conversion of the elements
+    fn to_literal(&self, language: ProgrammingLanguage) -> String {
+        match (self, language) {
+            (Self::Default, ProgrammingLanguage::JavaScript) => "null".to_string(),
+            (Self::Default, ProgrammingLanguage::Python) => "None".to_string(),
+            (Self::Converter(converter), _) => format!("'{}'", converter),
+            (Self::List(element), _) => format!("[{}]", element.to_literal(language)),
+        }
+    }
+}
+
+/// Resources the code of an implementation may use, for each call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CodeLimits {
+    /// Also bound how much the code allocates, as long as it does so in loops
+    pub max_loop_iterations: u64,
+    pub max_recursion: usize,
+    /// In values, for the engines with their own stack
+    pub max_stack_size: usize,
+    /// Function calls, for the engines that can’t count loop iterations
+    pub max_calls: u64,
+    /// For the engines that can be interrupted, and the worker processes
+    pub max_duration: Duration,
+    /// In bytes, for the worker processes
+    pub max_memory: u64,
+}
+
+impl Default for CodeLimits {
+    fn default() -> Self {
+        Self {
+            max_loop_iterations: 10_000_000,
+            max_recursion: 512,
+            max_stack_size: 1024 * 1024,
+            max_calls: 1_000_000,
+            max_duration: Duration::from_secs(10),
+            max_memory: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// Convert an evaluated argument to the value passed to the code
+pub fn to_native(entry: &DataEntry) -> Result<Value, EvaluationErrorKind> {
+    match entry {
+        DataEntry::String(text) => Ok(Value::String(text.clone())),
+        DataEntry::Array(elements) => elements
+            .iter()
+            // the first element is the type of the typed list
+            .skip(1)
+            .enumerate()
+            .map(|(pos, element)| {
+                to_native(element).map_err(|e| e.trace(format!("At array position {}", pos + 1)))
+            })
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        DataEntry::IdMap(map) => match map.get(&zid!(1, 1)) {
+            Some(DataEntry::String(object_type)) if object_type == "Z6" => Ok(Value::String(
+                entry.get_map_entry(&zid!(6, 1))?.get_str()?.to_string(),
+            )),
+            Some(DataEntry::String(object_type)) if object_type == "Z40" => {
+                match entry.get_map_entry(&zid!(40, 1))?.get_str()? {
+                    "Z41" => Ok(Value::Bool(true)),
+                    "Z42" => Ok(Value::Bool(false)),
+                    other => Err(EvaluationErrorKind::Unimplemented(format!(
+                        "the boolean {}",
+                        other
+                    ))),
+                }
+            }
+            _ => serde_json::to_value(entry)
+                .map_err(|e| EvaluationErrorKind::CodeExecution(e.to_string())),
+        },
+    }
+}
+
+/// The canonical form of the ZObject, with strings and references written directly, as given to type converters
+pub fn to_canonical(entry: &DataEntry) -> Value {
+    match entry {
+        DataEntry::String(text) => Value::String(text.clone()),
+        DataEntry::Array(elements) => Value::Array(elements.iter().map(to_canonical).collect()),
+        DataEntry::IdMap(map) => {
+            let object_type = map.get(&zid!(1, 1)).and_then(|t| t.get_str().ok());
+            let simple = match object_type {
+                Some("Z6") => map.get(&zid!(6, 1)),
+                Some("Z9") => map.get(&zid!(9, 1)),
+                _ => None,
+            };
+            match simple {
+                Some(DataEntry::String(text)) if map.len() == 2 => Value::String(text.clone()),
+                _ => Value::Object(
+                    map.iter()
+                        .map(|(key, value)| (key.to_zid(), to_canonical(value)))
+                        .collect(),
+                ),
+            }
+        }
+    }
+}
+
+/// Convert an evaluated argument following the conversion, before the converters run in the engine
+fn to_native_with(
+    entry: &DataEntry,
+    conversion: &Conversion,
+) -> Result<Value, EvaluationErrorKind> {
+    match conversion {
+        Conversion::Default => to_native(entry),
+        Conversion::Converter(_) => Ok(to_canonical(entry)),
+        Conversion::List(element_conversion) => entry
+            .get_array()?
+            .iter()
+            .skip(1)
+            .enumerate()
+            .map(|(pos, element)| {
+                to_native_with(element, element_conversion)
+                    .map_err(|e| e.trace(format!("At array position {}", pos + 1)))
+            })
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+    }
+}
+
+/// Convert the value returned by the code back to a ZObject
+pub fn from_native(value: Value) -> Result<DataEntry, EvaluationErrorKind> {
+    match value {
+        // the unit
+        Value::Null => Ok(DataEntry::String("Z24".to_string())),
+        Value::Bool(value) => Ok(DataEntry::IdMap(BTreeMap::from([
+            (zid!(1, 1), DataEntry::String("Z40".to_string())),
+            (
+                zid!(40, 1),
+                DataEntry::String(if value { "Z41" } else { "Z42" }.to_string()),
+            ),
+        ]))),
+        Value::String(text) => Ok(raw_string_to_object_string(text)),
+        Value::Array(values) => {
+            let elements = values
+                .into_iter()
+                .map(from_native)
+                .collect::<Result<Vec<_>, _>>()?;
+            // the type of the elements, if they all have the same simple one
+            let element_types = elements
+                .iter()
+                .map(|element| element.get_map_entry(&zid!(1, 1)).ok())
+                .collect::<Vec<_>>();
+            let element_type = match element_types.first() {
+                Some(Some(first @ DataEntry::String(_)))
+                    if element_types.iter().all(|other| *other == Some(first)) =>
+                {
+                    (*first).clone()
+                }
+                _ => DataEntry::String("Z1".to_string()),
+            };
+            Ok(DataEntry::Array(
+                std::iter::once(element_type).chain(elements).collect(),
+            ))
+        }
+        Value::Object(_) if value.get("Z1K1").is_some() => serde_json::from_value(value)
+            .map_err(|e| EvaluationErrorKind::CodeExecution(e.to_string())),
+        other => Err(EvaluationErrorKind::CodeExecution(format!(
+            "can’t convert the returned value {} to a ZObject without a type converter",
+            other
+        ))),
+    }
+}
+
+impl Runner {
+    /// How values of the declared type are converted for code in this language. The sources of the converters used are added to `converters`.
+    pub fn conversion(
+        &self,
+        declared_type: &DataEntry,
+        language: ProgrammingLanguage,
+        direction: ConverterDirection,
+        converters: &mut BTreeMap<Zid, String>,
+    ) -> Result<Conversion, EvaluationErrorKind> {
+        match declared_type {
+            DataEntry::String(reference) => {
+                // they have the default serialization, and no converter
+                if matches!(reference.as_str(), "Z1" | "Z6" | "Z40") {
+                    return Ok(Conversion::Default);
+                }
+                let type_id = parse_zid_string(declared_type)?;
+                let type_object = self
+                    .get_persistent_object::<WfType>(&type_id)
+                    .map_err(|e| e.trace_str("getting the declared type"))?
+                    .value;
+                self.type_conversion(type_id, &type_object, language, direction, converters)
+                    .map_err(|e| e.trace(format!("getting the type converters of {}", type_id)))
+            }
+            DataEntry::IdMap(map)
+                if map.get(&zid!(1, 1)).and_then(|t| t.get_str().ok()) == Some("Z7")
+                    && map.get(&zid!(7, 1)).and_then(|t| t.get_str().ok()) == Some("Z881") =>
+            {
+                let element_type = declared_type.get_map_entry(&zid!(881, 1))?;
+                Ok(Conversion::List(Box::new(
+                    self.conversion(element_type, language, direction, converters)
+                        .map_err(|e| e.trace_str("in the type of the list elements"))?,
+                )))
+            }
+            // other generic types and type literals are passed as they are
+            _ => Ok(Conversion::Default),
+        }
+    }
+
+    fn type_conversion(
+        &self,
+        type_id: Zid,
+        type_object: &WfType,
+        language: ProgrammingLanguage,
+        direction: ConverterDirection,
+        converters: &mut BTreeMap<Zid, String>,
+    ) -> Result<Conversion, EvaluationErrorKind> {
+        let list = match direction {
+            ConverterDirection::ToCode => &type_object.type_converters_to_code,
+            ConverterDirection::FromCode => &type_object.type_converters_from_code,
+        }
+        .evaluate(self)?;
+        let list = list.entry.get_array()?;
+        // types without any converter are passed as they are
+        if list.len() <= 1 {
+            return Ok(Conversion::Default);
+        }
+        // the first element is the type of the typed list
+        for (pos, entry) in list.iter().enumerate().skip(1) {
+            let reference = PotentialReference::<WfTypeConverter>::new(entry);
+            let converter = reference
+                .evaluate(self)
+                .map_err(|e| e.trace(format!("at converter position {}", pos)))?;
+            let converter_id = match entry {
+                DataEntry::String(_) => reference.get_reference()?,
+                _ => converter.identity.get_reference()?,
+            };
+            let code = converter.code.evaluate(self)?;
+            if ProgrammingLanguage::parse(code.language).ok() == Some(language) {
+                converters.insert(converter_id, code.source.to_string());
+                return Ok(Conversion::Converter(converter_id));
+            }
+        }
+        Err(EvaluationErrorKind::NoTypeConverter(
+            type_id, direction, language,
+        ))
+    }
+
+    /// Call the function defined by the code with the arguments, in the order of their declaration
+    pub(crate) fn run_code(
+        &self,
+        code: &WfCode,
+        function_id: Zid,
+        arguments: &[DataEntry],
+        argument_types: &[DataEntry],
+        return_type: &DataEntry,
+        context: &EvaluationContext,
+    ) -> Result<DataEntry, EvaluationErrorKind> {
+        let language = ProgrammingLanguage::parse(code.language)
+            .map_err(|e| e.trace_str("getting the programming language"))?;
+        let mut converters = BTreeMap::new();
+        let argument_conversions = argument_types
+            .iter()
+            .enumerate()
+            .map(|(pos, declared_type)| {
+                self.conversion(
+                    declared_type,
+                    language,
+                    ConverterDirection::ToCode,
+                    &mut converters,
+                )
+                .map_err(|e| e.trace(format!("at argument position {}", pos + 1)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let result_conversion = self
+            .conversion(
+                return_type,
+                language,
+                ConverterDirection::FromCode,
+                &mut converters,
+            )
+            .map_err(|e| e.trace_str("for the return type"))?;
+        let arguments = arguments
+            .iter()
+            .zip(&argument_conversions)
+            .map(|(argument, conversion)| to_native_with(argument, conversion))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.trace_str("converting the arguments"))?;
+
+        let (source, function) = if argument_conversions.iter().all(Conversion::is_default)
+            && result_conversion.is_default()
+        {
+            (code.source.to_string(), function_id.to_zid())
+        } else {
+            let (entry_point, function) =
+                language.entry_point(function_id, &argument_conversions, &result_conversion);
+            let mut source = code.source.to_string();
+            for converter in converters.values() {
+                source.push('\n');
+                source.push_str(converter);
+            }
+            source.push('\n');
+            source.push_str(&entry_point);
+            (source, function.to_string())
+        };
+        let result = context
+            .option
+            .code_executor()
+            .execute(
+                language,
+                &source,
+                &function,
+                &arguments,
+                &context.code_limits(),
+                &context.option.cancellation_token,
+            )
+            // the code was stopped by the timeout of the evaluation rather than its own
+            .map_err(|e| context.check_limits().err().unwrap_or(e))?;
+        from_native(result).map_err(|e| e.trace_str("converting the result"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use std::{collections::BTreeMap, sync::Arc};
+
+    use crate::{
+        DataEntry, EvaluationErrorKind, ProgrammingLanguage, Runner, Zid,
+        code::{Conversion, ConverterDirection},
+        test_fixture::{add_type, add_type_converter, fixture_datas},
+    };
+
+    use super::{from_native, to_canonical, to_native};
+
+    #[test]
+    fn test_default_serialization() {
+        let list = serde_json::from_str::<DataEntry>(
+            r#"["Z6", { "Z1K1": "Z6", "Z6K1": "a" }, { "Z1K1": "Z6", "Z6K1": "b" }]"#,
+        )
+        .unwrap();
+        assert_eq!(to_native(&list).unwrap(), json!(["a", "b"]));
+        assert_eq!(from_native(json!(["a", "b"])).unwrap(), list);
+
+        let boolean =
+            serde_json::from_str::<DataEntry>(r#"{ "Z1K1": "Z40", "Z40K1": "Z41" }"#).unwrap();
+        assert_eq!(to_native(&boolean).unwrap(), json!(true));
+        assert_eq!(from_native(json!(true)).unwrap(), boolean);
+
+        // other objects are passed as they are
+        let other = serde_json::from_str::<DataEntry>(r#"{ "Z1K1": "Z10010", "Z10010K1": "Z41" }"#)
+            .unwrap();
+        assert_eq!(from_native(to_native(&other).unwrap()).unwrap(), other);
+        // a mixed list is a list of Z1
+        assert_eq!(
+            from_native(json!(["a", true]))
+                .unwrap()
+                .get_array()
+                .unwrap()[0],
+            DataEntry::String("Z1".to_string())
+        );
+        assert!(from_native(json!(1.5)).is_err());
+    }
+
+    #[test]
+    fn test_type_converters() {
+        let mut datas = fixture_datas();
+        add_type(&mut datas, "Z10040", &["Z10042"], &["Z10043"]);
+        add_type_converter(
+            &mut datas,
+            "Z10042",
+            "Z46",
+            "Z10040",
+            "Z600",
+            "function Z10042( Z10042K1 ) { return BigInt( Z10042K1.Z10040K1 ); }",
+        );
+        add_type_converter(
+            &mut datas,
+            "Z10043",
+            "Z64",
+            "Z10040",
+            "Z600",
+            "function Z10043( Z10043K1 ) { return { Z1K1: 'Z10040', Z10040K1: Z10043K1.toString() }; }",
+        );
+        let runner = Runner::new(Arc::new(datas));
+        let mut converters = BTreeMap::new();
+        let declared_type = DataEntry::String("Z10040".to_string());
+        assert_eq!(
+            runner
+                .conversion(
+                    &declared_type,
+                    ProgrammingLanguage::JavaScript,
+                    ConverterDirection::ToCode,
+                    &mut converters
+                )
+                .unwrap(),
+            Conversion::Converter(zid!(10042))
+        );
+        let list_type = serde_json::from_str::<DataEntry>(
+            r#"{ "Z1K1": "Z7", "Z7K1": "Z881", "Z881K1": "Z10040" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            runner
+                .conversion(
+                    &list_type,
+                    ProgrammingLanguage::JavaScript,
+                    ConverterDirection::FromCode,
+                    &mut converters
+                )
+                .unwrap(),
+            Conversion::List(Box::new(Conversion::Converter(zid!(10043))))
+        );
+        assert_eq!(
+            converters.keys().copied().collect::<Vec<_>>(),
+            [zid!(10042), zid!(10043)]
+        );
+        // no converter for that language
+        assert!(matches!(
+            runner
+                .conversion(
+                    &declared_type,
+                    ProgrammingLanguage::Python,
+                    ConverterDirection::ToCode,
+                    &mut converters
+                )
+                .unwrap_err()
+                .root(),
+            EvaluationErrorKind::NoTypeConverter(
+                _,
+                ConverterDirection::ToCode,
+                ProgrammingLanguage::Python
+            )
+        ));
+
+        // converters get canonical ZObjects
+        let value = serde_json::from_str::<DataEntry>(
+            r#"{ "Z1K1": "Z10040", "Z10040K1": { "Z1K1": "Z6", "Z6K1": "5" } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            to_canonical(&value),
+            json!({ "Z1K1": "Z10040", "Z10040K1": "5" })
+        );
+    }
+}
@@ -1,54 +1,203 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
-use crate::{DataEntry, EvaluationErrorKind, Zid};
+use crate::{DataEntry, EvaluationErrorKind, Zid, parse_tool::parse_string_permissive};
 
+const Z1K1: Zid = Zid::from_u64s_panic(Some(1), Some(1));
+const Z18K1: Zid = Zid::from_u64s_panic(Some(18), Some(1));
+
+/// The keys of the arguments declared by an unevaluated Z8 literal
+pub fn declared_argument_keys(function: &DataEntry) -> Result<BTreeSet<Zid>, EvaluationErrorKind> {
+    let mut result = BTreeSet::new();
+    // the first element is the type of the typed list
+    for (pos, argument) in function
+        .get_map_entry(&zid!(8, 1))?
+        .get_array()?
+        .iter()
+        .enumerate()
+        .skip(1)
+    {
+        let key = parse_string_permissive(argument.get_map_entry(&zid!(17, 2))?)
+            .map_err(|e| e.trace(format!("at argument position {}", pos)))?;
+        result.insert(
+            Zid::from_zid(key)
+                .map_err(EvaluationErrorKind::ParseZID)
+                .map_err(|e| e.trace(format!("at argument position {}", pos)))?,
+        );
+    }
+    Ok(result)
+}
+
+/// Return the literal key of a Z18, or None if the key is itself to be computed
+fn literal_argument_reference(map: &BTreeMap<Zid, DataEntry>) -> Option<Zid> {
+    if map.get(&Z1K1)?.get_str().ok()? != "Z18" {
+        return None;
+    }
+    Zid::from_zid(parse_string_permissive(map.get(&Z18K1)?).ok()?).ok()
+}
+
+fn is_function_literal(map: &BTreeMap<Zid, DataEntry>) -> bool {
+    matches!(map.get(&Z1K1), Some(DataEntry::String(t)) if t == "Z8")
+}
+
+/// The key a function literal gets once evaluated, holding the index of the scope it is closed over in the evaluation context.
+/// It isn’t part of the Wikifunctions model, and is removed from the values leaving the evaluation.
+pub(crate) const ZID_CLOSURE_SCOPE: Zid = zid!(8, 6);
+
+/// The index of the captured scope, if the map is an evaluated function literal
+pub(crate) fn closure_scope(map: &BTreeMap<Zid, DataEntry>) -> Option<usize> {
+    if !is_function_literal(map) {
+        return None;
+    }
+    map.get(&ZID_CLOSURE_SCOPE)?.get_str().ok()?.parse().ok()
+}
+
+/// Whether an evaluated function literal is part of the value
+pub(crate) fn contains_closure(entry: &DataEntry) -> bool {
+    match entry {
+        DataEntry::IdMap(map) => closure_scope(map).is_some() || map.values().any(contains_closure),
+        DataEntry::Array(array) => array.iter().any(contains_closure),
+        DataEntry::String(_) => false,
+    }
+}
+
+/// Collect the argument references that are not bound by the function literals they are inside of. Evaluated function literals have none, as they resolve them in their own scope.
+pub fn collect_free_arguments(
+    entry: &DataEntry,
+    bound: &BTreeSet<Zid>,
+    free: &mut BTreeSet<Zid>,
+) -> Result<(), EvaluationErrorKind> {
+    match entry {
+        DataEntry::IdMap(map) => {
+            if closure_scope(map).is_some() {
+                return Ok(());
+            }
+            if let Some(key) = literal_argument_reference(map) {
+                if !bound.contains(&key) {
+                    free.insert(key);
+                }
+                return Ok(());
+            }
+            if is_function_literal(map) {
+                let mut inner_bound = bound.clone();
+                inner_bound.extend(declared_argument_keys(entry)?);
+                for value in map.values() {
+                    collect_free_arguments(value, &inner_bound, free)?;
+                }
+            } else {
+                for value in map.values() {
+                    collect_free_arguments(value, bound, free)?;
+                }
+            }
+            Ok(())
+        }
+        DataEntry::Array(array) => {
+            for value in array {
+                collect_free_arguments(value, bound, free)?;
+            }
+            Ok(())
+        }
+        DataEntry::String(_) => Ok(()),
+    }
+}
+
+/// Replace the argument references that are not bound by the function literals they are inside of with the given values. Evaluated function literals are kept as they are.
+pub fn replace_free_arguments(
+    entry: &DataEntry,
+    bound: &BTreeSet<Zid>,
+    values: &BTreeMap<Zid, DataEntry>,
+) -> Result<DataEntry, EvaluationErrorKind> {
+    match entry {
+        DataEntry::IdMap(map) => {
+            if closure_scope(map).is_some() {
+                return Ok(entry.clone());
+            }
+            if let Some(key) = literal_argument_reference(map) {
+                if !bound.contains(&key)
+                    && let Some(value) = values.get(&key)
+                {
+                    return Ok(value.clone());
+                }
+                return Ok(entry.clone());
+            }
+            let inner_bound = if is_function_literal(map) {
+                let mut inner_bound = bound.clone();
+                inner_bound.extend(declared_argument_keys(entry)?);
+                inner_bound
+            } else {
+                bound.clone()
+            };
+            let mut new_map = BTreeMap::new();
+            for (key, value) in map.iter() {
+                new_map.insert(
+                    *key,
+                    replace_free_arguments(value, &inner_bound, values)
+                        .map_err(|e| e.trace(format!("Inside {}", key)))?,
+                );
+            }
+            Ok(DataEntry::IdMap(new_map))
+        }
+        DataEntry::Array(array) => {
+            let mut new_array = Vec::new();
+            for (pos, value) in array.iter().enumerate() {
+                new_array.push(
+                    replace_free_arguments(value, bound, values)
+                        .map_err(|e| e.trace(format!("Position {} in the array", pos)))?,
+                );
+            }
+            Ok(DataEntry::Array(new_array))
+        }
+        DataEntry::String(v) => Ok(DataEntry::String(v.clone())),
+    }
+}
+
+/// Replace every argument reference of the composition with the given argument, unevaluated.
+/// Arguments are now bound in a scope, as thunks evaluated at most once, and resolved where they are used.
+#[deprecated(note = "arguments are bound in a `Scope` instead of being substituted")]
 pub fn recurse_and_replace_placeholder(
     source_entry: &DataEntry,
     to_replace: &BTreeMap<Zid, &DataEntry>, // the function call unwraped
 ) -> Result<DataEntry, EvaluationErrorKind> {
-    const Z1K1: Zid = Zid::from_u64s_panic(Some(1), Some(1));
-    const Z18K1: Zid = Zid::from_u64s_panic(Some(18), Some(1));
-
     match source_entry {
         DataEntry::IdMap(map) => {
-            if let Some(object_type) = map.get(&Z1K1) {
-                //TODO: I think in some case this might be a function call itself
-                if object_type.get_str().unwrap() == "Z18" {
-                    if let Some(key) = map.get(&Z18K1) {
-                        let ref_to_use_to_replace = Zid::from_zid(
-                            key.get_str()
-                                .map_err(|e| e.trace("inside a Z18K1".to_string()))?,
-                        )
-                        .map_err(EvaluationErrorKind::ParseZID)
-                        .map_err(|e| e.trace("inside a Z18K1".to_string()))?;
-                        //TODO: what to do in this case? Report the error. How to format it? idk
-                        let new_entry = to_replace.get(&ref_to_use_to_replace).unwrap();
-                        return Ok((*new_entry).clone());
-                    } else {
-                        return Err(EvaluationErrorKind::MissingKey(Z18K1));
-                    }
-                }
+            if let Some(object_type) = map.get(&Z1K1)
+                && object_type.get_str()? == "Z18"
+            {
+                let key = map
+                    .get(&Z18K1)
+                    .ok_or(EvaluationErrorKind::MissingKey(Z18K1))?;
+                let ref_to_use_to_replace = Zid::from_zid(
+                    key.get_str()
+                        .map_err(|e| e.trace("inside a Z18K1".to_string()))?,
+                )
+                .map_err(EvaluationErrorKind::ParseZID)
+                .map_err(|e| e.trace("inside a Z18K1".to_string()))?;
+                return to_replace
+                    .get(&ref_to_use_to_replace)
+                    .map(|new_entry| (*new_entry).clone())
+                    .ok_or(EvaluationErrorKind::UnboundArgument(ref_to_use_to_replace));
             }
 
             let mut new_map = BTreeMap::new();
             for (key, value) in map.iter() {
+                #[allow(deprecated)]
                 new_map.insert(
                     key.to_owned(),
                     recurse_and_replace_placeholder(value, to_replace)
                         .map_err(|e| e.trace(format!("Inside {}", key)))?,
                 );
             }
-            return Ok(DataEntry::IdMap(new_map));
+            Ok(DataEntry::IdMap(new_map))
         }
         DataEntry::Array(array) => {
             let mut new_array = Vec::new();
             for (pos, value) in array.iter().enumerate() {
+                #[allow(deprecated)]
                 new_array.push(
                     recurse_and_replace_placeholder(value, to_replace)
                         .map_err(|e| e.trace(format!("Position {} in the array", pos)))?,
                 );
             }
-            return Ok(DataEntry::Array(new_array));
+            Ok(DataEntry::Array(new_array))
         }
         DataEntry::String(v) => Ok(DataEntry::String(v.clone())),
     }
@@ -1,5 +1,5 @@
 use map_macro::btree_map;
-use serde::{Deserialize, de::Visitor};
+use serde::{Deserialize, Serialize, de::Visitor};
 
 use crate::{
     EvaluationErrorKind, Runner, Zid,
@@ -21,21 +21,21 @@ impl<'de> Visitor<'de> for DataEntryVisitor {
     where
         E: serde::de::Error,
     {
-        return self.visit_string(v.to_string());
+        self.visit_string(v.to_string())
     }
 
     fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
     where
         E: serde::de::Error,
     {
-        return Ok(DataEntry::String(v));
+        Ok(DataEntry::String(v))
     }
 
     fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
     where
         E: serde::de::Error,
     {
-        return self.visit_string(v.to_string());
+        self.visit_string(v.to_string())
     }
 
     fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
@@ -63,7 +63,7 @@ impl<'de> Visitor<'de> for DataEntryVisitor {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum DataEntry {
     String(String),
     IdMap(BTreeMap<Zid, DataEntry>),
@@ -74,7 +74,7 @@ impl DataEntry {
     pub fn get_map_entry(&self, reference: &Zid) -> Result<&DataEntry, EvaluationErrorKind> {
         match self.get_map()?.get(reference) {
             Some(v) => Ok(v),
-            None => Err(EvaluationErrorKind::MissingKey(reference.clone())),
+            None => Err(EvaluationErrorKind::MissingKey(*reference)),
         }
     }
 
@@ -89,7 +89,7 @@ impl DataEntry {
         &'l self,
         reference: &'l Zid,
     ) -> Result<PotentialReference<'l, T>, EvaluationErrorKind> {
-        Ok(PotentialReference::parse(self.get_map_entry(reference)?)?)
+        PotentialReference::parse(self.get_map_entry(reference)?)
     }
 
     pub fn get_map_potential_reference_option<'l, T: WfParse<'l>>(
@@ -124,7 +124,33 @@ impl DataEntry {
         }
     }
 
+    /// Return the canonical form of this entry: Z6 strings and Z9 references written as objects are collapsed into plain strings, so two representations of the same value compare equal
+    pub fn normalize(&self) -> DataEntry {
+        match self {
+            Self::String(value) => Self::String(value.clone()),
+            Self::IdMap(map) => {
+                if map.len() == 2
+                    && let Some(Self::String(object_type)) = map.get(&zid!(1, 1))
+                {
+                    let wrapped_key = match object_type.as_str() {
+                        "Z6" => Some(zid!(6, 1)),
+                        "Z9" => Some(zid!(9, 1)),
+                        _ => None,
+                    };
+                    if let Some(wrapped_key) = wrapped_key
+                        && let Some(Self::String(value)) = map.get(&wrapped_key)
+                    {
+                        return Self::String(value.clone());
+                    }
+                }
+                Self::IdMap(map.iter().map(|(k, v)| (*k, v.normalize())).collect())
+            }
+            Self::Array(array) => Self::Array(array.iter().map(Self::normalize).collect()),
+        }
+    }
+
     /// transform the representation into something the running code can parse. Take care of typed list, that are only vec for the json format!
+    #[allow(clippy::only_used_in_recursion)]
     pub fn reify(&self, runner: &Runner) -> Result<DataEntry, EvaluationErrorKind> {
         //TODO: should we follow reference here? Confused...
         /*let self_pointed = PotentialReference::<WfUntyped<'_>>::new(self)
@@ -184,6 +210,19 @@ impl<'de> Deserialize<'de> for DataEntry {
     }
 }
 
+impl Serialize for DataEntry {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Self::String(value) => serializer.serialize_str(value),
+            Self::IdMap(map) => serializer.collect_map(map.iter().map(|(k, v)| (k.to_zid(), v))),
+            Self::Array(array) => serializer.collect_seq(array),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use map_macro::btree_map;
@@ -239,4 +278,48 @@ mod tests {
 
         assert!(serde_json::from_str::<DataEntry>("{1: \"Z2\"}").is_err());
     }
+
+    #[test]
+    fn test_normalize() {
+        let expanded = serde_json::from_str::<DataEntry>(
+            "{
+                \"Z1K1\": { \"Z1K1\": \"Z9\", \"Z9K1\": \"Z11\" },
+                \"Z11K1\": \"Z1002\",
+                \"Z11K2\": { \"Z1K1\": \"Z6\", \"Z6K1\": \"text\" }
+            }",
+        )
+        .unwrap();
+        let canonical = serde_json::from_str::<DataEntry>(
+            "{
+                \"Z1K1\": \"Z11\",
+                \"Z11K1\": \"Z1002\",
+                \"Z11K2\": \"text\"
+            }",
+        )
+        .unwrap();
+        assert_eq!(expanded.normalize(), canonical);
+        assert_eq!(canonical.normalize(), canonical);
+
+        assert_eq!(
+            serde_json::from_str::<DataEntry>("[{ \"Z1K1\": \"Z9\", \"Z9K1\": \"Z6\" }, \"a\"]")
+                .unwrap()
+                .normalize(),
+            DataEntry::Array(vec![
+                DataEntry::String("Z6".to_string()),
+                DataEntry::String("a".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let entry = serde_json::from_str::<DataEntry>(
+            r#"{ "Z1K1": "Z7", "Z7K1": "Z873", "Z873K2": ["Z40", { "Z1K1": "Z40", "Z40K1": "Z41" }] }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::from_str::<DataEntry>(&serde_json::to_string(&entry).unwrap()).unwrap(),
+            entry
+        );
+    }
 }
@@ -0,0 +1,783 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Debug, Write as _},
+    io::{BufRead, Write},
+    sync::{Arc, Mutex},
+};
+
+use serde_json::json;
+
+use crate::{
+    DataEntry, EvaluationErrorKind, Runner, RunnerOption, Zid,
+    composition_tool::replace_free_arguments,
+    parse_tool::{WfFunctionCall, WfParse},
+    tracer::OpenCall,
+};
+
+/// A function call the debugger stops at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Breakpoint {
+    /// Any call to this function
+    Function(Zid),
+    /// Any call run with this implementation
+    Implementation(Zid),
+}
+
+/// How to resume the evaluation after a stop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugCommand {
+    /// Stop at the next function call
+    StepInto,
+    /// Stop at the next function call that isn’t performed by this one
+    StepOver,
+    /// Stop at the next function call performed after this one returned
+    StepOut,
+    /// Only stop at breakpoints
+    Continue,
+    /// Cancel the evaluation
+    Abort,
+}
+
+impl DebugCommand {
+    /// Parse the name of the command, or its one letter abbreviation
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "step_into" | "s" => Some(Self::StepInto),
+            "step_over" | "n" => Some(Self::StepOver),
+            "step_out" | "o" => Some(Self::StepOut),
+            "continue" | "c" => Some(Self::Continue),
+            "abort" | "q" => Some(Self::Abort),
+            _ => None,
+        }
+    }
+}
+
+/// What an evaluation does until the next stop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DebugMode {
+    StepInto,
+    /// Stop at a call at this depth or above
+    StepOver(usize),
+    /// Stop at a call above this depth
+    StepOut(usize),
+    Continue,
+}
+
+/// The value bound to an argument
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentBinding {
+    Evaluated(DataEntry),
+    /// Not evaluated yet (or its value isn’t kept), with its expression
+    Unevaluated(DataEntry),
+}
+
+/// A function call being evaluated
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugFrame {
+    pub function: String,
+    pub implementation: Option<Zid>,
+    pub arguments: BTreeMap<Zid, ArgumentBinding>,
+    /// The composition run, with the arguments evaluated so far substituted. None for other kinds of implementation.
+    pub composition: Option<DataEntry>,
+}
+
+impl DebugFrame {
+    fn new(call: &OpenCall) -> Self {
+        let arguments = call
+            .arguments
+            .iter()
+            .map(|(key, thunk)| {
+                let binding = match (thunk.get_value(), thunk.get_expression()) {
+                    (Some(value), _) => ArgumentBinding::Evaluated(value.clone()),
+                    (None, Some((expression, _))) => {
+                        ArgumentBinding::Unevaluated(expression.clone())
+                    }
+                    (None, None) => unreachable!("a thunk without expression should have a value"),
+                };
+                (*key, binding)
+            })
+            .collect::<BTreeMap<_, _>>();
+        let values = arguments
+            .iter()
+            .filter_map(|(key, binding)| match binding {
+                ArgumentBinding::Evaluated(value) => Some((*key, value.clone())),
+                ArgumentBinding::Unevaluated(_) => None,
+            })
+            .collect();
+        let composition = call.composition.as_ref().map(|composition| {
+            replace_free_arguments(composition, &BTreeSet::new(), &values)
+                .unwrap_or_else(|_| composition.clone())
+        });
+        Self {
+            function: call.function.clone(),
+            implementation: call.implementation,
+            arguments,
+            composition,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "function": self.function,
+            "implementation": self.implementation.map(|implementation| implementation.to_zid()),
+            "arguments": self
+                .arguments
+                .iter()
+                .map(|(key, binding)| {
+                    let binding = match binding {
+                        ArgumentBinding::Evaluated(value) => json!({ "value": value }),
+                        ArgumentBinding::Unevaluated(expression) => {
+                            json!({ "unevaluated": expression })
+                        }
+                    };
+                    (key.to_zid(), binding)
+                })
+                .collect::<serde_json::Map<_, _>>(),
+            "composition": self.composition,
+        })
+    }
+}
+
+/// The state of the evaluation when the debugger stops, before the body of a function call is run
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugStop {
+    /// The calls being evaluated, the outermost first. The last one is the call being entered.
+    pub frames: Vec<DebugFrame>,
+    /// The depth of the call being entered
+    pub depth: usize,
+}
+
+impl DebugStop {
+    /// For the commands run between evaluations, that have no calls to show
+    fn outside_calls() -> Self {
+        Self {
+            frames: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    pub(crate) fn new(open_calls: &[OpenCall], depth: usize) -> Self {
+        Self {
+            frames: open_calls.iter().map(DebugFrame::new).collect(),
+            depth,
+        }
+    }
+
+    pub fn current(&self) -> &DebugFrame {
+        self.frames
+            .last()
+            .expect("the debugger stops at a function call")
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "depth": self.depth,
+            "frames": self.frames.iter().map(DebugFrame::to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    /// The current call, its arguments and composition
+    pub fn to_text(&self) -> String {
+        let compact = |value: &DataEntry| serde_json::to_string(value).unwrap_or_default();
+        let current = self.current();
+        let mut output = format!("stopped at {}", current.function);
+        if let Some(implementation) = current.implementation {
+            let _ = write!(output, " ({})", implementation);
+        }
+        let _ = writeln!(output, ", depth {}", self.depth);
+        for (key, binding) in &current.arguments {
+            let _ = match binding {
+                ArgumentBinding::Evaluated(value) => {
+                    writeln!(output, "  {} = {}", key, compact(value))
+                }
+                ArgumentBinding::Unevaluated(expression) => {
+                    writeln!(output, "  {} = unevaluated {}", key, compact(expression))
+                }
+            };
+        }
+        if let Some(composition) = &current.composition {
+            let _ = writeln!(output, "  composition: {}", compact(composition));
+        }
+        output
+    }
+}
+
+/// Decide how to resume each time the debugger stops. Called on the thread running the evaluation, that waits for the answer.
+pub trait DebugHandler: Send + Sync {
+    fn stopped(&self, stop: &DebugStop, debugger: &Debugger) -> DebugCommand;
+}
+
+impl<F: Fn(&DebugStop, &Debugger) -> DebugCommand + Send + Sync> DebugHandler for F {
+    fn stopped(&self, stop: &DebugStop, debugger: &Debugger) -> DebugCommand {
+        self(stop, debugger)
+    }
+}
+
+/// Stop the evaluations whose RunnerOption holds it at function calls. Clones share the same breakpoints.
+#[derive(Clone)]
+pub struct Debugger {
+    breakpoints: Arc<Mutex<BTreeSet<Breakpoint>>>,
+    stop_on_entry: bool,
+    handler: Arc<dyn DebugHandler>,
+}
+
+impl Debug for Debugger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Debugger")
+            .field("breakpoints", &self.get_breakpoints())
+            .field("stop_on_entry", &self.stop_on_entry)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Debugger {
+    pub fn new(handler: impl DebugHandler + 'static) -> Self {
+        Self {
+            breakpoints: Arc::default(),
+            stop_on_entry: false,
+            handler: Arc::new(handler),
+        }
+    }
+
+    /// Also stop at the first call of each evaluation
+    pub fn with_stop_on_entry(mut self) -> Self {
+        self.stop_on_entry = true;
+        self
+    }
+
+    pub fn add_breakpoint(&self, breakpoint: Breakpoint) {
+        self.breakpoints.lock().unwrap().insert(breakpoint);
+    }
+
+    pub fn remove_breakpoint(&self, breakpoint: &Breakpoint) {
+        self.breakpoints.lock().unwrap().remove(breakpoint);
+    }
+
+    pub fn get_breakpoints(&self) -> BTreeSet<Breakpoint> {
+        self.breakpoints.lock().unwrap().clone()
+    }
+
+    pub(crate) fn initial_mode(&self) -> DebugMode {
+        if self.stop_on_entry {
+            DebugMode::StepInto
+        } else {
+            DebugMode::Continue
+        }
+    }
+
+    /// Whether to stop at the call, entered at the given depth
+    pub(crate) fn should_stop(&self, call: &OpenCall, depth: usize, mode: DebugMode) -> bool {
+        let stepped = match mode {
+            DebugMode::StepInto => true,
+            DebugMode::StepOver(stop_depth) => depth <= stop_depth,
+            DebugMode::StepOut(stop_depth) => depth < stop_depth,
+            DebugMode::Continue => false,
+        };
+        if stepped {
+            return true;
+        }
+        let breakpoints = self.breakpoints.lock().unwrap();
+        call.function_id
+            .is_some_and(|function| breakpoints.contains(&Breakpoint::Function(function)))
+            || call.implementation.is_some_and(|implementation| {
+                breakpoints.contains(&Breakpoint::Implementation(implementation))
+            })
+    }
+
+    pub(crate) fn stop(&self, stop: &DebugStop) -> DebugCommand {
+        self.handler.stopped(stop, self)
+    }
+}
+
+/// The protocol of a LineDebugHandler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugProtocol {
+    /// Human readable stops, and commands like "s", "n", "b Z10001" (see the "help" command)
+    Text,
+    /// A JSON-RPC 2.0 "stopped" notification per stop, answered with one request per line
+    JsonRpc,
+}
+
+const TEXT_HELP: &str = "s: step into, n: step over, o: step out, c: continue, q: abort, \
+    b ZID: break on a function, bi ZID: break on an implementation, d ZID: delete the breakpoints on a ZID, \
+    bt: show the calls being evaluated, l: list the breakpoints\n";
+
+/// Drive the debugger with lines of text read from an input, like the standard input. The evaluation continues once the input is closed.
+pub struct LineDebugHandler {
+    protocol: DebugProtocol,
+    io: Mutex<(Box<dyn BufRead + Send>, Box<dyn Write + Send>)>,
+}
+
+impl LineDebugHandler {
+    pub fn new(
+        protocol: DebugProtocol,
+        input: impl BufRead + Send + 'static,
+        output: impl Write + Send + 'static,
+    ) -> Self {
+        Self {
+            protocol,
+            io: Mutex::new((Box::new(input), Box::new(output))),
+        }
+    }
+
+    fn text_command(
+        line: &str,
+        stop: &DebugStop,
+        debugger: &Debugger,
+    ) -> Result<String, DebugCommand> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or("");
+        if let Some(command) = DebugCommand::parse(name) {
+            return Err(command);
+        }
+        let zid = words.next().map(Zid::from_zid);
+        Ok(match (name, zid) {
+            ("b", Some(Ok(zid))) => {
+                debugger.add_breakpoint(Breakpoint::Function(zid));
+                format!("breaking on calls to {}\n", zid)
+            }
+            ("bi", Some(Ok(zid))) => {
+                debugger.add_breakpoint(Breakpoint::Implementation(zid));
+                format!("breaking on calls run with {}\n", zid)
+            }
+            ("d", Some(Ok(zid))) => {
+                debugger.remove_breakpoint(&Breakpoint::Function(zid));
+                debugger.remove_breakpoint(&Breakpoint::Implementation(zid));
+                format!("removed the breakpoints on {}\n", zid)
+            }
+            ("b" | "bi" | "d", _) => "expected a ZID\n".to_string(),
+            ("l", _) => {
+                let mut output = String::new();
+                for breakpoint in debugger.get_breakpoints() {
+                    let _ = writeln!(output, "{:?}", breakpoint);
+                }
+                output
+            }
+            ("bt", _) => {
+                let mut output = String::new();
+                for (depth, frame) in stop.frames.iter().enumerate() {
+                    let _ = writeln!(output, "#{} {}", depth, frame.function);
+                }
+                output
+            }
+            _ => TEXT_HELP.to_string(),
+        })
+    }
+
+    fn json_rpc_command(
+        line: &str,
+        stop: &DebugStop,
+        debugger: &Debugger,
+    ) -> (serde_json::Value, Option<DebugCommand>) {
+        let error = |id: &serde_json::Value, code: i64, message: &str| json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } });
+        let Ok(request) = serde_json::from_str::<serde_json::Value>(line) else {
+            return (error(&serde_json::Value::Null, -32700, "parse error"), None);
+        };
+        let id = request["id"].clone();
+        let Some(method) = request["method"].as_str() else {
+            return (error(&id, -32600, "invalid request"), None);
+        };
+        let breakpoint = || {
+            let params = &request["params"];
+            match (
+                params["function"].as_str(),
+                params["implementation"].as_str(),
+            ) {
+                (Some(function), None) => Zid::from_zid(function).ok().map(Breakpoint::Function),
+                (None, Some(implementation)) => Zid::from_zid(implementation)
+                    .ok()
+                    .map(Breakpoint::Implementation),
+                _ => None,
+            }
+        };
+        let result =
+            |result: serde_json::Value| json!({ "jsonrpc": "2.0", "id": id, "result": result });
+        match method {
+            "set_breakpoint" | "remove_breakpoint" => match breakpoint() {
+                Some(breakpoint) => {
+                    if method == "set_breakpoint" {
+                        debugger.add_breakpoint(breakpoint);
+                    } else {
+                        debugger.remove_breakpoint(&breakpoint);
+                    }
+                    (result(serde_json::Value::Null), None)
+                }
+                None => (
+                    error(&id, -32602, "expected a function or an implementation ZID"),
+                    None,
+                ),
+            },
+            "stack" => (result(stop.to_json()), None),
+            _ => match DebugCommand::parse(method).filter(|_| method.len() > 1) {
+                Some(command) => (result(serde_json::Value::Null), Some(command)),
+                None => (error(&id, -32601, "method not found"), None),
+            },
+        }
+    }
+}
+
+impl DebugHandler for LineDebugHandler {
+    fn stopped(&self, stop: &DebugStop, debugger: &Debugger) -> DebugCommand {
+        let mut io = self.io.lock().unwrap();
+        let (input, output) = &mut *io;
+        let _ = match self.protocol {
+            DebugProtocol::Text => write!(output, "{}(debug) ", stop.to_text()),
+            DebugProtocol::JsonRpc => writeln!(
+                output,
+                "{}",
+                json!({ "jsonrpc": "2.0", "method": "stopped", "params": stop.to_json() })
+            ),
+        };
+        loop {
+            let _ = output.flush();
+            let mut line = String::new();
+            match input.read_line(&mut line) {
+                Ok(0) | Err(_) => return DebugCommand::Continue,
+                Ok(_) => (),
+            }
+            match self.protocol {
+                DebugProtocol::Text => match Self::text_command(line.trim(), stop, debugger) {
+                    Ok(answer) => {
+                        let _ = write!(output, "{}(debug) ", answer);
+                    }
+                    Err(command) => return command,
+                },
+                DebugProtocol::JsonRpc => {
+                    let (answer, command) = Self::json_rpc_command(&line, stop, debugger);
+                    let _ = writeln!(output, "{}", answer);
+                    if let Some(command) = command {
+                        let _ = output.flush();
+                        return command;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The handler of the debugger of a read-eval-print loop, that reads from the same input
+struct SharedHandler(Arc<LineDebugHandler>);
+
+impl DebugHandler for SharedHandler {
+    fn stopped(&self, stop: &DebugStop, debugger: &Debugger) -> DebugCommand {
+        self.0.stopped(stop, debugger)
+    }
+}
+
+impl LineDebugHandler {
+    /// Evaluate the function calls read from the input until it is closed, each in a debugger driven from the same input. It starts with the breakpoints of the debugger of the option, if any.
+    /// With the Text protocol, a line is a call in JSON, prefixed with "s " to stop at its first call, or a breakpoint command ("b", "bi", "d" or "l"). The result is written in JSON.
+    /// With JsonRpc, an "evaluate" request has the call as "call" param, and an optional "stop_on_entry". It is answered with the result, or an error with the message of the evaluation error.
+    pub fn repl(self, runner: &Runner, option: &RunnerOption) {
+        let protocol = self.protocol;
+        let handler = Arc::new(self);
+        let debugger = Debugger::new(SharedHandler(handler.clone()));
+        for breakpoint in option.debugger.iter().flat_map(Debugger::get_breakpoints) {
+            debugger.add_breakpoint(breakpoint);
+        }
+        let evaluate = |call: DataEntry, stop_on_entry: bool| {
+            let debugger = if stop_on_entry {
+                debugger.clone().with_stop_on_entry()
+            } else {
+                debugger.clone()
+            };
+            let option = RunnerOption {
+                debugger: Some(debugger),
+                ..option.clone()
+            };
+            runner.run_function_call(&WfFunctionCall::parse(&call)?, &option)
+        };
+        loop {
+            let mut line = String::new();
+            {
+                let mut io = handler.io.lock().unwrap();
+                let (input, output) = &mut *io;
+                if protocol == DebugProtocol::Text {
+                    let _ = write!(output, "> ");
+                }
+                let _ = output.flush();
+                match input.read_line(&mut line) {
+                    Ok(0) | Err(_) => return,
+                    Ok(_) => (),
+                }
+            }
+            // the input is unlocked while evaluating, for the debugger to read its commands
+            let answer = match protocol {
+                DebugProtocol::Text => Self::repl_text(line.trim(), &evaluate, &debugger),
+                DebugProtocol::JsonRpc => Self::repl_json_rpc(&line, &evaluate, &debugger),
+            };
+            let mut io = handler.io.lock().unwrap();
+            let _ = write!(io.1, "{}", answer);
+        }
+    }
+
+    /// The answer to a line of the read-eval-print loop with the Text protocol
+    fn repl_text(
+        line: &str,
+        evaluate: &dyn Fn(DataEntry, bool) -> Result<DataEntry, EvaluationErrorKind>,
+        debugger: &Debugger,
+    ) -> String {
+        let (stop_on_entry, call) = match line.strip_prefix("s ") {
+            Some(call) => (true, call.trim_start()),
+            None => (false, line),
+        };
+        if line.is_empty() {
+            return String::new();
+        }
+        if !call.starts_with('{') {
+            return match Self::text_command(line, &DebugStop::outside_calls(), debugger) {
+                Ok(answer) => answer,
+                Err(_) => "not stopped in the debugger\n".to_string(),
+            };
+        }
+        match serde_json::from_str::<DataEntry>(call) {
+            Ok(call) => match evaluate(call, stop_on_entry) {
+                Ok(value) => format!("{}\n", json!(value)),
+                Err(error) => format!("error: {}\n", error),
+            },
+            Err(error) => format!("invalid call: {}\n", error),
+        }
+    }
+
+    /// The answer to a line of the read-eval-print loop with the JsonRpc protocol
+    fn repl_json_rpc(
+        line: &str,
+        evaluate: &dyn Fn(DataEntry, bool) -> Result<DataEntry, EvaluationErrorKind>,
+        debugger: &Debugger,
+    ) -> String {
+        let request = serde_json::from_str::<serde_json::Value>(line).unwrap_or_default();
+        let id = &request["id"];
+        let error = |code: i64, message: String| json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } });
+        let answer = if request["method"] == "evaluate" {
+            let params = &request["params"];
+            let stop_on_entry = params["stop_on_entry"].as_bool().unwrap_or(false);
+            match serde_json::from_value::<DataEntry>(params["call"].clone()) {
+                Ok(call) => match evaluate(call, stop_on_entry) {
+                    Ok(value) => json!({ "jsonrpc": "2.0", "id": id, "result": value }),
+                    Err(evaluation_error) => error(-32000, evaluation_error.to_string()),
+                },
+                Err(parse_error) => error(-32602, format!("invalid call: {}", parse_error)),
+            }
+        } else {
+            match Self::json_rpc_command(line, &DebugStop::outside_calls(), debugger) {
+                (_, Some(_)) => error(-32600, "not stopped in the debugger".to_string()),
+                (answer, None) => answer,
+            }
+        };
+        format!("{}\n", answer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Cursor, Write},
+        sync::{Arc, Mutex},
+    };
+
+    use crate::{
+        Breakpoint, DataEntry, DebugCommand, DebugProtocol, DebugStop, Debugger,
+        EvaluationErrorKind, LineDebugHandler, RunnerOption, Zid,
+        parse_tool::{WfFunctionCall, WfParse},
+        test_fixture::{first_not_loop_call, fixture_runner},
+    };
+
+    fn run_debugged(debugger: Debugger) -> Result<DataEntry, EvaluationErrorKind> {
+        let option = RunnerOption {
+            debugger: Some(debugger),
+            ..Default::default()
+        };
+        fixture_runner().run_function_call(
+            &WfFunctionCall::parse(&first_not_loop_call()).unwrap(),
+            &option,
+        )
+    }
+
+    /// Answer each stop with the command, and return the function and depth of the stops
+    fn stops_with(
+        debugger: impl FnOnce(Arc<Mutex<Vec<DebugStop>>>) -> Debugger,
+    ) -> Vec<(String, usize)> {
+        let stops = Arc::new(Mutex::new(Vec::new()));
+        let _ = run_debugged(debugger(stops.clone()));
+        let stops = stops.lock().unwrap();
+        stops
+            .iter()
+            .map(|stop| (stop.current().function.clone(), stop.depth))
+            .collect()
+    }
+
+    fn recording(stops: Arc<Mutex<Vec<DebugStop>>>, command: DebugCommand) -> Debugger {
+        Debugger::new(move |stop: &DebugStop, _: &Debugger| {
+            stops.lock().unwrap().push(stop.clone());
+            command
+        })
+    }
+
+    #[test]
+    fn test_step_debugger() {
+        let at = |function: &str, depth| (function.to_string(), depth);
+
+        assert_eq!(
+            stops_with(|stops| recording(stops, DebugCommand::StepInto).with_stop_on_entry()),
+            [at("Z10004", 1), at("Z10001", 2), at("Z802", 2)]
+        );
+        // the calls of not are performed by first
+        assert_eq!(
+            stops_with(|stops| recording(stops, DebugCommand::StepOver).with_stop_on_entry()),
+            [at("Z10004", 1)]
+        );
+        assert_eq!(
+            stops_with(|stops| {
+                let debugger = recording(stops, DebugCommand::Continue);
+                debugger.add_breakpoint(Breakpoint::Implementation(zid!(902)));
+                debugger
+            }),
+            [at("Z802", 2)]
+        );
+
+        // the frames of the callers are exposed, with their arguments and compositions
+        let stops = Arc::new(Mutex::new(Vec::new()));
+        let debugger = recording(stops.clone(), DebugCommand::Continue);
+        debugger.add_breakpoint(Breakpoint::Function(zid!(10001)));
+        run_debugged(debugger).unwrap();
+        let stop = stops.lock().unwrap()[0].clone();
+        assert_eq!(stop.frames.len(), 2);
+        assert_eq!(stop.frames[0].function, "Z10004");
+        assert_eq!(stop.frames[0].implementation, Some(zid!(10014)));
+        assert_eq!(
+            stop.frames[0].composition,
+            Some(serde_json::from_str(r#"{ "Z1K1": "Z18", "Z18K1": "Z10004K1" }"#).unwrap())
+        );
+        assert!(matches!(
+            stop.frames[1].arguments[&zid!(10001, 1)],
+            crate::ArgumentBinding::Unevaluated(_)
+        ));
+
+        let error =
+            run_debugged(recording(Arc::default(), DebugCommand::Abort).with_stop_on_entry())
+                .unwrap_err();
+        assert!(matches!(error.root(), EvaluationErrorKind::Cancelled));
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_rpc_debugger() {
+        let input = Cursor::new(
+            [
+                r#"{ "jsonrpc": "2.0", "id": 1, "method": "set_breakpoint", "params": { "function": "Z10001" } }"#,
+                r#"{ "jsonrpc": "2.0", "id": 2, "method": "jump" }"#,
+                r#"{ "jsonrpc": "2.0", "id": 3, "method": "continue" }"#,
+                r#"{ "jsonrpc": "2.0", "id": 4, "method": "step_out" }"#,
+            ]
+            .join("\n"),
+        );
+        let output = SharedOutput::default();
+        let debugger = Debugger::new(LineDebugHandler::new(
+            DebugProtocol::JsonRpc,
+            input,
+            output.clone(),
+        ))
+        .with_stop_on_entry();
+        run_debugged(debugger).unwrap();
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let messages = output
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(messages.len(), 6);
+        assert_eq!(messages[0]["method"], "stopped");
+        assert_eq!(messages[0]["params"]["frames"][0]["function"], "Z10004");
+        assert_eq!(messages[1]["id"], 1);
+        assert_eq!(messages[2]["error"]["code"], -32601);
+        assert_eq!(messages[3]["id"], 3);
+        assert_eq!(messages[4]["params"]["frames"][1]["function"], "Z10001");
+        assert_eq!(
+            messages[4]["params"]["frames"][1]["arguments"]["Z10001K1"]["unevaluated"]["Z40K1"],
+            "Z42"
+        );
+        assert_eq!(messages[5]["id"], 4);
+    }
+
+    #[test]
+    fn test_text_repl() {
+        let call = serde_json::to_string(&first_not_loop_call()).unwrap();
+        let input = Cursor::new(
+            [
+                "b Z10001",
+                &call,
+                "bt",
+                "c",
+                // stopping at first, then stepping over it
+                &format!("s {}", call),
+                "n",
+                "c",
+                "c",
+            ]
+            .join("\n"),
+        );
+        let output = SharedOutput::default();
+        LineDebugHandler::new(DebugProtocol::Text, input, output.clone())
+            .repl(&fixture_runner(), &RunnerOption::default());
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let true_value = r#"{"Z1K1":"Z40","Z40K1":"Z41"}"#;
+        assert_eq!(
+            output.matches(&format!("{}\n", true_value)).count(),
+            2,
+            "{}",
+            output
+        );
+        assert!(output.contains("breaking on calls to Z10001"));
+        assert!(output.contains("stopped at Z10001"));
+        assert!(output.contains("#0 Z10004\n#1 Z10001\n"));
+        assert!(output.contains("stopped at Z10004"));
+        // not stopped anymore
+        assert!(output.ends_with("> not stopped in the debugger\n> "));
+    }
+
+    #[test]
+    fn test_json_rpc_repl() {
+        let call = serde_json::to_string(&first_not_loop_call()).unwrap();
+        let input = Cursor::new(
+            [
+                format!(
+                    r#"{{ "jsonrpc": "2.0", "id": 1, "method": "evaluate", "params": {{ "call": {}, "stop_on_entry": true }} }}"#,
+                    call
+                ),
+                r#"{ "jsonrpc": "2.0", "id": 2, "method": "continue" }"#.to_string(),
+                r#"{ "jsonrpc": "2.0", "id": 3, "method": "continue" }"#.to_string(),
+                r#"{ "jsonrpc": "2.0", "id": 4, "method": "evaluate", "params": { "call": "Z10001" } }"#.to_string(),
+            ]
+            .join("\n"),
+        );
+        let output = SharedOutput::default();
+        LineDebugHandler::new(DebugProtocol::JsonRpc, input, output.clone())
+            .repl(&fixture_runner(), &RunnerOption::default());
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let messages = output
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(messages.len(), 5);
+        assert_eq!(messages[0]["method"], "stopped");
+        assert_eq!(messages[1]["id"], 2);
+        assert_eq!(messages[2]["id"], 1);
+        assert_eq!(messages[2]["result"]["Z40K1"], "Z41");
+        assert_eq!(messages[3]["error"]["code"], -32600);
+        assert_eq!(messages[4]["error"]["code"], -32000);
+    }
+}
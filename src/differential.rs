@@ -0,0 +1,201 @@
+//! Run the same function calls through every implementation of a function, to find the implementations that disagree
+
+use crate::{
+    DataEntry, EvaluationContext, EvaluationErrorKind, Runner, RunnerOption, Zid,
+    parse_tool::{WfFunction, WfFunctionCall, WfTestCase},
+};
+
+/// The outcomes of a function call with each implementation of the function
+#[derive(Debug)]
+pub struct DifferentialReport {
+    pub function: Zid,
+    /// The test case whose call was run, None for the other calls
+    pub test_case: Option<Zid>,
+    /// The result with each implementation, in the order they are listed. The error message if it failed.
+    pub outcomes: Vec<(Zid, Result<DataEntry, String>)>,
+    /// The implementations whose outcome differs from the one of the first implementation. Failures don’t differ from one another.
+    pub disagreeing: Vec<Zid>,
+}
+
+impl DifferentialReport {
+    /// All the implementations return the same value
+    pub fn agrees(&self) -> bool {
+        self.disagreeing.is_empty() && self.outcomes.iter().all(|(_, outcome)| outcome.is_ok())
+    }
+
+    /// No implementation returns a value, so the call tells none of them apart
+    pub fn all_fail(&self) -> bool {
+        self.outcomes.iter().all(|(_, outcome)| outcome.is_err())
+    }
+}
+
+impl Runner {
+    /// Run the calls of the test cases of the function with each of its implementations
+    pub fn differential_test(
+        &self,
+        function_id: &Zid,
+        option: &RunnerOption,
+    ) -> Result<Vec<DifferentialReport>, EvaluationErrorKind> {
+        let function = self
+            .get_persistent_object::<WfFunction>(function_id)
+            .map_err(|e| e.trace(format!("getting the function {}", function_id)))?
+            .value;
+        let mut reports = Vec::new();
+        for test_case_id in self.listed_references(&function.testers, "test cases")? {
+            let test_case = self
+                .get_persistent_object::<WfTestCase>(&test_case_id)
+                .map_err(|e| e.trace(format!("getting the test case {}", test_case_id)))?;
+            let call = test_case
+                .value
+                .call
+                .evaluate(self)
+                .map_err(|e| e.trace(format!("getting the call of {}", test_case_id)))?;
+            let mut report = self.differential_test_call(function_id, &call, option)?;
+            report.test_case = Some(test_case_id);
+            reports.push(report);
+        }
+        Ok(reports)
+    }
+
+    /// Run the call, of the function, with each of its implementations. Results agree when they are equal for the equality function of their type. Failures aren’t told apart, but a report whose outcomes are all failures doesn’t agree.
+    pub fn differential_test_call(
+        &self,
+        function_id: &Zid,
+        call: &WfFunctionCall,
+        option: &RunnerOption,
+    ) -> Result<DifferentialReport, EvaluationErrorKind> {
+        let function = self
+            .get_persistent_object::<WfFunction>(function_id)
+            .map_err(|e| e.trace(format!("getting the function {}", function_id)))?
+            .value;
+        let implementations =
+            self.listed_references(&function.implementations, "implementations")?;
+
+        let outcomes = implementations
+            .into_iter()
+            .map(|implementation| {
+                let mut option = option.clone();
+                option
+                    .force_use_impl
+                    .get_or_insert_with(Default::default)
+                    .insert(*function_id, implementation);
+                let result = self
+                    .run_function_call(call, &option)
+                    .map_err(|e| e.root().to_string());
+                (implementation, result)
+            })
+            .collect::<Vec<_>>();
+
+        let mut disagreeing = Vec::new();
+        if let Some((_, first)) = outcomes.first() {
+            let context = EvaluationContext::new(option);
+            for (implementation, outcome) in &outcomes[1..] {
+                let agrees = match (first, outcome) {
+                    // an equality function that fails can’t tell them equal
+                    (Ok(first), Ok(result)) => {
+                        self.values_equal(first, result, &context).unwrap_or(false)
+                    }
+                    (Err(_), Err(_)) => true,
+                    _ => false,
+                };
+                if !agrees {
+                    disagreeing.push(*implementation);
+                }
+            }
+        }
+
+        Ok(DifferentialReport {
+            function: *function_id,
+            test_case: None,
+            outcomes,
+            disagreeing,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        DataEntry, Runner, RunnerOption, Zid,
+        parse_tool::{WfFunctionCall, WfParse},
+        test_fixture::{add_boolean_identity, add_composition, add_function, fixture_datas},
+    };
+
+    #[test]
+    fn test_differential_test() {
+        let mut datas = fixture_datas();
+        // identity on booleans, with a right composition and a wrong one, on true only
+        add_boolean_identity(
+            &mut datas,
+            "Z10065",
+            &[
+                ("Z10066", Some(r#"{ "Z1K1": "Z18", "Z18K1": "Z10065K1" }"#)),
+                ("Z10067", Some(r#"{ "Z1K1": "Z40", "Z40K1": "Z42" }"#)),
+            ],
+            "Z10068",
+            false,
+        );
+        // both loop forever
+        add_function(
+            &mut datas,
+            "Z10069",
+            "stuck",
+            &["Z10069K1"],
+            "Z40",
+            &["Z10076", "Z10077"],
+        );
+        for implementation in ["Z10076", "Z10077"] {
+            add_composition(
+                &mut datas,
+                implementation,
+                "Z10069",
+                r#"{ "Z1K1": "Z7", "Z7K1": "Z10003", "Z10003K1": { "Z1K1": "Z18", "Z18K1": "Z10069K1" } }"#,
+            );
+        }
+        let runner = Runner::new(Arc::new(datas));
+        let option = RunnerOption::default();
+
+        // the test case doesn’t tell them apart
+        let reports = runner.differential_test(&zid!(10065), &option).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].test_case, Some(zid!(10068)));
+        assert_eq!(reports[0].outcomes.len(), 2);
+        assert!(reports[0].agrees());
+
+        // a generated input does
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{ "Z1K1": "Z7", "Z7K1": "Z10065", "Z10065K1": { "Z1K1": "Z40", "Z40K1": "Z41" } }"#,
+        )
+        .unwrap();
+        let report = runner
+            .differential_test_call(
+                &zid!(10065),
+                &WfFunctionCall::parse(&call).unwrap(),
+                &option,
+            )
+            .unwrap();
+        assert_eq!(report.disagreeing, vec![zid!(10067)]);
+        assert!(!report.all_fail());
+
+        // failing along with the others isn’t agreeing
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{ "Z1K1": "Z7", "Z7K1": "Z10069", "Z10069K1": { "Z1K1": "Z40", "Z40K1": "Z41" } }"#,
+        )
+        .unwrap();
+        let report = runner
+            .differential_test_call(
+                &zid!(10069),
+                &WfFunctionCall::parse(&call).unwrap(),
+                &RunnerOption {
+                    max_steps: Some(100),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert!(report.disagreeing.is_empty());
+        assert!(report.all_fail());
+        assert!(!report.agrees());
+    }
+}
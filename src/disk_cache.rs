@@ -0,0 +1,206 @@
+//! A second level for the call cache, persisted in a directory to be reused across runs
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{CallKey, DataEntry, GlobalDatas, Zid, call_cache::CachedCall};
+
+#[derive(Serialize, Deserialize)]
+struct DiskCacheEntry {
+    /// The content hash of each persistent object read while computing the result
+    dependencies: BTreeMap<String, String>,
+    result: DataEntry,
+}
+
+pub enum DiskLookup {
+    Hit(CachedCall),
+    /// Found, but some of the persistent objects it depends on changed since
+    Stale,
+    Missing,
+}
+
+/// Results of function calls, stored in a file named after the content hash of the call
+#[derive(Debug)]
+pub struct DiskCache {
+    directory: PathBuf,
+    /// The content hash of the persistent objects, computed at most once
+    versions: Mutex<HashMap<Zid, String>>,
+}
+
+impl DiskCache {
+    pub fn new(directory: impl Into<PathBuf>) -> io::Result<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+        Ok(Self {
+            directory,
+            versions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn entry_path(&self, key: &CallKey) -> PathBuf {
+        let hash = content_hash(&(
+            key.function.to_zid(),
+            key.implementation
+                .map(|implementation| implementation.to_zid()),
+            DataEntry::IdMap(key.arguments.clone()),
+        ));
+        self.directory
+            .join(&hash[..2])
+            .join(format!("{}.json", hash))
+    }
+
+    fn version(&self, zid: &Zid, datas: &GlobalDatas) -> String {
+        self.versions
+            .lock()
+            .unwrap()
+            .entry(*zid)
+            .or_insert_with(|| match datas.get(zid) {
+                Some(entry) => content_hash(entry),
+                None => "missing".to_string(),
+            })
+            .clone()
+    }
+
+    pub fn get(&self, key: &CallKey, datas: &GlobalDatas) -> DiskLookup {
+        // unreadable entries (like the ones written by an older version) are recomputed
+        let Ok(content) = fs::read(self.entry_path(key)) else {
+            return DiskLookup::Missing;
+        };
+        let Ok(entry) = serde_json::from_slice::<DiskCacheEntry>(&content) else {
+            return DiskLookup::Missing;
+        };
+
+        let mut dependencies = BTreeSet::new();
+        for (zid, version) in &entry.dependencies {
+            let Ok(zid) = Zid::from_zid(zid) else {
+                return DiskLookup::Missing;
+            };
+            if &self.version(&zid, datas) != version {
+                return DiskLookup::Stale;
+            }
+            dependencies.insert(zid);
+        }
+        DiskLookup::Hit(CachedCall {
+            value: entry.result,
+            dependencies,
+        })
+    }
+
+    pub fn insert(&self, key: &CallKey, call: &CachedCall, datas: &GlobalDatas) -> io::Result<()> {
+        let entry = DiskCacheEntry {
+            dependencies: call
+                .dependencies
+                .iter()
+                .map(|zid| (zid.to_zid(), self.version(zid, datas)))
+                .collect(),
+            result: call.value.clone(),
+        };
+        let path = self.entry_path(key);
+        let directory = path.parent().expect("entries are in a subdirectory");
+        fs::create_dir_all(directory)?;
+        // written aside then moved, so that a concurrent reader never sees a partial entry
+        let mut file = tempfile::NamedTempFile::new_in(directory)?;
+        serde_json::to_writer(&mut file, &entry)?;
+        file.flush()?;
+        file.persist(path).map_err(|e| e.error)?;
+        Ok(())
+    }
+}
+
+fn content_hash(value: &impl Serialize) -> String {
+    let content = serde_json::to_vec(value).expect("values can always be serialized");
+    format!("{:x}", Sha256::digest(content))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        CallCache, DataEntry, GlobalDatas, Runner, RunnerOption,
+        parse_tool::{WfFunctionCall, WfParse},
+        test_fixture::{add_composition, add_function, fixture_datas},
+    };
+
+    use super::DiskCache;
+
+    /// Z10009 calls Z10008, implemented by the given composition
+    fn datas_with(implementation: &str) -> GlobalDatas {
+        let mut datas = fixture_datas();
+        add_function(
+            &mut datas,
+            "Z10008",
+            "inner",
+            &["Z10008K1"],
+            "Z40",
+            &["Z10018"],
+        );
+        add_composition(&mut datas, "Z10018", "Z10008", implementation);
+        add_function(
+            &mut datas,
+            "Z10009",
+            "outer",
+            &["Z10009K1"],
+            "Z40",
+            &["Z10019"],
+        );
+        add_composition(
+            &mut datas,
+            "Z10019",
+            "Z10009",
+            r#"{
+                "Z1K1": "Z7",
+                "Z7K1": "Z10008",
+                "Z10008K1": { "Z1K1": "Z18", "Z18K1": "Z10009K1" }
+            }"#,
+        );
+        datas
+    }
+
+    #[test]
+    fn test_disk_cache_invalidation() {
+        let directory = tempfile::tempdir().unwrap();
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{ "Z1K1": "Z7", "Z7K1": "Z10009", "Z10009K1": { "Z1K1": "Z40", "Z40K1": "Z42" } }"#,
+        )
+        .unwrap();
+        let call = WfFunctionCall::parse(&call).unwrap();
+        let run = |datas: GlobalDatas| {
+            let runner = Runner::new(Arc::new(datas)).with_call_cache(
+                CallCache::new(100).with_disk_cache(DiskCache::new(directory.path()).unwrap()),
+            );
+            let result = runner
+                .run_function_call(&call, &RunnerOption::default())
+                .unwrap();
+            (result, runner.get_call_cache().unwrap().stats())
+        };
+        let identity = r#"{ "Z1K1": "Z18", "Z18K1": "Z10008K1" }"#;
+        let not = r#"{
+            "Z1K1": "Z7",
+            "Z7K1": "Z10001",
+            "Z10001K1": { "Z1K1": "Z18", "Z18K1": "Z10008K1" }
+        }"#;
+
+        let (first_result, first_stats) = run(datas_with(identity));
+        assert_eq!(first_stats.disk_hits, 0);
+
+        // a new runner reuses the results of the previous one
+        let (result, stats) = run(datas_with(identity));
+        assert_eq!(result, first_result);
+        assert_eq!(stats.disk_hits, 1);
+
+        // Z10009 didn’t change, but what it calls did
+        let (result, stats) = run(datas_with(not));
+        assert_ne!(result, first_result);
+        assert_eq!(stats.disk_hits, 0);
+        assert!(stats.stale_disk_entries > 0);
+    }
+}
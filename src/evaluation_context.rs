@@ -0,0 +1,190 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Instant,
+};
+
+use crate::{
+    CodeLimits, DataEntry, EvaluationErrorKind, RunnerOption, Scope,
+    debugger::{DebugCommand, DebugMode, DebugStop},
+    evaluation_error::EvaluationLimit,
+    tracer::{OpenCall, TraceStack},
+};
+
+/// The state of an evaluation, shared by all the function calls it performs
+#[derive(Debug)]
+pub struct EvaluationContext<'o> {
+    pub option: &'o RunnerOption,
+    started_at: Instant,
+    steps: Cell<u64>,
+    /// A description of each function call being evaluated, the outermost first
+    call_stack: RefCell<Vec<String>>,
+    /// Number of calls to impure functions so far. The results computed while it changed can’t be cached.
+    impure_calls: Cell<u64>,
+    /// The calls being recorded, if the RunnerOption has a tracer, a profiler or a debugger
+    trace_stack: RefCell<TraceStack>,
+    /// Where the debugger stops next
+    debug_mode: Cell<DebugMode>,
+    /// The scopes the function literals evaluated so far were closed over, by the index their value holds
+    closures: RefCell<Vec<Rc<Scope>>>,
+}
+
+impl<'o> EvaluationContext<'o> {
+    pub fn new(option: &'o RunnerOption) -> Self {
+        Self {
+            option,
+            started_at: Instant::now(),
+            steps: Cell::new(0),
+            call_stack: RefCell::new(Vec::new()),
+            impure_calls: Cell::new(0),
+            trace_stack: RefCell::new(TraceStack::default()),
+            debug_mode: Cell::new(match &option.debugger {
+                Some(debugger) => debugger.initial_mode(),
+                None => DebugMode::Continue,
+            }),
+            closures: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn get_steps(&self) -> u64 {
+        self.steps.get()
+    }
+
+    pub fn get_call_stack(&self) -> Vec<String> {
+        self.call_stack.borrow().clone()
+    }
+
+    pub fn get_impure_calls(&self) -> u64 {
+        self.impure_calls.get()
+    }
+
+    pub fn mark_impure_call(&self) {
+        self.impure_calls.set(self.impure_calls.get() + 1);
+    }
+
+    /// Account for a new function call. It must be matched with a call to exit_call once the function returned, unless an error is returned.
+    pub fn enter_call(&self, description: String) -> Result<(), EvaluationErrorKind> {
+        self.check_cancelled()?;
+        self.steps.set(self.steps.get() + 1);
+        // pushed first so that the failing call is part of the reported call stack
+        self.call_stack.borrow_mut().push(description);
+
+        let result = self.check_limits();
+        if result.is_err() {
+            self.exit_call();
+        }
+        result
+    }
+
+    pub fn exit_call(&self) {
+        self.call_stack.borrow_mut().pop();
+    }
+
+    /// Return an error if a limit of the option was exceeded. Also called by the loops of the built-ins, that don’t enter calls at each iteration.
+    pub(crate) fn check_limits(&self) -> Result<(), EvaluationErrorKind> {
+        if let Some(max_steps) = self.option.max_steps
+            && self.steps.get() > max_steps
+        {
+            return Err(self.limit_exceeded(EvaluationLimit::Steps(max_steps)));
+        }
+        if let Some(max_depth) = self.option.max_depth
+            && self.call_stack.borrow().len() > max_depth
+        {
+            return Err(self.limit_exceeded(EvaluationLimit::Depth(max_depth)));
+        }
+        if let Some(timeout) = self.option.timeout
+            && self.started_at.elapsed() > timeout
+        {
+            return Err(self.limit_exceeded(EvaluationLimit::Time(timeout)));
+        }
+        Ok(())
+    }
+
+    /// The limits of a code implementation run now, that may not outlast the evaluation
+    pub fn code_limits(&self) -> CodeLimits {
+        let mut limits = self.option.code_limits;
+        if let Some(timeout) = self.option.timeout {
+            limits.max_duration = limits
+                .max_duration
+                .min(timeout.saturating_sub(self.started_at.elapsed()));
+        }
+        limits
+    }
+
+    /// Whether the calls are recorded, by a tracer, a profiler or a debugger
+    pub fn records_calls(&self) -> bool {
+        self.option.tracer.is_some()
+            || self.option.profiler.is_some()
+            || self.option.debugger.is_some()
+    }
+
+    pub fn open_traced_call(&self, call: OpenCall) {
+        self.trace_stack.borrow_mut().open(call);
+    }
+
+    pub fn close_traced_call(&self, result: Result<&DataEntry, &EvaluationErrorKind>) {
+        if self.records_calls() {
+            self.trace_stack.borrow_mut().close(
+                result,
+                self.option.tracer.as_ref(),
+                self.option.profiler.as_ref(),
+            );
+        }
+    }
+
+    /// Let the debugger stop at the call just entered, if it should. Return an error if it aborts the evaluation.
+    pub fn debug_call(&self) -> Result<(), EvaluationErrorKind> {
+        let Some(debugger) = &self.option.debugger else {
+            return Ok(());
+        };
+        let depth = self.call_stack.borrow().len();
+        let stop = {
+            let trace_stack = self.trace_stack.borrow();
+            let open_calls = trace_stack.get_open_calls();
+            match open_calls.last() {
+                Some(call) if debugger.should_stop(call, depth, self.debug_mode.get()) => {
+                    DebugStop::new(open_calls, depth)
+                }
+                _ => return Ok(()),
+            }
+        };
+        self.debug_mode.set(match debugger.stop(&stop) {
+            DebugCommand::StepInto => DebugMode::StepInto,
+            DebugCommand::StepOver => DebugMode::StepOver(depth),
+            DebugCommand::StepOut => DebugMode::StepOut(depth),
+            DebugCommand::Continue => DebugMode::Continue,
+            DebugCommand::Abort => return Err(EvaluationErrorKind::Cancelled),
+        });
+        Ok(())
+    }
+
+    /// Keep the scope a function literal is closed over, returning the index its value refers to it with
+    pub(crate) fn capture(&self, scope: &Rc<Scope>) -> usize {
+        let mut closures = self.closures.borrow_mut();
+        // literals evaluated in turn in the same scope, like the ones of a loop body, share it
+        if let Some(last) = closures.last()
+            && Rc::ptr_eq(last, scope)
+        {
+            return closures.len() - 1;
+        }
+        closures.push(scope.clone());
+        closures.len() - 1
+    }
+
+    /// The scope a function literal value is closed over
+    pub(crate) fn captured(&self, index: usize) -> Option<Rc<Scope>> {
+        self.closures.borrow().get(index).cloned()
+    }
+
+    /// Return an error if the evaluation was cancelled
+    pub fn check_cancelled(&self) -> Result<(), EvaluationErrorKind> {
+        if self.option.cancellation_token.is_cancelled() {
+            return Err(EvaluationErrorKind::Cancelled);
+        }
+        Ok(())
+    }
+
+    fn limit_exceeded(&self, limit: EvaluationLimit) -> EvaluationErrorKind {
+        EvaluationErrorKind::LimitExceeded(limit, self.get_call_stack())
+    }
+}
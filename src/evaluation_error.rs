@@ -1,11 +1,13 @@
 use std::{
     error::Error,
     fmt::{Display, Write},
+    time::Duration,
 };
 
+use map_macro::btree_map;
 use thiserror::Error;
 
-use crate::{DataEntry, Zid};
+use crate::{DataEntry, ProgrammingLanguage, Zid, code::ConverterDirection};
 
 //TODO: error handling should be much better than that. Will do for now.
 #[derive(Error, Debug)]
@@ -26,6 +28,20 @@ pub enum EvaluationErrorKind {
     Unimplemented(String),
     #[error("low level: wrong type {0}, expected {1}")]
     WrongType(Zid, Zid),
+    #[error("low level: {0} is not a boolean value")]
+    InvalidBoolean(String),
+    #[error("low level: argument {0} is not bound in the current scope")]
+    UnboundArgument(Zid),
+    #[error("low level: {0} exceeded, with call stack {1:?}")]
+    LimitExceeded(EvaluationLimit, Vec<String>),
+    #[error("low level: the evaluation was cancelled")]
+    Cancelled,
+    #[error("low level: error in code: {0}")]
+    CodeExecution(String),
+    #[error("low level: arithmetic: {0}")]
+    Arithmetic(String),
+    #[error("low level: the type {0} has no type converter {1} {2:?} code")]
+    NoTypeConverter(Zid, ConverterDirection, ProgrammingLanguage),
     #[error("info: test result: {0:?}")]
     TestResultInfo(DataEntry, #[source] Box<EvaluationErrorKind>),
     #[error("info: trace: {0}")]
@@ -40,6 +56,59 @@ impl EvaluationErrorKind {
     pub fn trace_str(self, message: &str) -> Self {
         self.trace(message.to_string())
     }
+
+    /// The error at the origin of the traces
+    pub fn root(&self) -> &Self {
+        match self {
+            Self::Previous(_, source) | Self::TestResultInfo(_, source) => source.root(),
+            _ => self,
+        }
+    }
+
+    /// Represent the error as a Z5, the way the orchestrator would report it
+    pub fn to_zobject(&self) -> DataEntry {
+        let (error_type, details) = match self.root() {
+            // error in evaluation. The limit reached is only told by the details.
+            Self::CodeExecution(_) | Self::Arithmetic(_) | Self::LimitExceeded(..) => {
+                (zid!(507), self.to_string())
+            }
+            // generic error
+            _ => (zid!(500), self.to_string()),
+        };
+        let error_type_string = DataEntry::String(error_type.to_zid());
+        DataEntry::IdMap(btree_map! {
+            zid!(1, 1) => DataEntry::String("Z5".to_string()),
+            zid!(5, 1) => error_type_string.clone(),
+            zid!(5, 2) => DataEntry::IdMap(btree_map! {
+                // the type of the details is built from the error type with Z885
+                zid!(1, 1) => DataEntry::IdMap(btree_map! {
+                    zid!(1, 1) => DataEntry::String("Z7".to_string()),
+                    zid!(7, 1) => DataEntry::String("Z885".to_string()),
+                    zid!(885, 1) => error_type_string,
+                }),
+                Zid::from_u64s_panic(error_type.get_z().map(|z| z.get()), Some(1))
+                    => DataEntry::String(details),
+            }),
+        })
+    }
+}
+
+/// A limit on the resources an evaluation may use, set in the RunnerOption
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvaluationLimit {
+    Steps(u64),
+    Depth(usize),
+    Time(Duration),
+}
+
+impl Display for EvaluationLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Steps(max) => write!(f, "step budget of {}", max),
+            Self::Depth(max) => write!(f, "maximum call depth of {}", max),
+            Self::Time(max) => write!(f, "timeout of {:?}", max),
+        }
+    }
 }
 
 #[derive(Debug)]
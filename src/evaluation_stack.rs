@@ -0,0 +1,405 @@
+//! The evaluator keeps what remains to be done after each sub-evaluation in a heap-allocated stack of frames, rather than in the native call stack. Deep recursions are then only bounded by the limits of the RunnerOption.
+
+use std::{collections::BTreeMap, fmt::Display, rc::Rc};
+
+use crate::{
+    CallKey, DataEntry, EvaluationContext, EvaluationErrorKind, Runner, Scope, Thunk, Zid,
+    call_cache::read_log_position, composition_tool::contains_closure, tracer::OpenCall,
+};
+
+/// What to do with the value of a sub-evaluation
+pub type Continuation = Box<
+    dyn FnOnce(&Runner, &EvaluationContext<'_>, DataEntry) -> Result<Step, EvaluationErrorKind>,
+>;
+
+/// What the evaluator has to do next
+pub enum Step {
+    /// The current sub-evaluation is over, with this value
+    Value(DataEntry),
+    /// Evaluate the expression, resolving argument references in the scope
+    Evaluate(DataEntry, Rc<Scope>),
+    /// Evaluate the argument, or reuse its value
+    Force(Rc<Thunk>),
+    /// Call the function, with arguments to evaluate in the scope
+    Call {
+        function: DataEntry,
+        arguments: BTreeMap<Zid, DataEntry>,
+        scope: Rc<Scope>,
+    },
+    /// Account for a new function call, then run its body. The trace is added to the errors of the body.
+    Enter {
+        description: String,
+        trace: String,
+        body: Box<Step>,
+        traced_call: Option<Box<OpenCall>>,
+    },
+    /// Do the step, then store its value in the call cache of the runner
+    Cached(CallKey, Box<Step>),
+    /// Do the step, adding the trace to its errors
+    Traced(Trace, Box<Step>),
+    /// Do the step, then give its value to the continuation
+    Then(Box<Step>, Continuation),
+}
+
+impl Step {
+    pub fn traced(trace: Trace, step: Step) -> Self {
+        Self::Traced(trace, Box::new(step))
+    }
+
+    pub fn then(
+        step: Step,
+        continuation: impl FnOnce(
+            &Runner,
+            &EvaluationContext<'_>,
+            DataEntry,
+        ) -> Result<Step, EvaluationErrorKind>
+        + 'static,
+    ) -> Self {
+        Self::Then(Box::new(step), Box::new(continuation))
+    }
+}
+
+/// A message added to the errors raised below a frame. Only formatted if an error occurs.
+pub enum Trace {
+    Message(&'static str),
+    Formatted(String),
+    InsideKey(Zid),
+    ArrayPosition(usize),
+    Argument(Zid),
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Message(message) => write!(f, "{}", message),
+            Self::Formatted(message) => write!(f, "{}", message),
+            Self::InsideKey(key) => write!(f, "Inside {}", key),
+            Self::ArrayPosition(pos) => write!(f, "At array position {}", pos),
+            Self::Argument(key) => write!(f, "evaluating argument {}", key),
+        }
+    }
+}
+
+/// Maximum number of traces added to an error while unwinding the stack. The innermost ones are kept.
+const MAX_TRACES: usize = 256;
+
+enum Frame {
+    Continue(Continuation),
+    /// Store the value of the argument once evaluated
+    Memoize(Rc<Thunk>),
+    /// The end of a function call, with the number of traced calls that end with it (more than one after tail calls)
+    Return(String, usize),
+    /// Store the result of a function call, unless an impure function was called since the given count. Also keep the position in the read log when the call started.
+    Store(CallKey, u64, usize),
+    Trace(Trace),
+}
+
+impl Runner {
+    /// Run the step and all the ones that follow from it
+    pub fn evaluate(
+        &self,
+        step: Step,
+        context: &EvaluationContext<'_>,
+    ) -> Result<DataEntry, EvaluationErrorKind> {
+        let mut stack = Vec::new();
+        let mut state = Ok(step);
+        let mut kept_traces = 0;
+        let mut omitted_traces = 0;
+        loop {
+            state = match state {
+                Ok(Step::Value(value)) => match stack.pop() {
+                    None => return Ok(value),
+                    Some(frame) => self.return_to(frame, value, context),
+                },
+                Ok(step) => self.advance(step, &mut stack, context),
+                // unwind the whole stack, so that the call stack of the context stays balanced
+                Err(error) => match stack.pop() {
+                    None if omitted_traces > 0 => {
+                        return Err(error.trace(format!("{} outer traces omitted", omitted_traces)));
+                    }
+                    None => return Err(error),
+                    Some(frame) => match Self::unwind(frame, &error, context) {
+                        // errors are nested once per trace, and deep recursions would make them too deep to even be dropped
+                        Some(trace) if kept_traces < MAX_TRACES => {
+                            kept_traces += 1;
+                            Err(error.trace(trace.to_string()))
+                        }
+                        Some(_) => {
+                            omitted_traces += 1;
+                            Err(error)
+                        }
+                        None => Err(error),
+                    },
+                },
+            };
+        }
+    }
+
+    fn advance(
+        &self,
+        step: Step,
+        stack: &mut Vec<Frame>,
+        context: &EvaluationContext<'_>,
+    ) -> Result<Step, EvaluationErrorKind> {
+        context.check_cancelled()?;
+        match step {
+            Step::Value(value) => Ok(Step::Value(value)),
+            Step::Evaluate(entry, scope) => self.evaluate_entry(entry, scope, context),
+            Step::Force(thunk) => {
+                if let Some(value) = thunk.get_value() {
+                    return Ok(Step::Value(value.clone()));
+                }
+                let (expression, scope) = thunk
+                    .get_expression()
+                    .expect("a thunk without expression should have a value");
+                let step = Step::Evaluate(expression.clone(), scope.clone());
+                if thunk.is_memoized() {
+                    stack.push(Frame::Memoize(thunk));
+                }
+                Ok(step)
+            }
+            Step::Call {
+                function,
+                arguments,
+                scope,
+            } => Ok(Self::call_step(function, arguments, scope)),
+            Step::Enter {
+                description,
+                trace,
+                body,
+                traced_call,
+            } => {
+                // a tail call. The caller has nothing left to do but return this value, so it can be dropped.
+                // Its traced calls are still ended with the value.
+                let mut traced_calls = 0;
+                if let Some(&Frame::Return(_, inherited_traced_calls)) = stack.last() {
+                    traced_calls = inherited_traced_calls;
+                    stack.pop();
+                    context.exit_call();
+                }
+                if let Err(error) = context.enter_call(description) {
+                    for _ in 0..traced_calls {
+                        context.close_traced_call(Err(&error));
+                    }
+                    return Err(error);
+                }
+                if let Some(traced_call) = traced_call {
+                    context.open_traced_call(*traced_call);
+                    traced_calls += 1;
+                }
+                stack.push(Frame::Return(trace, traced_calls));
+                // once the frame is pushed, so that aborting the evaluation ends the call
+                context.debug_call()?;
+                Ok(*body)
+            }
+            Step::Cached(key, step) => {
+                stack.push(Frame::Store(
+                    key,
+                    context.get_impure_calls(),
+                    read_log_position(),
+                ));
+                Ok(*step)
+            }
+            Step::Traced(trace, step) => {
+                stack.push(Frame::Trace(trace));
+                Ok(*step)
+            }
+            Step::Then(step, continuation) => {
+                stack.push(Frame::Continue(continuation));
+                Ok(*step)
+            }
+        }
+    }
+
+    fn return_to(
+        &self,
+        frame: Frame,
+        value: DataEntry,
+        context: &EvaluationContext<'_>,
+    ) -> Result<Step, EvaluationErrorKind> {
+        match frame {
+            Frame::Continue(continuation) => continuation(self, context, value),
+            Frame::Memoize(thunk) => {
+                thunk.set_value(value.clone());
+                Ok(Step::Value(value))
+            }
+            Frame::Return(_, traced_calls) => {
+                context.exit_call();
+                for _ in 0..traced_calls {
+                    context.close_traced_call(Ok(&value));
+                }
+                Ok(Step::Value(value))
+            }
+            Frame::Store(key, impure_calls, read_position) => {
+                if context.get_impure_calls() == impure_calls && !contains_closure(&value) {
+                    self.store_in_call_cache(key, value.clone(), read_position);
+                }
+                Ok(Step::Value(value))
+            }
+            Frame::Trace(_) => Ok(Step::Value(value)),
+        }
+    }
+
+    /// Leave the frame because of an error, returning the trace to add to it
+    fn unwind(
+        frame: Frame,
+        error: &EvaluationErrorKind,
+        context: &EvaluationContext<'_>,
+    ) -> Option<Trace> {
+        match frame {
+            Frame::Return(trace, traced_calls) => {
+                context.exit_call();
+                for _ in 0..traced_calls {
+                    context.close_traced_call(Err(error));
+                }
+                Some(Trace::Formatted(trace))
+            }
+            Frame::Trace(trace) => Some(trace),
+            Frame::Continue(_) | Frame::Memoize(_) | Frame::Store(..) => None,
+        }
+    }
+}
+
+/// Force the arguments one after the other, then give their values to the continuation
+pub fn force_arguments(
+    arguments: Vec<(Zid, Rc<Thunk>)>,
+    runner: &Runner,
+    context: &EvaluationContext<'_>,
+    then: impl FnOnce(
+        &Runner,
+        &EvaluationContext<'_>,
+        BTreeMap<Zid, DataEntry>,
+    ) -> Result<Step, EvaluationErrorKind>
+    + 'static,
+) -> Result<Step, EvaluationErrorKind> {
+    force_remaining(
+        arguments.into_iter(),
+        BTreeMap::new(),
+        runner,
+        context,
+        Box::new(then),
+    )
+}
+
+type ArgumentsContinuation = Box<
+    dyn FnOnce(
+        &Runner,
+        &EvaluationContext<'_>,
+        BTreeMap<Zid, DataEntry>,
+    ) -> Result<Step, EvaluationErrorKind>,
+>;
+
+fn force_remaining(
+    mut remaining: std::vec::IntoIter<(Zid, Rc<Thunk>)>,
+    mut values: BTreeMap<Zid, DataEntry>,
+    runner: &Runner,
+    context: &EvaluationContext<'_>,
+    then: ArgumentsContinuation,
+) -> Result<Step, EvaluationErrorKind> {
+    loop {
+        match remaining.next() {
+            None => return then(runner, context, values),
+            Some((key, thunk)) => {
+                if let Some(value) = thunk.get_value() {
+                    values.insert(key, value.clone());
+                    continue;
+                }
+                return Ok(Step::then(
+                    Step::traced(Trace::Argument(key), Step::Force(thunk)),
+                    move |runner, context, value| {
+                        values.insert(key, value);
+                        force_remaining(remaining, values, runner, context, then)
+                    },
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, rc::Rc, sync::Arc};
+
+    use crate::{
+        BuiltinRegistry, DataEntry, EvaluationContext, EvaluationErrorKind, EvaluationLimit,
+        FnBuiltin, Runner, RunnerOption, Scope, Step, Zid,
+        test_fixture::{add_builtin, add_function, fixture_datas},
+    };
+
+    const DEPTH: u64 = 20_000;
+
+    /// Z10093 and Z10094 count down from their argument to 0, then return it. Z10093 keeps a frame for each level, while Z10094 calls itself as a tail call.
+    fn countdown_runner() -> Runner {
+        let mut datas = fixture_datas();
+        let mut builtins = BuiltinRegistry::default();
+        for (function, builtin, tail) in [("Z10093", "Z100931", false), ("Z10094", "Z100941", true)]
+        {
+            let key = format!("{}K1", function);
+            add_function(&mut datas, function, "countdown", &[&key], "Z6", &[builtin]);
+            add_builtin(&mut datas, builtin, function);
+            let function = DataEntry::String(function.to_string());
+            let key = Zid::from_zid(&key).unwrap();
+            builtins.register(
+                Zid::from_zid(builtin).unwrap(),
+                FnBuiltin::new([key], move |_, mut arguments, _| {
+                    let count = arguments.take(key)?;
+                    let n: u64 = count.get_str()?.parse().unwrap();
+                    if n == 0 {
+                        return Ok(Step::Value(count));
+                    }
+                    let call = Step::Call {
+                        function: function.clone(),
+                        arguments: BTreeMap::from([(key, DataEntry::String((n - 1).to_string()))]),
+                        scope: Rc::new(Scope::default()),
+                    };
+                    Ok(if tail {
+                        call
+                    } else {
+                        Step::then(call, |_, _, value| Ok(Step::Value(value)))
+                    })
+                }),
+            );
+        }
+        Runner::new(Arc::new(datas)).with_builtins(builtins)
+    }
+
+    fn countdown(
+        runner: &Runner,
+        function: &str,
+        option: &RunnerOption,
+    ) -> Result<DataEntry, EvaluationErrorKind> {
+        runner.call_function_value(
+            &DataEntry::String(function.to_string()),
+            &[DataEntry::String(DEPTH.to_string())],
+            &EvaluationContext::new(option),
+        )
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        // a native stack far too small to hold that many levels if each of them used it
+        std::thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(|| {
+                let runner = countdown_runner();
+                let zero = DataEntry::String("0".to_string());
+                assert_eq!(
+                    countdown(&runner, "Z10093", &RunnerOption::default()).unwrap(),
+                    zero
+                );
+                // tail calls don’t make the call stack grow
+                let option = RunnerOption {
+                    max_depth: Some(2),
+                    ..Default::default()
+                };
+                assert_eq!(countdown(&runner, "Z10094", &option).unwrap(), zero);
+                assert!(matches!(
+                    countdown(&runner, "Z10093", &option).unwrap_err().root(),
+                    EvaluationErrorKind::LimitExceeded(EvaluationLimit::Depth(2), _)
+                ));
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+}
@@ -0,0 +1,378 @@
+//! Run code implementations, either with the embedded engines, or in worker processes.
+//!
+//! A worker gets one request on its stdin, a JSON object with the `source` of the implementation, the `function` it defines, the converted `arguments` and the `limits` of the call, then closes it.
+//! It writes one response on its stdout, a JSON object with either the `result` of the call or an `error` message, then exits. What the code prints goes to stderr.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    io::{Read, Write},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{CancellationToken, EvaluationErrorKind, ProgrammingLanguage, code::CodeLimits};
+
+/// Runs the code of implementations. The value passed and returned are the converted ones.
+pub trait CodeExecutor: Debug + Send + Sync {
+    fn supports(&self, language: ProgrammingLanguage) -> bool;
+
+    /// Run the source, then call the function of that name it defines with the arguments
+    fn execute(
+        &self,
+        language: ProgrammingLanguage,
+        source: &str,
+        function: &str,
+        arguments: &[Value],
+        limits: &CodeLimits,
+        cancellation_token: &CancellationToken,
+    ) -> Result<Value, EvaluationErrorKind>;
+}
+
+/// Run code with the engines built in, depending on the enabled features
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EmbeddedExecutor;
+
+impl CodeExecutor for EmbeddedExecutor {
+    fn supports(&self, language: ProgrammingLanguage) -> bool {
+        language.is_supported()
+    }
+
+    // the cancellation token is only polled by the engines that can be interrupted
+    #[cfg_attr(not(feature = "python"), allow(unused_variables))]
+    fn execute(
+        &self,
+        language: ProgrammingLanguage,
+        source: &str,
+        function: &str,
+        arguments: &[Value],
+        limits: &CodeLimits,
+        cancellation_token: &CancellationToken,
+    ) -> Result<Value, EvaluationErrorKind> {
+        match language {
+            #[cfg(feature = "javascript")]
+            ProgrammingLanguage::JavaScript => {
+                crate::javascript::run_javascript(source, function, arguments, limits)
+            }
+            #[cfg(feature = "python")]
+            ProgrammingLanguage::Python => {
+                crate::python::run_python(source, function, arguments, limits, cancellation_token)
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(EvaluationErrorKind::Unimplemented(format!(
+                "running {:?} code (not enabled)",
+                language
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct WorkerRequest<'l> {
+    source: &'l str,
+    function: &'l str,
+    arguments: &'l [Value],
+    limits: &'l CodeLimits,
+}
+
+/// A request as read by a worker, that may ignore the limits
+#[derive(Deserialize)]
+struct ReceivedRequest {
+    source: String,
+    function: String,
+    arguments: Vec<Value>,
+    #[serde(default)]
+    limits: CodeLimits,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum WorkerResponse {
+    Result(Value),
+    Error(String),
+}
+
+/// Run code in a new worker process for each call, killed when it takes longer than the time limit. On Unix, its CPU time and memory are also limited.
+#[derive(Debug, Clone, Default)]
+pub struct SubprocessExecutor {
+    commands: BTreeMap<ProgrammingLanguage, Vec<String>>,
+}
+
+impl SubprocessExecutor {
+    /// An executor without any worker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The `python3` and `node` of the system, with the workers shipped with the interpreter
+    pub fn system() -> Self {
+        Self::new()
+            .with_command(
+                ProgrammingLanguage::Python,
+                ["python3", "-c", include_str!("workers/worker.py")],
+            )
+            .with_command(
+                ProgrammingLanguage::JavaScript,
+                ["node", "-e", include_str!("workers/worker.js")],
+            )
+    }
+
+    /// The embedded engines, each run in a worker process of the current executable, that must call serve_worker when given `--worker` then the name of the language (see main.rs).
+    /// Unlike the EmbeddedExecutor, the engines that can’t be interrupted nor bounded in memory are then subject to the time and memory limits.
+    pub fn embedded() -> std::io::Result<Self> {
+        let executable = std::env::current_exe()?.to_string_lossy().into_owned();
+        Ok([
+            (ProgrammingLanguage::JavaScript, "javascript"),
+            (ProgrammingLanguage::Python, "python"),
+        ]
+        .into_iter()
+        .filter(|(language, _)| language.is_supported())
+        .fold(Self::new(), |executor, (language, name)| {
+            executor.with_command(language, [executable.as_str(), "--worker", name])
+        }))
+    }
+
+    /// Run the code of the language with this worker: a program then its arguments
+    pub fn with_command(
+        mut self,
+        language: ProgrammingLanguage,
+        command: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.commands
+            .insert(language, command.into_iter().map(Into::into).collect());
+        self
+    }
+
+    fn spawn(command: &[String], limits: &CodeLimits) -> std::io::Result<Child> {
+        let mut process = if cfg!(unix) {
+            // the shell applies the limits to itself, then becomes the worker
+            let mut process = Command::new("sh");
+            process.arg("-c").arg(format!(
+                "ulimit -t {} && ulimit -d {} && exec \"$0\" \"$@\"",
+                limits.max_duration.as_secs() + 1,
+                limits.max_memory / 1024
+            ));
+            process.args(command);
+            process
+        } else {
+            let mut process = Command::new(&command[0]);
+            process.args(&command[1..]);
+            process
+        };
+        process
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+    }
+}
+
+fn worker_error(message: impl std::fmt::Display) -> EvaluationErrorKind {
+    EvaluationErrorKind::CodeExecution(format!("worker: {}", message))
+}
+
+impl CodeExecutor for SubprocessExecutor {
+    fn supports(&self, language: ProgrammingLanguage) -> bool {
+        self.commands.contains_key(&language)
+    }
+
+    fn execute(
+        &self,
+        language: ProgrammingLanguage,
+        source: &str,
+        function: &str,
+        arguments: &[Value],
+        limits: &CodeLimits,
+        cancellation_token: &CancellationToken,
+    ) -> Result<Value, EvaluationErrorKind> {
+        let Some(command) = self.commands.get(&language).filter(|c| !c.is_empty()) else {
+            return Err(EvaluationErrorKind::Unimplemented(format!(
+                "running {:?} code (no worker)",
+                language
+            )));
+        };
+        let request = serde_json::to_vec(&WorkerRequest {
+            source,
+            function,
+            arguments,
+            limits,
+        })
+        .map_err(worker_error)?;
+
+        let mut child = Self::spawn(command, limits).map_err(worker_error)?;
+        // both are read while waiting, so that a worker writing a lot doesn’t block
+        let readers = [
+            child
+                .stdout
+                .take()
+                .map(|output| Box::new(output) as Box<dyn Read + Send>),
+            child
+                .stderr
+                .take()
+                .map(|output| Box::new(output) as Box<dyn Read + Send>),
+        ]
+        .map(|output| {
+            thread::spawn(move || {
+                let mut text = Vec::new();
+                if let Some(mut output) = output {
+                    let _ = output.read_to_end(&mut text);
+                }
+                text
+            })
+        });
+        if let Some(mut input) = child.stdin.take() {
+            // the worker may exit before reading it all, the status tells why
+            thread::spawn(move || input.write_all(&request));
+        }
+
+        let deadline = Instant::now() + limits.max_duration;
+        let status = loop {
+            if let Some(status) = child.try_wait().map_err(worker_error)? {
+                break status;
+            }
+            if cancellation_token.is_cancelled() {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(EvaluationErrorKind::Cancelled);
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(EvaluationErrorKind::CodeExecution(format!(
+                    "time limit of {:?} exceeded",
+                    limits.max_duration
+                )));
+            }
+            thread::sleep(Duration::from_millis(1));
+        };
+        let [output, errors] = readers.map(|reader| reader.join().unwrap_or_default());
+
+        // the last line, in case something else was written before it
+        let response = output
+            .split(|byte| *byte == b'\n')
+            .rev()
+            .find_map(|line| serde_json::from_slice::<WorkerResponse>(line).ok());
+        match response {
+            Some(WorkerResponse::Result(value)) => Ok(value),
+            Some(WorkerResponse::Error(message)) => {
+                Err(EvaluationErrorKind::CodeExecution(message))
+            }
+            None => Err(worker_error(format!(
+                "no response, {}: {}",
+                status,
+                String::from_utf8_lossy(&errors).trim()
+            ))),
+        }
+    }
+}
+
+/// Serve a single request of the worker protocol with the embedded engine of the language, reading it from the input and writing the response to the output
+pub fn serve_worker(
+    language: ProgrammingLanguage,
+    input: impl Read,
+    mut output: impl Write,
+) -> std::io::Result<()> {
+    let response = match serde_json::from_reader::<_, ReceivedRequest>(input) {
+        Ok(request) => {
+            let result = std::panic::catch_unwind(|| {
+                EmbeddedExecutor.execute(
+                    language,
+                    &request.source,
+                    &request.function,
+                    &request.arguments,
+                    &request.limits,
+                    // the worker is killed by the executor when the evaluation is cancelled
+                    &CancellationToken::new(),
+                )
+            });
+            match result {
+                Ok(Ok(value)) => WorkerResponse::Result(value),
+                Ok(Err(EvaluationErrorKind::CodeExecution(message))) => {
+                    WorkerResponse::Error(message)
+                }
+                Ok(Err(error)) => WorkerResponse::Error(error.to_string()),
+                Err(_) => WorkerResponse::Error("the engine panicked".to_string()),
+            }
+        }
+        Err(error) => WorkerResponse::Error(format!("invalid request: {}", error)),
+    };
+    serde_json::to_writer(&mut output, &response)?;
+    output.write_all(b"\n")?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use crate::{CancellationToken, EvaluationErrorKind, ProgrammingLanguage, code::CodeLimits};
+
+    use super::{CodeExecutor, SubprocessExecutor};
+
+    #[test]
+    fn test_subprocess_executor() {
+        let executor = SubprocessExecutor::system();
+        let limits = CodeLimits::default();
+        // skipped where the interpreter isn’t installed
+        if std::process::Command::new("python3")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            return;
+        }
+        assert_eq!(
+            executor
+                .execute(
+                    ProgrammingLanguage::Python,
+                    "def Z10010(Z10010K1):\n    print('ignored')\n    return Z10010K1[::-1]",
+                    "Z10010",
+                    &[json!("abc")],
+                    &limits,
+                    &CancellationToken::new(),
+                )
+                .unwrap(),
+            json!("cba")
+        );
+
+        let fails = |source: &str, limits: &CodeLimits| {
+            matches!(
+                executor.execute(
+                    ProgrammingLanguage::Python,
+                    source,
+                    "Z10010",
+                    &[],
+                    limits,
+                    &CancellationToken::new()
+                ),
+                Err(EvaluationErrorKind::CodeExecution(_))
+            )
+        };
+        assert!(fails("def Z1():\n    pass", &limits));
+        // killed
+        assert!(fails(
+            "def Z10010():\n    while True:\n        pass",
+            &CodeLimits {
+                max_duration: Duration::from_millis(200),
+                ..limits
+            }
+        ));
+        // out of memory
+        assert!(
+            !cfg!(unix)
+                || fails(
+                    "def Z10010():\n    return len(bytearray(10 ** 9))",
+                    &CodeLimits {
+                        max_memory: 256 * 1024 * 1024,
+                        ..limits
+                    }
+                )
+        );
+    }
+}
@@ -26,4 +26,52 @@ impl GlobalDatas {
     pub fn get(&self, reference: &Zid) -> Option<&DataEntry> {
         self.map.get(reference)
     }
+
+    /// The English (Z1002) label of the persistent object, if it has one
+    pub fn english_label(&self, reference: &Zid) -> Option<&str> {
+        self.get(reference)?
+            .get_map_entry(&zid!(2, 3))
+            .and_then(|labels| labels.get_map_entry(&zid!(12, 1)))
+            .and_then(DataEntry::get_array)
+            .ok()?
+            .iter()
+            // the first element is the type of the typed list
+            .skip(1)
+            .find(|label| {
+                label
+                    .get_map_entry(&zid!(11, 1))
+                    .is_ok_and(|language| language == &DataEntry::String("Z1002".to_string()))
+            })?
+            .get_map_entry(&zid!(11, 2))
+            .and_then(|text| text.get_str())
+            .ok()
+    }
+
+    /// The persistent objects whose value is a test case (Z20), sorted by ZID
+    pub fn test_cases(&self) -> Vec<Zid> {
+        self.objects_of_type("Z20")
+    }
+
+    /// The persistent objects whose value is a function (Z8), sorted by ZID
+    pub fn functions(&self) -> Vec<Zid> {
+        self.objects_of_type("Z8")
+    }
+
+    fn objects_of_type(&self, object_type: &str) -> Vec<Zid> {
+        let mut result: Vec<Zid> = self
+            .map
+            .iter()
+            .filter(|(_, entry)| {
+                entry
+                    .get_map_entry(&zid!(2, 2))
+                    .and_then(|value| value.get_map_entry(&zid!(1, 1)))
+                    .is_ok_and(|value_type| {
+                        value_type == &DataEntry::String(object_type.to_string())
+                    })
+            })
+            .map(|(zid, _)| *zid)
+            .collect();
+        result.sort();
+        result
+    }
 }
@@ -0,0 +1,401 @@
+//! Run JavaScript with Boa. The engine has no access to the filesystem nor to the network, as nothing is registered for it.
+
+use boa_engine::{Context, JsError, JsString, JsValue, Source};
+use serde_json::Value;
+
+use crate::{EvaluationErrorKind, code::CodeLimits};
+
+fn code_error(error: JsError) -> EvaluationErrorKind {
+    EvaluationErrorKind::CodeExecution(error.to_string())
+}
+
+/// Run the source in a fresh engine, then call the function of that name it defines with the arguments.
+/// Boa can’t be interrupted, and aborts when out of memory: only the loop iteration, recursion and stack size limits apply, unless it runs in a worker process (see SubprocessExecutor::embedded).
+pub fn run_javascript(
+    source: &str,
+    function_name: &str,
+    arguments: &[Value],
+    limits: &CodeLimits,
+) -> Result<Value, EvaluationErrorKind> {
+    let mut context = Context::default();
+    let runtime_limits = context.runtime_limits_mut();
+    runtime_limits.set_loop_iteration_limit(limits.max_loop_iterations);
+    runtime_limits.set_recursion_limit(limits.max_recursion);
+    runtime_limits.set_stack_size_limit(limits.max_stack_size);
+
+    context
+        .eval(Source::from_bytes(source))
+        .map_err(code_error)?;
+    let function = context
+        .global_object()
+        .get(JsString::from(function_name), &mut context)
+        .map_err(code_error)?;
+    let Some(function) = function.as_callable() else {
+        return Err(EvaluationErrorKind::CodeExecution(format!(
+            "the code doesn’t define the function {}",
+            function_name
+        )));
+    };
+    let arguments = arguments
+        .iter()
+        .map(|argument| JsValue::from_json(argument, &mut context))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(code_error)?;
+    let result = function
+        .call(&JsValue::undefined(), &arguments, &mut context)
+        .map_err(code_error)?;
+    // undefined is the unit, like null
+    Ok(result
+        .to_json(&mut context)
+        .map_err(code_error)?
+        .unwrap_or(Value::Null))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use crate::{
+        CancellationToken, CodeExecutor, DataEntry, EvaluationErrorKind, EvaluationLimit,
+        ProgrammingLanguage, Runner, RunnerOption, SubprocessExecutor,
+        code::CodeLimits,
+        parse_tool::{WfFunctionCall, WfParse},
+        serve_worker,
+        test_fixture::{add_function, add_persistent, add_type, add_type_converter, fixture_datas},
+    };
+
+    use super::run_javascript;
+
+    #[test]
+    fn test_run_javascript() {
+        let limits = CodeLimits::default();
+        assert_eq!(
+            run_javascript(
+                "function Z10010( Z10010K1, Z10010K2 ) { return Z10010K1.map( ( x ) => x + Z10010K2 ); }",
+                "Z10010",
+                &[json!(["a", "b"]), json!("!")],
+                &limits,
+            )
+            .unwrap(),
+            json!(["a!", "b!"])
+        );
+
+        // no function of that name
+        assert!(matches!(
+            run_javascript("function Z1( ) { }", "Z10010", &[], &limits),
+            Err(EvaluationErrorKind::CodeExecution(_))
+        ));
+        // the loop iteration limit stops infinite loops
+        assert!(matches!(
+            run_javascript(
+                "function Z10010( ) { while ( true ) { } }",
+                "Z10010",
+                &[],
+                &limits
+            ),
+            Err(EvaluationErrorKind::CodeExecution(_))
+        ));
+        // there is nothing to reach the outside world
+        assert!(matches!(
+            run_javascript(
+                "function Z10010( ) { return require( 'fs' ); }",
+                "Z10010",
+                &[],
+                &limits
+            ),
+            Err(EvaluationErrorKind::CodeExecution(_))
+        ));
+    }
+
+    /// Runs JavaScript in worker processes of the test binary, each running only the `worker` test below
+    fn worker_executor() -> SubprocessExecutor {
+        let executable = std::env::current_exe().unwrap();
+        SubprocessExecutor::new().with_command(
+            ProgrammingLanguage::JavaScript,
+            [
+                executable.to_str().unwrap(),
+                "javascript::tests::worker",
+                WORKER_FILTER,
+                "--exact",
+                "--nocapture",
+                "--quiet",
+            ],
+        )
+    }
+
+    /// Matches no test, only there to tell the worker processes from a normal run
+    const WORKER_FILTER: &str = "javascript-worker";
+
+    #[test]
+    fn worker() {
+        if std::env::args().any(|argument| argument == WORKER_FILTER) {
+            serve_worker(
+                ProgrammingLanguage::JavaScript,
+                std::io::stdin(),
+                std::io::stdout(),
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_javascript_limits() {
+        let executor = worker_executor();
+        let fails = |source: &str, limits: &CodeLimits| {
+            matches!(
+                executor.execute(
+                    ProgrammingLanguage::JavaScript,
+                    source,
+                    "Z10010",
+                    &[],
+                    limits,
+                    &CancellationToken::new()
+                ),
+                Err(EvaluationErrorKind::CodeExecution(_))
+            )
+        };
+        // killed, even when the loop iteration limit doesn’t stop it
+        assert!(fails(
+            "function Z10010( ) { while ( true ) { } }",
+            &CodeLimits {
+                max_loop_iterations: u64::MAX,
+                max_duration: Duration::from_millis(500),
+                ..Default::default()
+            }
+        ));
+        // a single call to a built-in, stopped by whichever limit it reaches first
+        let limits = CodeLimits {
+            max_memory: 256 * 1024 * 1024,
+            max_duration: Duration::from_secs(2),
+            ..Default::default()
+        };
+        for source in [
+            "function Z10010( ) { return 'a'.repeat( 2 ** 30 ).length; }",
+            "function Z10010( ) { return new Array( 1e9 ).fill( 0 ).length; }",
+        ] {
+            assert!(!cfg!(unix) || fails(source, &limits), "{}", source);
+        }
+        // still enough for the rest
+        assert_eq!(
+            executor
+                .execute(
+                    ProgrammingLanguage::JavaScript,
+                    "function Z10010( ) { return 'a'.repeat( 2 ** 20 ).length; }",
+                    "Z10010",
+                    &[],
+                    &limits,
+                    &CancellationToken::new()
+                )
+                .unwrap(),
+            json!(1048576)
+        );
+    }
+
+    #[test]
+    fn test_worker_cancelled() {
+        let cancellation_token = CancellationToken::new();
+        let canceller = {
+            let cancellation_token = cancellation_token.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(300));
+                cancellation_token.cancel();
+            })
+        };
+        let started_at = std::time::Instant::now();
+        let result = worker_executor().execute(
+            ProgrammingLanguage::JavaScript,
+            "function Z10010( ) { while ( true ) { } }",
+            "Z10010",
+            &[],
+            &CodeLimits {
+                max_loop_iterations: u64::MAX,
+                max_duration: Duration::from_secs(60),
+                ..Default::default()
+            },
+            &cancellation_token,
+        );
+        canceller.join().unwrap();
+        assert!(matches!(result, Err(EvaluationErrorKind::Cancelled)));
+        assert!(started_at.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_javascript_implementation() {
+        let mut datas = fixture_datas();
+        add_function(
+            &mut datas,
+            "Z10030",
+            "join",
+            &["Z10030K1", "Z10030K2"],
+            "Z6",
+            &["Z10031"],
+        );
+        add_persistent(
+            &mut datas,
+            "Z10031",
+            "Z10031",
+            r#"{
+                "Z1K1": "Z14",
+                "Z14K1": "Z10030",
+                "Z14K3": {
+                    "Z1K1": "Z16",
+                    "Z16K1": "Z600",
+                    "Z16K2": "function Z10030( Z10030K1, Z10030K2 ) { return Z10030K2 ? Z10030K1.join( '' ) : ''; }"
+                }
+            }"#,
+        );
+        let runner = Runner::new(std::sync::Arc::new(datas));
+        // the boolean is computed by a composition before being converted
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{
+                "Z1K1": "Z7",
+                "Z7K1": "Z10030",
+                "Z10030K1": ["Z6", "a", { "Z1K1": "Z6", "Z6K1": "b" }],
+                "Z10030K2": {
+                    "Z1K1": "Z7",
+                    "Z7K1": "Z10001",
+                    "Z10001K1": { "Z1K1": "Z40", "Z40K1": "Z42" }
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            runner
+                .run_function_call(
+                    &WfFunctionCall::parse(&call).unwrap(),
+                    &RunnerOption::default()
+                )
+                .unwrap(),
+            serde_json::from_str::<DataEntry>(r#"{ "Z1K1": "Z6", "Z6K1": "ab" }"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_evaluation_timeout_bounds_code() {
+        let mut datas = fixture_datas();
+        add_function(
+            &mut datas,
+            "Z10034",
+            "spin",
+            &["Z10034K1"],
+            "Z6",
+            &["Z10035"],
+        );
+        add_persistent(
+            &mut datas,
+            "Z10035",
+            "Z10035",
+            r#"{
+                "Z1K1": "Z14",
+                "Z14K1": "Z10034",
+                "Z14K3": {
+                    "Z1K1": "Z16",
+                    "Z16K1": "Z600",
+                    "Z16K2": "function Z10034( Z10034K1 ) { while ( true ) {} }"
+                }
+            }"#,
+        );
+        let runner = Runner::new(std::sync::Arc::new(datas));
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{ "Z1K1": "Z7", "Z7K1": "Z10034", "Z10034K1": "a" }"#,
+        )
+        .unwrap();
+        // the code may run for much longer than what remains of the evaluation
+        let option = RunnerOption {
+            timeout: Some(Duration::from_millis(300)),
+            code_executor: Some(std::sync::Arc::new(worker_executor())),
+            code_limits: CodeLimits {
+                max_loop_iterations: u64::MAX,
+                max_duration: Duration::from_secs(60),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let started_at = std::time::Instant::now();
+        let error = runner
+            .run_function_call(&WfFunctionCall::parse(&call).unwrap(), &option)
+            .unwrap_err();
+        assert!(
+            matches!(
+                error.root(),
+                EvaluationErrorKind::LimitExceeded(EvaluationLimit::Time(_), _)
+            ),
+            "{:?}",
+            error
+        );
+        assert!(started_at.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_javascript_type_converters() {
+        let mut datas = fixture_datas();
+        add_type(&mut datas, "Z10040", &["Z10042"], &["Z10043"]);
+        add_type_converter(
+            &mut datas,
+            "Z10042",
+            "Z46",
+            "Z10040",
+            "Z600",
+            "function Z10042( Z10042K1 ) { return BigInt( Z10042K1.Z10040K1 ); }",
+        );
+        add_type_converter(
+            &mut datas,
+            "Z10043",
+            "Z64",
+            "Z10040",
+            "Z600",
+            "function Z10043( Z10043K1 ) { return { Z1K1: 'Z10040', Z10040K1: Z10043K1.toString() }; }",
+        );
+        add_persistent(
+            &mut datas,
+            "Z10044",
+            "double all",
+            r#"{
+                "Z1K1": "Z8",
+                "Z8K1": ["Z17", { "Z1K1": "Z17", "Z17K1": { "Z1K1": "Z7", "Z7K1": "Z881", "Z881K1": "Z10040" }, "Z17K2": "Z10044K1", "Z17K3": { "Z1K1": "Z12", "Z12K1": ["Z11"] } }],
+                "Z8K2": { "Z1K1": "Z7", "Z7K1": "Z881", "Z881K1": "Z10040" },
+                "Z8K3": ["Z20"],
+                "Z8K4": ["Z14", "Z10045"],
+                "Z8K5": "Z10044"
+            }"#,
+        );
+        add_persistent(
+            &mut datas,
+            "Z10045",
+            "Z10045",
+            r#"{
+                "Z1K1": "Z14",
+                "Z14K1": "Z10044",
+                "Z14K3": {
+                    "Z1K1": "Z16",
+                    "Z16K1": "Z600",
+                    "Z16K2": "function Z10044( Z10044K1 ) { return Z10044K1.map( ( x ) => x * 2n ); }"
+                }
+            }"#,
+        );
+        let runner = Runner::new(std::sync::Arc::new(datas));
+        // beyond what a JavaScript number holds exactly
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{
+                "Z1K1": "Z7",
+                "Z7K1": "Z10044",
+                "Z10044K1": ["Z10040", { "Z1K1": "Z10040", "Z10040K1": "9007199254740993" }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            runner
+                .run_function_call(
+                    &WfFunctionCall::parse(&call).unwrap(),
+                    &RunnerOption::default()
+                )
+                .unwrap(),
+            serde_json::from_str::<DataEntry>(
+                r#"["Z10040", { "Z1K1": "Z10040", "Z10040K1": "18014398509481986" }]"#
+            )
+            .unwrap()
+        );
+    }
+}
@@ -9,12 +9,59 @@ mod globaldatas;
 pub use globaldatas::GlobalDatas;
 
 mod runner;
-pub use runner::{Runner, RunnerOption};
+pub use runner::{ChosenImplementation, EvaluationStrategy, Runner, RunnerOption};
 
 mod evaluation_error;
-pub use evaluation_error::{EvaluationError, EvaluationErrorKind};
+pub use evaluation_error::{EvaluationError, EvaluationErrorKind, EvaluationLimit};
+
+mod cancellation;
+pub use cancellation::CancellationToken;
+mod evaluation_context;
+pub use evaluation_context::EvaluationContext;
+mod call_cache;
+pub use call_cache::{CachedCall, CallCache, CallCacheStats, CallKey};
+mod disk_cache;
+pub use disk_cache::DiskCache;
+mod debugger;
+pub use debugger::{
+    ArgumentBinding, Breakpoint, DebugCommand, DebugFrame, DebugHandler, DebugProtocol, DebugStop,
+    Debugger, LineDebugHandler,
+};
+mod evaluation_stack;
+pub use evaluation_stack::{Continuation, Step, Trace};
 
 pub mod parse_tool;
+mod profiler;
+pub use profiler::{Profiler, Span};
 
+mod builtin;
+pub use builtin::{Builtin, BuiltinArguments, BuiltinRegistry, FnBuiltin};
+mod code;
+pub use code::{CodeLimits, Conversion, ConverterDirection, ProgrammingLanguage};
 mod composition_tool;
+#[allow(deprecated)]
 pub use composition_tool::recurse_and_replace_placeholder;
+mod differential;
+pub use differential::DifferentialReport;
+mod executor;
+pub use executor::{CodeExecutor, EmbeddedExecutor, SubprocessExecutor, serve_worker};
+#[cfg(feature = "javascript")]
+mod javascript;
+#[cfg(feature = "python")]
+mod python;
+
+mod selection;
+pub use selection::{ImplementationKind, RandomChoice, SelectionNote, SelectionPolicy};
+mod numbers;
+pub use numbers::{NUMBER_FUNCTIONS, NUMBER_OPERATIONS, NumberOperation, NumberType, NumberValue};
+mod shim;
+pub use shim::{DEFAULT_SHIMS, DefaultShim, ShimCheck, ShimRegistry};
+mod test_runner;
+pub use test_runner::{TestJob, TestOutcome, TestReport};
+mod thunk;
+mod tracer;
+pub use thunk::{Scope, Thunk};
+pub use tracer::{TracedCall, Tracer};
+
+#[cfg(test)]
+mod test_fixture;
@@ -1,9 +1,32 @@
-use std::{fs::File, io::BufReader, sync::Arc};
+use std::{
+    env,
+    fs::File,
+    io::{self, BufReader},
+    num::NonZeroUsize,
+    sync::Arc,
+    thread,
+    time::Duration,
+};
 
-use anyhow::Context;
-use wikifunctions_interpreter::{GlobalDatas, Runner, RunnerOption, Zid, parse_tool::WfTestCase};
+use anyhow::{Context, bail};
+use wikifunctions_interpreter::{
+    Breakpoint, CodeExecutor, DataEntry, DebugProtocol, Debugger, GlobalDatas, LineDebugHandler,
+    NumberOperation, Profiler, ProgrammingLanguage, RandomChoice, Runner, RunnerOption,
+    SelectionPolicy, SubprocessExecutor, TestJob, TestOutcome, Tracer, Zid, serve_worker,
+};
 
 fn main() -> anyhow::Result<()> {
+    // --worker LANGUAGE serves a single request of the code executor with the embedded engine of that language, see src/executor.rs
+    let arguments = env::args().collect::<Vec<_>>();
+    if let Some(position) = arguments.iter().position(|argument| argument == "--worker") {
+        let language = arguments
+            .get(position + 1)
+            .context("missing the language after --worker")?;
+        let language = ProgrammingLanguage::parse(&DataEntry::String(language.clone()))
+            .context("parsing the language after --worker")?;
+        return Ok(serve_worker(language, io::stdin(), io::stdout())?);
+    }
+
     let file =
         BufReader::new(File::open("./wikifunctionswiki-20251201-pages-meta-current.xml").unwrap());
     let mut gb = GlobalDatas::default();
@@ -14,28 +37,303 @@ fn main() -> anyhow::Result<()> {
         }
     }
 
-    let runner = Runner::new(Arc::new(gb));
-
-    for test_to_run in [
-        // is empty list
-        "Z8130", "Z8131", // reify/abstract
-        "Z15796", "Z15800", // other stuff
-        "Z10071",
-    ] {
-        let test_case_persistent = runner
-            .get_persistent_object::<WfTestCase>(&Zid::from_zid(test_to_run).unwrap())
-            .unwrap();
-        let function = test_case_persistent
-            .value
-            .function
-            .evaluate(&runner)
-            .unwrap();
-        let implementation_persistant = runner
-            .get_preferred_implementation(&function, &RunnerOption::default())
-            .unwrap();
-        runner
-            .run_test_case(&test_case_persistent, &implementation_persistant)
-            .with_context(|| format!("running the test case {}", test_to_run))?;
+    let datas = Arc::new(gb);
+    let runner = Runner::new(datas.clone());
+    // --all runs every test case of the dump, --threads N sets the number of threads used,
+    // --trace FILE writes the tree of the calls performed to FILE, as JSON,
+    // --profile PREFIX writes their timings to PREFIX.json (Chrome trace) and PREFIX.folded (flamegraph),
+    // and --debug (or --debug-json, for JSON-RPC) runs them one at a time in a debugger on the standard input and output,
+    // stopping at the first call and at each --break ZID function or implementation.
+    // --deny ZID never runs that implementation, and --random SEED picks the implementations at random.
+    // --native-numbers FILE runs the functions on naturals, integers, rationals and float64s listed in FILE natively,
+    // one per line as the ZID of the function then the name of its operation, like "Z12345 add naturals",
+    // and --check-shims compares them with the code implementations on the calls of their test cases instead.
+    // --differential ZID runs the test cases of that function with each of its implementations instead, reporting where they disagree,
+    // and --repl evaluates the function calls read from the standard input instead, in the debugger (with --debug-json, as JSON-RPC "evaluate" requests)
+    let trace_file = arguments
+        .iter()
+        .position(|argument| argument == "--trace")
+        .map(|position| {
+            arguments
+                .get(position + 1)
+                .context("missing the file name after --trace")
+        })
+        .transpose()?;
+    let profile_prefix = arguments
+        .iter()
+        .position(|argument| argument == "--profile")
+        .map(|position| {
+            arguments
+                .get(position + 1)
+                .context("missing the file name prefix after --profile")
+        })
+        .transpose()?;
+    let debug_protocol = if arguments.iter().any(|argument| argument == "--debug") {
+        Some(DebugProtocol::Text)
+    } else if arguments.iter().any(|argument| argument == "--debug-json") {
+        Some(DebugProtocol::JsonRpc)
+    } else {
+        None
+    };
+    let debugger = debug_protocol
+        .map(|protocol| -> anyhow::Result<Debugger> {
+            let debugger = Debugger::new(LineDebugHandler::new(
+                protocol,
+                BufReader::new(io::stdin()),
+                io::stdout(),
+            ))
+            .with_stop_on_entry();
+            for (position, _) in arguments
+                .iter()
+                .enumerate()
+                .filter(|(_, argument)| *argument == "--break")
+            {
+                let zid = arguments
+                    .get(position + 1)
+                    .context("missing the ZID after --break")?;
+                let zid = Zid::from_zid(zid).context("parsing the ZID after --break")?;
+                // the same ZID can’t be both a function and an implementation
+                debugger.add_breakpoint(Breakpoint::Function(zid));
+                debugger.add_breakpoint(Breakpoint::Implementation(zid));
+            }
+            Ok(debugger)
+        })
+        .transpose()?;
+
+    let mut selection = SelectionPolicy::default();
+    for (position, _) in arguments
+        .iter()
+        .enumerate()
+        .filter(|(_, argument)| *argument == "--deny")
+    {
+        let zid = arguments
+            .get(position + 1)
+            .context("missing the ZID after --deny")?;
+        selection
+            .deny
+            .insert(Zid::from_zid(zid).context("parsing the ZID after --deny")?);
+    }
+    if let Some(position) = arguments.iter().position(|argument| argument == "--random") {
+        let seed = arguments
+            .get(position + 1)
+            .context("missing the seed after --random")?
+            .parse()
+            .context("parsing the seed after --random")?;
+        selection.random = Some(RandomChoice::new(seed));
+    }
+
+    // so that a non-terminating test case doesn’t hang or exhaust the memory. The timeout would also count the time spent stopped in the debugger.
+    let mut option = RunnerOption {
+        max_depth: Some(500),
+        timeout: debugger.is_none().then_some(Duration::from_secs(30)),
+        tracer: trace_file.map(|_| Tracer::new()),
+        profiler: profile_prefix.map(|_| Profiler::new()),
+        debugger,
+        // run code in the system interpreters rather than the embedded ones, that still run in worker processes for the time and memory limits to apply
+        code_executor: Some(
+            if arguments.iter().any(|argument| argument == "--subprocess") {
+                Arc::new(SubprocessExecutor::system()) as Arc<dyn CodeExecutor>
+            } else {
+                Arc::new(SubprocessExecutor::embedded().context("locating the interpreter")?)
+            },
+        ),
+        selection,
+        ..Default::default()
+    };
+
+    if let Some(position) = arguments
+        .iter()
+        .position(|argument| argument == "--native-numbers")
+    {
+        let file = arguments
+            .get(position + 1)
+            .context("missing the file name after --native-numbers")?;
+        let functions = std::fs::read_to_string(file)
+            .context("reading the number functions")?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (function, name) = line
+                    .split_once(' ')
+                    .with_context(|| format!("missing the operation of {:?}", line))?;
+                let function = Zid::from_zid(function)
+                    .with_context(|| format!("parsing the ZID of {:?}", line))?;
+                let operation = NumberOperation::named(name.trim())
+                    .with_context(|| format!("no operation is named {:?}", name))?;
+                Ok((function, operation))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut shims = option
+            .shims
+            .take()
+            .unwrap_or_else(|| runner.get_shims().clone());
+        runner.register_number_functions(&mut shims, &functions)?;
+        option.shims = Some(shims);
+    }
+
+    if arguments.iter().any(|argument| argument == "--check-shims") {
+        let mut disagreements = 0;
+        for check in runner.check_shims(&option)? {
+            if check.agrees() {
+                println!(
+                    "{} on {}: agrees with {}",
+                    check.function, check.test_case, check.implementation
+                );
+                continue;
+            }
+            if check.both_fail() {
+                println!(
+                    "{} on {}: fails along with {}",
+                    check.function, check.test_case, check.implementation
+                );
+            } else {
+                disagreements += 1;
+                println!(
+                    "{} on {}: disagrees with {}",
+                    check.function, check.test_case, check.implementation
+                );
+            }
+            for (source, result) in [("shim", &check.shim_result), ("code", &check.code_result)] {
+                match result {
+                    Ok(value) => println!("  {}: {}", source, serde_json::to_string(value)?),
+                    Err(error) => println!("  {}: error: {}", source, error),
+                }
+            }
+        }
+        if disagreements > 0 {
+            bail!(
+                "the shims disagree with the code on {} calls",
+                disagreements
+            );
+        }
+        return Ok(());
+    }
+
+    if arguments.iter().any(|argument| argument == "--repl") {
+        // the time spent stopped in the debugger would count
+        let option = RunnerOption {
+            timeout: None,
+            ..option
+        };
+        LineDebugHandler::new(
+            debug_protocol.unwrap_or(DebugProtocol::Text),
+            BufReader::new(io::stdin()),
+            io::stdout(),
+        )
+        .repl(&runner, &option);
+        return Ok(());
+    }
+
+    let differential_functions = arguments
+        .iter()
+        .enumerate()
+        .filter(|(_, argument)| *argument == "--differential")
+        .map(|(position, _)| {
+            let zid = arguments
+                .get(position + 1)
+                .context("missing the ZID after --differential")?;
+            Zid::from_zid(zid).context("parsing the ZID after --differential")
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if !differential_functions.is_empty() {
+        let mut disagreements = 0;
+        for function in differential_functions {
+            for report in runner.differential_test(&function, &option)? {
+                let test_case = report
+                    .test_case
+                    .map_or_else(|| "a call".to_string(), |test_case| test_case.to_string());
+                if report.agrees() {
+                    println!("{} on {}: all agree", function, test_case);
+                    continue;
+                }
+                if report.all_fail() {
+                    println!("{} on {}: all fail", function, test_case);
+                } else {
+                    disagreements += 1;
+                    println!("{} on {}: disagreement", function, test_case);
+                }
+                for (implementation, outcome) in &report.outcomes {
+                    match outcome {
+                        Ok(value) => {
+                            println!("  {}: {}", implementation, serde_json::to_string(value)?)
+                        }
+                        Err(error) => println!("  {}: error: {}", implementation, error),
+                    }
+                }
+            }
+        }
+        if disagreements > 0 {
+            bail!("the implementations disagree on {} calls", disagreements);
+        }
+        return Ok(());
+    }
+
+    let test_cases = if arguments.iter().any(|argument| argument == "--all") {
+        datas.test_cases()
+    } else {
+        [
+            // is empty list
+            "Z8130", "Z8131", // reify/abstract
+            "Z15796", "Z15800", // other stuff
+            "Z10071",
+        ]
+        .into_iter()
+        .map(|test_case| Zid::from_zid(test_case).unwrap())
+        .collect()
+    };
+    let jobs = test_cases
+        .into_iter()
+        .map(|test_case| TestJob {
+            test_case,
+            implementation: None,
+        })
+        .collect::<Vec<_>>();
+
+    let threads = match arguments
+        .iter()
+        .position(|argument| argument == "--threads")
+    {
+        Some(position) => arguments
+            .get(position + 1)
+            .context("missing the number of threads after --threads")?
+            .parse()
+            .context("parsing the number of threads")?,
+        None if option.debugger.is_some() => NonZeroUsize::MIN,
+        None => thread::available_parallelism().unwrap_or(NonZeroUsize::MIN),
+    };
+    let mut failures = 0;
+    for report in runner.run_test_jobs(&jobs, &option, threads) {
+        match report.outcome {
+            TestOutcome::Passed => println!("{}: passed", report.job.test_case),
+            TestOutcome::Failed(error) => {
+                failures += 1;
+                println!("{}: failed\n{:?}", report.job.test_case, error);
+            }
+            TestOutcome::Panicked(message) => {
+                failures += 1;
+                println!("{}: panicked: {}", report.job.test_case, message);
+            }
+        }
+    }
+    if let (Some(trace_file), Some(tracer)) = (trace_file, &option.tracer) {
+        std::fs::write(trace_file, serde_json::to_string_pretty(&tracer.to_json())?)
+            .context("writing the trace")?;
+    }
+    if let (Some(profile_prefix), Some(profiler)) = (profile_prefix, &option.profiler) {
+        std::fs::write(
+            format!("{}.json", profile_prefix),
+            serde_json::to_string(&profiler.to_chrome_trace())?,
+        )
+        .context("writing the Chrome trace")?;
+        std::fs::write(
+            format!("{}.folded", profile_prefix),
+            profiler.to_folded_stacks(),
+        )
+        .context("writing the folded stacks")?;
+    }
+    if failures > 0 {
+        bail!("{} test cases out of {} did not pass", failures, jobs.len());
     }
 
     Ok(())
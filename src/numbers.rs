@@ -0,0 +1,1476 @@
+//! Native arithmetic on the number types of Wikifunctions: naturals, integers and rationals with arbitrary precision, and IEEE 754 doubles. The operations run as shims, in place of the code implementations of the number functions, along with a few operations on strings and booleans.
+
+use std::{cmp::Ordering, fmt::Debug};
+
+use map_macro::btree_map;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+
+use crate::{
+    DataEntry, DefaultShim, EvaluationErrorKind, Runner, ShimRegistry, Zid,
+    parse_tool::{WfFunction, raw_string_to_object_string},
+    shim::default_shim,
+};
+
+const ZID_NATURAL_NUMBER: Zid = zid!(13518);
+const ZID_INTEGER: Zid = zid!(16683);
+const ZID_SIGN: Zid = zid!(16659);
+const ZID_FLOAT64: Zid = zid!(20838);
+const ZID_RATIONAL: Zid = zid!(19677);
+const ZID_POSITIVE: Zid = zid!(16660);
+const ZID_NEUTRAL: Zid = zid!(16661);
+const ZID_NEGATIVE: Zid = zid!(16662);
+
+/// A type the number operations take or return
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberType {
+    Natural,
+    Integer,
+    Float64,
+    Rational,
+    Sign,
+    Boolean,
+    String,
+}
+
+impl NumberType {
+    /// The type, from a reference to it
+    pub fn of(declared_type: &DataEntry) -> Option<Self> {
+        let declared_type = Zid::from_zid(declared_type.normalize().get_str().ok()?).ok()?;
+        Some(match declared_type {
+            ZID_NATURAL_NUMBER => Self::Natural,
+            ZID_INTEGER => Self::Integer,
+            ZID_FLOAT64 => Self::Float64,
+            ZID_RATIONAL => Self::Rational,
+            ZID_SIGN => Self::Sign,
+            _ if declared_type == zid!(40) => Self::Boolean,
+            _ if declared_type == zid!(6) => Self::String,
+            _ => return None,
+        })
+    }
+
+    fn parse(&self, entry: &DataEntry) -> Result<NumberValue, EvaluationErrorKind> {
+        let entry = entry.normalize();
+        Ok(match self {
+            Self::Natural => NumberValue::Number(parse_natural(&entry)?),
+            Self::Integer => NumberValue::Number(parse_integer(&entry)?),
+            Self::Float64 => NumberValue::Float(parse_float(&entry)?),
+            Self::Rational => {
+                check_type(&entry, ZID_RATIONAL)?;
+                rational(
+                    parse_integer(entry.get_map_entry(&zid!(19677, 1))?)?,
+                    parse_natural(entry.get_map_entry(&zid!(19677, 2))?)?,
+                )?
+            }
+            Self::Sign => NumberValue::Sign(parse_sign(&entry)?),
+            Self::Boolean => {
+                check_type(&entry, zid!(40))?;
+                NumberValue::Boolean(match entry.get_map_entry(&zid!(40, 1))?.get_str()? {
+                    "Z41" => true,
+                    "Z42" => false,
+                    other => {
+                        return Err(EvaluationErrorKind::Arithmetic(format!(
+                            "{} isn’t a boolean",
+                            other
+                        )));
+                    }
+                })
+            }
+            Self::String => NumberValue::String(entry.get_str()?.to_string()),
+        })
+    }
+
+    fn produce(&self, value: NumberValue) -> Result<DataEntry, EvaluationErrorKind> {
+        Ok(match (self, value) {
+            (Self::Natural, NumberValue::Number(number)) => natural_to_entry(&number)?,
+            (Self::Integer, NumberValue::Number(number)) => integer_to_entry(&number),
+            (Self::Float64, NumberValue::Float(value)) => float_to_entry(value),
+            (Self::Rational, NumberValue::Rational(numerator, denominator)) => {
+                DataEntry::IdMap(btree_map! {
+                    zid!(1, 1) => DataEntry::String(ZID_RATIONAL.to_zid()),
+                    zid!(19677, 1) => integer_to_entry(&numerator),
+                    zid!(19677, 2) => natural_to_entry(&denominator)?,
+                })
+            }
+            (Self::Sign, NumberValue::Sign(sign)) => sign_to_entry(sign),
+            (Self::Boolean, NumberValue::Boolean(value)) => DataEntry::IdMap(btree_map! {
+                zid!(1, 1) => DataEntry::String("Z40".to_string()),
+                zid!(40, 1) => DataEntry::String(if value { "Z41" } else { "Z42" }.to_string()),
+            }),
+            (Self::String, NumberValue::String(text)) => raw_string_to_object_string(text),
+            (_, value) => {
+                return Err(EvaluationErrorKind::Arithmetic(format!(
+                    "{:?} isn’t a {:?}",
+                    value, self
+                )));
+            }
+        })
+    }
+}
+
+trait SignOrdering {
+    fn sign_ordering(&self) -> Ordering;
+}
+
+impl SignOrdering for BigInt {
+    fn sign_ordering(&self) -> Ordering {
+        self.cmp(&BigInt::zero())
+    }
+}
+
+fn check_type(entry: &DataEntry, expected: Zid) -> Result<(), EvaluationErrorKind> {
+    let found = entry.get_map_entry(&zid!(1, 1))?.get_str()?;
+    if found == expected.to_zid() {
+        Ok(())
+    } else {
+        Err(EvaluationErrorKind::WrongType(
+            Zid::from_zid(found).map_err(EvaluationErrorKind::ParseZID)?,
+            expected,
+        ))
+    }
+}
+
+fn parse_natural(entry: &DataEntry) -> Result<BigInt, EvaluationErrorKind> {
+    check_type(entry, ZID_NATURAL_NUMBER)?;
+    parse_digits(entry.get_map_entry(&zid!(13518, 1))?.get_str()?)
+}
+
+/// Decimal digits only, as the dump writes natural numbers
+fn parse_digits(text: &str) -> Result<BigInt, EvaluationErrorKind> {
+    if text.is_empty() || !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(EvaluationErrorKind::Arithmetic(format!(
+            "{:?} isn’t a natural number",
+            text
+        )));
+    }
+    text.parse()
+        .map_err(|_| EvaluationErrorKind::Arithmetic(format!("{:?} isn’t a natural number", text)))
+}
+
+fn natural_to_entry(number: &BigInt) -> Result<DataEntry, EvaluationErrorKind> {
+    if number.is_negative() {
+        return Err(EvaluationErrorKind::Arithmetic(format!(
+            "{} isn’t a natural number",
+            number
+        )));
+    }
+    Ok(DataEntry::IdMap(btree_map! {
+        zid!(1, 1) => DataEntry::String(ZID_NATURAL_NUMBER.to_zid()),
+        zid!(13518, 1) => DataEntry::String(number.to_string()),
+    }))
+}
+
+fn parse_integer(entry: &DataEntry) -> Result<BigInt, EvaluationErrorKind> {
+    check_type(entry, ZID_INTEGER)?;
+    let sign = parse_sign(entry.get_map_entry(&zid!(16683, 1))?)?;
+    let absolute = parse_natural(entry.get_map_entry(&zid!(16683, 2))?)?;
+    if (sign == Ordering::Equal) != absolute.is_zero() {
+        return Err(EvaluationErrorKind::Arithmetic(format!(
+            "the integer of sign {:?} and absolute value {}",
+            sign, absolute
+        )));
+    }
+    Ok(match sign {
+        Ordering::Less => -absolute,
+        _ => absolute,
+    })
+}
+
+fn integer_to_entry(number: &BigInt) -> DataEntry {
+    DataEntry::IdMap(btree_map! {
+        zid!(1, 1) => DataEntry::String(ZID_INTEGER.to_zid()),
+        zid!(16683, 1) => sign_to_entry(number.sign_ordering()),
+        zid!(16683, 2) => DataEntry::IdMap(btree_map! {
+            zid!(1, 1) => DataEntry::String(ZID_NATURAL_NUMBER.to_zid()),
+            zid!(13518, 1) => DataEntry::String(number.abs().to_string()),
+        }),
+    })
+}
+
+/// A float64 holds the fields of the IEEE 754 double: the sign bit, the exponent without its bias (1024 for the infinities and NaN, -1023 for the zeros and subnormals), and the 52 bits of the fraction as the mantissa
+fn parse_float(entry: &DataEntry) -> Result<f64, EvaluationErrorKind> {
+    check_type(entry, ZID_FLOAT64)?;
+    let negative = parse_sign(entry.get_map_entry(&zid!(20838, 1))?)? == Ordering::Less;
+    let exponent = parse_integer(entry.get_map_entry(&zid!(20838, 2))?)?;
+    let mantissa = parse_natural(entry.get_map_entry(&zid!(20838, 3))?)?;
+    let biased_exponent = (exponent + 1023u32)
+        .to_u64()
+        .filter(|exponent| *exponent <= 0x7ff);
+    let mantissa = mantissa.to_u64().filter(|mantissa| *mantissa < 1 << 52);
+    let (Some(biased_exponent), Some(mantissa)) = (biased_exponent, mantissa) else {
+        return Err(EvaluationErrorKind::Arithmetic(
+            "the exponent or mantissa is out of the range of a float64".to_string(),
+        ));
+    };
+    Ok(f64::from_bits(
+        (negative as u64) << 63 | biased_exponent << 52 | mantissa,
+    ))
+}
+
+fn float_to_entry(value: f64) -> DataEntry {
+    // the quiet NaN JavaScript and Python return, whatever the payload of this one
+    let bits = if value.is_nan() { f64::NAN } else { value }.to_bits();
+    let sign = if bits >> 63 == 1 {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    };
+    let exponent = BigInt::from((bits >> 52 & 0x7ff) as i64 - 1023);
+    let mantissa = BigInt::from(bits & ((1 << 52) - 1));
+    DataEntry::IdMap(btree_map! {
+        zid!(1, 1) => DataEntry::String(ZID_FLOAT64.to_zid()),
+        zid!(20838, 1) => sign_to_entry(sign),
+        zid!(20838, 2) => integer_to_entry(&exponent),
+        zid!(20838, 3) => DataEntry::IdMap(btree_map! {
+            zid!(1, 1) => DataEntry::String(ZID_NATURAL_NUMBER.to_zid()),
+            zid!(13518, 1) => DataEntry::String(mantissa.to_string()),
+        }),
+    })
+}
+
+/// The rational in lowest terms, with a positive denominator
+fn rational(numerator: BigInt, denominator: BigInt) -> Result<NumberValue, EvaluationErrorKind> {
+    if denominator.is_zero() {
+        return Err(EvaluationErrorKind::Arithmetic(
+            "a rational with a zero denominator".to_string(),
+        ));
+    }
+    let divisor = numerator.gcd(&denominator) * denominator.sign_ordering() as i8;
+    Ok(NumberValue::Rational(
+        numerator / &divisor,
+        denominator / divisor,
+    ))
+}
+
+/// The double nearest to the rational, ties to even
+fn rational_to_float(numerator: &BigInt, denominator: &BigInt) -> f64 {
+    if numerator.is_negative() {
+        return -rational_to_float(&-numerator, denominator);
+    }
+    if numerator.is_zero() {
+        return 0.0;
+    }
+    // 2^exponent <= numerator / denominator < 2^(exponent + 1)
+    let scaled = |numerator: &BigInt, denominator: &BigInt, exponent: i64| {
+        if exponent < 0 {
+            (numerator << -exponent as u64, denominator.clone())
+        } else {
+            (numerator.clone(), denominator << exponent as u64)
+        }
+    };
+    let mut exponent = numerator.bits() as i64 - denominator.bits() as i64;
+    let (low, high) = scaled(numerator, denominator, exponent);
+    if low < high {
+        exponent -= 1;
+    }
+    if exponent > 1023 {
+        return f64::INFINITY;
+    }
+    // rounded once, at the precision of the result: 53 bits, or fewer for subnormals
+    let unit = (exponent - 52).max(-1074);
+    let (scaled_numerator, scaled_denominator) = scaled(numerator, denominator, unit);
+    let (mut quotient, remainder) = scaled_numerator.div_rem(&scaled_denominator);
+    match (remainder << 1u8).cmp(&scaled_denominator) {
+        Ordering::Greater => quotient += 1,
+        Ordering::Equal if quotient.is_odd() => quotient += 1,
+        _ => {}
+    }
+    // both are exact, and so is their product, unless it overflows to infinity
+    let quotient = quotient.to_u64().unwrap_or(u64::MAX) as f64;
+    let power = if unit < -1022 {
+        f64::from_bits(1 << (unit + 1074))
+    } else {
+        f64::from_bits(((unit + 1023) as u64) << 52)
+    };
+    quotient * power
+}
+
+/// A sign is one of the three persistent instances of Z16659, referenced or written out
+fn parse_sign(entry: &DataEntry) -> Result<Ordering, EvaluationErrorKind> {
+    let entry = entry.normalize();
+    let identity = match &entry {
+        DataEntry::String(identity) => identity.as_str(),
+        _ => {
+            check_type(&entry, ZID_SIGN)?;
+            entry.get_map_entry(&zid!(16659, 1))?.get_str()?
+        }
+    };
+    match Zid::from_zid(identity).map_err(EvaluationErrorKind::ParseZID)? {
+        ZID_POSITIVE => Ok(Ordering::Greater),
+        ZID_NEUTRAL => Ok(Ordering::Equal),
+        ZID_NEGATIVE => Ok(Ordering::Less),
+        other => Err(EvaluationErrorKind::Arithmetic(format!(
+            "{} isn’t a sign",
+            other
+        ))),
+    }
+}
+
+fn sign_to_entry(sign: Ordering) -> DataEntry {
+    let identity = match sign {
+        Ordering::Greater => ZID_POSITIVE,
+        Ordering::Equal => ZID_NEUTRAL,
+        Ordering::Less => ZID_NEGATIVE,
+    };
+    DataEntry::IdMap(btree_map! {
+        zid!(1, 1) => DataEntry::String(ZID_SIGN.to_zid()),
+        zid!(16659, 1) => DataEntry::String(identity.to_zid()),
+    })
+}
+
+/// A value of a NumberType. Naturals and integers are both numbers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NumberValue {
+    Number(BigInt),
+    Float(f64),
+    /// The numerator and the denominator, in lowest terms with a positive denominator
+    Rational(BigInt, BigInt),
+    Sign(Ordering),
+    Boolean(bool),
+    String(String),
+}
+
+type Apply = fn(&[NumberValue]) -> Result<NumberValue, EvaluationErrorKind>;
+
+/// An operation on numbers, that may stand in for the code implementations of a function of the same signature it is bound to
+#[derive(Clone, Copy)]
+pub struct NumberOperation {
+    pub name: &'static str,
+    pub arguments: &'static [NumberType],
+    pub result: NumberType,
+    apply: Apply,
+}
+
+impl Debug for NumberOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+impl NumberOperation {
+    /// Run the operation on the evaluated arguments, in declaration order
+    pub fn apply(&self, arguments: &[DataEntry]) -> Result<DataEntry, EvaluationErrorKind> {
+        if arguments.len() != self.arguments.len() {
+            return Err(EvaluationErrorKind::Arithmetic(format!(
+                "{} takes {} arguments, got {}",
+                self.name,
+                self.arguments.len(),
+                arguments.len()
+            )));
+        }
+        let values = self
+            .arguments
+            .iter()
+            .zip(arguments)
+            .enumerate()
+            .map(|(pos, (argument_type, argument))| {
+                argument_type.parse(argument).map_err(|e| {
+                    e.trace(format!("parsing the argument {} of {}", pos + 1, self.name))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.result.produce((self.apply)(&values)?)
+    }
+
+    /// The operation of NUMBER_OPERATIONS of that name
+    pub fn named(name: &str) -> Option<Self> {
+        NUMBER_OPERATIONS
+            .iter()
+            .find(|operation| operation.name == name)
+            .copied()
+    }
+
+    pub fn has_signature(&self, arguments: &[NumberType], result: NumberType) -> bool {
+        self.arguments == arguments && self.result == result
+    }
+}
+
+/// The N values, when they are all of the kind the function picks
+fn operands<'v, T, const N: usize>(
+    values: &'v [NumberValue],
+    kind: &str,
+    pick: fn(&'v NumberValue) -> Option<T>,
+) -> Result<[T; N], EvaluationErrorKind> {
+    values
+        .iter()
+        .map(pick)
+        .collect::<Option<Vec<_>>>()
+        .and_then(|operands| operands.try_into().ok())
+        .ok_or_else(|| {
+            EvaluationErrorKind::Arithmetic(format!("expected {} {}, got {:?}", N, kind, values))
+        })
+}
+
+fn numbers<const N: usize>(values: &[NumberValue]) -> Result<[&BigInt; N], EvaluationErrorKind> {
+    operands(values, "numbers", |value| match value {
+        NumberValue::Number(number) => Some(number),
+        _ => None,
+    })
+}
+
+fn floats<const N: usize>(values: &[NumberValue]) -> Result<[f64; N], EvaluationErrorKind> {
+    operands(values, "float64s", |value| match value {
+        NumberValue::Float(value) => Some(*value),
+        _ => None,
+    })
+}
+
+fn rationals<const N: usize>(
+    values: &[NumberValue],
+) -> Result<[(&BigInt, &BigInt); N], EvaluationErrorKind> {
+    operands(values, "rationals", |value| match value {
+        NumberValue::Rational(numerator, denominator) => Some((numerator, denominator)),
+        _ => None,
+    })
+}
+
+fn texts<const N: usize>(values: &[NumberValue]) -> Result<[&str; N], EvaluationErrorKind> {
+    operands(values, "strings", |value| match value {
+        NumberValue::String(text) => Some(text.as_str()),
+        _ => None,
+    })
+}
+
+fn booleans<const N: usize>(values: &[NumberValue]) -> Result<[bool; N], EvaluationErrorKind> {
+    operands(values, "booleans", |value| match value {
+        NumberValue::Boolean(value) => Some(*value),
+        _ => None,
+    })
+}
+
+fn non_zero(divisor: &BigInt) -> Result<&BigInt, EvaluationErrorKind> {
+    if divisor.is_zero() {
+        Err(EvaluationErrorKind::Arithmetic(
+            "division by zero".to_string(),
+        ))
+    } else {
+        Ok(divisor)
+    }
+}
+
+macro_rules! binary {
+    ($result:ident, |$a:ident, $b:ident| $body:expr) => {
+        |values| {
+            let [$a, $b] = numbers::<2>(values)?;
+            Ok(NumberValue::$result($body))
+        }
+    };
+}
+
+macro_rules! unary {
+    ($result:ident, |$a:ident| $body:expr) => {
+        |values| {
+            let [$a] = numbers::<1>(values)?;
+            Ok(NumberValue::$result($body))
+        }
+    };
+}
+
+use NumberType::{
+    Boolean, Float64 as Float, Integer as Int, Natural as Nat, Rational as Ratio, Sign,
+    String as Text,
+};
+
+const ADD: Apply = binary!(Number, |a, b| a + b);
+const SUBTRACT: Apply = binary!(Number, |a, b| a - b);
+const MULTIPLY: Apply = binary!(Number, |a, b| a * b);
+const TRUNCATED_DIVIDE: Apply = |values| {
+    let [a, b] = numbers::<2>(values)?;
+    Ok(NumberValue::Number(a / non_zero(b)?))
+};
+const FLOORED_DIVIDE: Apply = |values| {
+    let [a, b] = numbers::<2>(values)?;
+    Ok(NumberValue::Number(a.div_floor(non_zero(b)?)))
+};
+const TRUNCATED_REMAINDER: Apply = |values| {
+    let [a, b] = numbers::<2>(values)?;
+    Ok(NumberValue::Number(a % non_zero(b)?))
+};
+const FLOORED_MODULO: Apply = |values| {
+    let [a, b] = numbers::<2>(values)?;
+    Ok(NumberValue::Number(a.mod_floor(non_zero(b)?)))
+};
+const EQUAL: Apply = binary!(Boolean, |a, b| a == b);
+const LESS: Apply = binary!(Boolean, |a, b| a < b);
+const GREATER: Apply = binary!(Boolean, |a, b| a > b);
+const LESS_OR_EQUAL: Apply = binary!(Boolean, |a, b| a <= b);
+const GREATER_OR_EQUAL: Apply = binary!(Boolean, |a, b| a >= b);
+const COMPARE: Apply = binary!(Sign, |a, b| a.cmp(b));
+const IDENTITY: Apply = unary!(Number, |a| a.clone());
+const NEGATE: Apply = unary!(Number, |a| -a);
+const ABSOLUTE: Apply = unary!(Number, |a| a.abs());
+const SIGN: Apply = unary!(Sign, |a| a.sign_ordering());
+const SUCCESSOR: Apply = unary!(Number, |a| a + 1);
+const PREDECESSOR: Apply = unary!(Number, |a| a - 1);
+const TO_STRING: Apply = unary!(String, |a| a.to_string());
+const NATURAL_FROM_STRING: Apply = |values| match values {
+    [NumberValue::String(text)] => Ok(NumberValue::Number(parse_digits(text)?)),
+    _ => Err(EvaluationErrorKind::Arithmetic(format!(
+        "expected a string, got {:?}",
+        values
+    ))),
+};
+const INTEGER_FROM_STRING: Apply = |values| match values {
+    [NumberValue::String(text)] => Ok(NumberValue::Number(match text.strip_prefix('-') {
+        Some(digits) => -parse_digits(digits)?,
+        None => parse_digits(text)?,
+    })),
+    _ => Err(EvaluationErrorKind::Arithmetic(format!(
+        "expected a string, got {:?}",
+        values
+    ))),
+};
+
+macro_rules! float_binary {
+    ($result:ident, |$a:ident, $b:ident| $body:expr) => {
+        |values| {
+            let [$a, $b] = floats::<2>(values)?;
+            Ok(NumberValue::$result($body))
+        }
+    };
+}
+
+macro_rules! float_unary {
+    ($result:ident, |$a:ident| $body:expr) => {
+        |values| {
+            let [$a] = floats::<1>(values)?;
+            Ok(NumberValue::$result($body))
+        }
+    };
+}
+
+const FLOAT_ADD: Apply = float_binary!(Float, |a, b| a + b);
+const FLOAT_SUBTRACT: Apply = float_binary!(Float, |a, b| a - b);
+const FLOAT_MULTIPLY: Apply = float_binary!(Float, |a, b| a * b);
+const FLOAT_DIVIDE: Apply = float_binary!(Float, |a, b| a / b);
+const FLOAT_EQUAL: Apply = float_binary!(Boolean, |a, b| a == b);
+// NaN is identical to itself, and the zeros differ
+const FLOAT_IDENTICAL: Apply = float_binary!(Boolean, |a, b| {
+    a.to_bits() == b.to_bits() || a.is_nan() && b.is_nan()
+});
+const FLOAT_LESS: Apply = float_binary!(Boolean, |a, b| a < b);
+const FLOAT_GREATER: Apply = float_binary!(Boolean, |a, b| a > b);
+const FLOAT_NEGATE: Apply = float_unary!(Float, |a| -a);
+const FLOAT_ABSOLUTE: Apply = float_unary!(Float, |a| a.abs());
+const FLOAT_IS_NAN: Apply = float_unary!(Boolean, |a| a.is_nan());
+const INTEGER_TO_FLOAT: Apply = unary!(Float, |a| a.to_f64().unwrap_or(f64::NAN));
+const FLOAT_TO_INTEGER: Apply = |values| {
+    let [a] = floats::<1>(values)?;
+    BigInt::from_f64(a.trunc())
+        .map(NumberValue::Number)
+        .ok_or_else(|| EvaluationErrorKind::Arithmetic(format!("{} isn’t finite", a)))
+};
+const FLOAT_TO_RATIONAL: Apply = |values| {
+    let [a] = floats::<1>(values)?;
+    if !a.is_finite() {
+        return Err(EvaluationErrorKind::Arithmetic(format!(
+            "{} isn’t finite",
+            a
+        )));
+    }
+    let bits = a.to_bits();
+    let biased_exponent = (bits >> 52 & 0x7ff) as i64;
+    let fraction = bits & ((1 << 52) - 1);
+    // subnormals have no implicit leading bit, and the exponent of the smallest normals
+    let (mantissa, exponent) = match biased_exponent {
+        0 => (fraction, -1074),
+        _ => (fraction | 1 << 52, biased_exponent - 1075),
+    };
+    let mantissa = BigInt::from(mantissa) * if a.is_sign_negative() { -1 } else { 1 };
+    if exponent < 0 {
+        rational(mantissa, BigInt::one() << -exponent as u64)
+    } else {
+        rational(mantissa << exponent as u64, BigInt::one())
+    }
+};
+
+const RATIONAL_ADD: Apply = |values| {
+    let [(a, b), (c, d)] = rationals::<2>(values)?;
+    rational(a * d + c * b, b * d)
+};
+const RATIONAL_SUBTRACT: Apply = |values| {
+    let [(a, b), (c, d)] = rationals::<2>(values)?;
+    rational(a * d - c * b, b * d)
+};
+const RATIONAL_MULTIPLY: Apply = |values| {
+    let [(a, b), (c, d)] = rationals::<2>(values)?;
+    rational(a * c, b * d)
+};
+const RATIONAL_DIVIDE: Apply = |values| {
+    let [(a, b), (c, d)] = rationals::<2>(values)?;
+    rational(a * d, b * non_zero(c)?)
+};
+// the denominators are positive, so the cross products compare like the rationals
+const RATIONAL_COMPARE: Apply = |values| {
+    let [(a, b), (c, d)] = rationals::<2>(values)?;
+    Ok(NumberValue::Sign((a * d).cmp(&(c * b))))
+};
+const RATIONAL_EQUAL: Apply = |values| {
+    let [first, second] = rationals::<2>(values)?;
+    Ok(NumberValue::Boolean(first == second))
+};
+const RATIONAL_LESS: Apply = |values| {
+    let [(a, b), (c, d)] = rationals::<2>(values)?;
+    Ok(NumberValue::Boolean(a * d < c * b))
+};
+const RATIONAL_GREATER: Apply = |values| {
+    let [(a, b), (c, d)] = rationals::<2>(values)?;
+    Ok(NumberValue::Boolean(a * d > c * b))
+};
+const RATIONAL_NEGATE: Apply = |values| {
+    let [(a, b)] = rationals::<1>(values)?;
+    Ok(NumberValue::Rational(-a, b.clone()))
+};
+const INTEGER_TO_RATIONAL: Apply = |values| {
+    let [a] = numbers::<1>(values)?;
+    Ok(NumberValue::Rational(a.clone(), BigInt::one()))
+};
+const RATIONAL_TO_FLOAT: Apply = |values| {
+    let [(a, b)] = rationals::<1>(values)?;
+    Ok(NumberValue::Float(rational_to_float(a, b)))
+};
+const NUMERATOR: Apply = |values| {
+    let [(a, _)] = rationals::<1>(values)?;
+    Ok(NumberValue::Number(a.clone()))
+};
+const DENOMINATOR: Apply = |values| {
+    let [(_, b)] = rationals::<1>(values)?;
+    Ok(NumberValue::Number(b.clone()))
+};
+
+const JOIN: Apply = |values| {
+    let [a, b] = texts::<2>(values)?;
+    Ok(NumberValue::String(format!("{}{}", a, b)))
+};
+const REVERSE: Apply = |values| {
+    let [a] = texts::<1>(values)?;
+    Ok(NumberValue::String(a.chars().rev().collect()))
+};
+/// In code points, as the code implementations count them
+const LENGTH: Apply = |values| {
+    let [a] = texts::<1>(values)?;
+    Ok(NumberValue::Number(BigInt::from(a.chars().count())))
+};
+const IS_EMPTY: Apply = |values| {
+    let [a] = texts::<1>(values)?;
+    Ok(NumberValue::Boolean(a.is_empty()))
+};
+const UPPERCASE: Apply = |values| {
+    let [a] = texts::<1>(values)?;
+    Ok(NumberValue::String(a.to_uppercase()))
+};
+const LOWERCASE: Apply = |values| {
+    let [a] = texts::<1>(values)?;
+    Ok(NumberValue::String(a.to_lowercase()))
+};
+const AND: Apply = |values| {
+    let [a, b] = booleans::<2>(values)?;
+    Ok(NumberValue::Boolean(a && b))
+};
+const OR: Apply = |values| {
+    let [a, b] = booleans::<2>(values)?;
+    Ok(NumberValue::Boolean(a || b))
+};
+const NOT: Apply = |values| {
+    let [a] = booleans::<1>(values)?;
+    Ok(NumberValue::Boolean(!a))
+};
+
+const fn operation(
+    name: &'static str,
+    arguments: &'static [NumberType],
+    result: NumberType,
+    apply: Apply,
+) -> NumberOperation {
+    NumberOperation {
+        name,
+        arguments,
+        result,
+        apply,
+    }
+}
+
+/// The operations that may be run natively. Results out of the type, like a negative natural, are errors.
+pub const NUMBER_OPERATIONS: &[NumberOperation] = &[
+    operation("add naturals", &[Nat, Nat], Nat, ADD),
+    operation("subtract naturals", &[Nat, Nat], Nat, SUBTRACT),
+    operation("multiply naturals", &[Nat, Nat], Nat, MULTIPLY),
+    operation("divide naturals", &[Nat, Nat], Nat, TRUNCATED_DIVIDE),
+    operation("naturals modulo", &[Nat, Nat], Nat, TRUNCATED_REMAINDER),
+    operation("naturals equal", &[Nat, Nat], Boolean, EQUAL),
+    operation("natural less than", &[Nat, Nat], Boolean, LESS),
+    operation("natural greater than", &[Nat, Nat], Boolean, GREATER),
+    operation("natural at most", &[Nat, Nat], Boolean, LESS_OR_EQUAL),
+    operation("natural at least", &[Nat, Nat], Boolean, GREATER_OR_EQUAL),
+    operation("compare naturals", &[Nat, Nat], Sign, COMPARE),
+    operation("natural successor", &[Nat], Nat, SUCCESSOR),
+    operation("natural predecessor", &[Nat], Nat, PREDECESSOR),
+    operation("natural to string", &[Nat], Text, TO_STRING),
+    operation("string to natural", &[Text], Nat, NATURAL_FROM_STRING),
+    operation("add integers", &[Int, Int], Int, ADD),
+    operation("subtract integers", &[Int, Int], Int, SUBTRACT),
+    operation("multiply integers", &[Int, Int], Int, MULTIPLY),
+    operation(
+        "truncated integer division",
+        &[Int, Int],
+        Int,
+        TRUNCATED_DIVIDE,
+    ),
+    operation("floored integer division", &[Int, Int], Int, FLOORED_DIVIDE),
+    operation("integer remainder", &[Int, Int], Int, TRUNCATED_REMAINDER),
+    operation("integer modulo", &[Int, Int], Int, FLOORED_MODULO),
+    operation("integers equal", &[Int, Int], Boolean, EQUAL),
+    operation("integer less than", &[Int, Int], Boolean, LESS),
+    operation("integer greater than", &[Int, Int], Boolean, GREATER),
+    operation("integer at most", &[Int, Int], Boolean, LESS_OR_EQUAL),
+    operation("integer at least", &[Int, Int], Boolean, GREATER_OR_EQUAL),
+    operation("compare integers", &[Int, Int], Sign, COMPARE),
+    operation("negate integer", &[Int], Int, NEGATE),
+    operation("integer successor", &[Int], Int, SUCCESSOR),
+    operation("integer predecessor", &[Int], Int, PREDECESSOR),
+    operation("absolute value", &[Int], Nat, ABSOLUTE),
+    operation("sign of integer", &[Int], Sign, SIGN),
+    operation("natural to integer", &[Nat], Int, IDENTITY),
+    operation("integer to natural", &[Int], Nat, IDENTITY),
+    operation("integer to string", &[Int], Text, TO_STRING),
+    operation("string to integer", &[Text], Int, INTEGER_FROM_STRING),
+    operation("add float64s", &[Float, Float], Float, FLOAT_ADD),
+    operation("subtract float64s", &[Float, Float], Float, FLOAT_SUBTRACT),
+    operation("multiply float64s", &[Float, Float], Float, FLOAT_MULTIPLY),
+    operation("divide float64s", &[Float, Float], Float, FLOAT_DIVIDE),
+    operation("float64s equal", &[Float, Float], Boolean, FLOAT_EQUAL),
+    operation(
+        "float64s identical",
+        &[Float, Float],
+        Boolean,
+        FLOAT_IDENTICAL,
+    ),
+    operation("float64 less than", &[Float, Float], Boolean, FLOAT_LESS),
+    operation(
+        "float64 greater than",
+        &[Float, Float],
+        Boolean,
+        FLOAT_GREATER,
+    ),
+    operation("negate float64", &[Float], Float, FLOAT_NEGATE),
+    operation("absolute float64", &[Float], Float, FLOAT_ABSOLUTE),
+    operation("float64 is NaN", &[Float], Boolean, FLOAT_IS_NAN),
+    operation("integer to float64", &[Int], Float, INTEGER_TO_FLOAT),
+    operation("truncate float64", &[Float], Int, FLOAT_TO_INTEGER),
+    operation("float64 to rational", &[Float], Ratio, FLOAT_TO_RATIONAL),
+    operation("add rationals", &[Ratio, Ratio], Ratio, RATIONAL_ADD),
+    operation(
+        "subtract rationals",
+        &[Ratio, Ratio],
+        Ratio,
+        RATIONAL_SUBTRACT,
+    ),
+    operation(
+        "multiply rationals",
+        &[Ratio, Ratio],
+        Ratio,
+        RATIONAL_MULTIPLY,
+    ),
+    operation("divide rationals", &[Ratio, Ratio], Ratio, RATIONAL_DIVIDE),
+    operation("rationals equal", &[Ratio, Ratio], Boolean, RATIONAL_EQUAL),
+    operation(
+        "rational less than",
+        &[Ratio, Ratio],
+        Boolean,
+        RATIONAL_LESS,
+    ),
+    operation(
+        "rational greater than",
+        &[Ratio, Ratio],
+        Boolean,
+        RATIONAL_GREATER,
+    ),
+    operation("compare rationals", &[Ratio, Ratio], Sign, RATIONAL_COMPARE),
+    operation("negate rational", &[Ratio], Ratio, RATIONAL_NEGATE),
+    operation("integer to rational", &[Int], Ratio, INTEGER_TO_RATIONAL),
+    operation("rational to float64", &[Ratio], Float, RATIONAL_TO_FLOAT),
+    operation("numerator", &[Ratio], Int, NUMERATOR),
+    operation("denominator", &[Ratio], Nat, DENOMINATOR),
+    operation("join strings", &[Text, Text], Text, JOIN),
+    operation("reverse string", &[Text], Text, REVERSE),
+    operation("string length", &[Text], Nat, LENGTH),
+    operation("string is empty", &[Text], Boolean, IS_EMPTY),
+    operation("uppercase", &[Text], Text, UPPERCASE),
+    operation("lowercase", &[Text], Text, LOWERCASE),
+    operation("and", &[Boolean, Boolean], Boolean, AND),
+    operation("or", &[Boolean, Boolean], Boolean, OR),
+    operation("not", &[Boolean], Boolean, NOT),
+];
+
+/// The number functions of Wikifunctions the runner shims by default
+pub const NUMBER_FUNCTIONS: &[DefaultShim] = &[
+    default_shim(zid!(13522), "add natural numbers", "add naturals"),
+    default_shim(zid!(13541), "subtract natural numbers", "subtract naturals"),
+    default_shim(zid!(13539), "multiply natural numbers", "multiply naturals"),
+    default_shim(
+        zid!(13549),
+        "integer division of natural numbers",
+        "divide naturals",
+    ),
+    default_shim(zid!(13577), "natural number modulo", "naturals modulo"),
+    default_shim(zid!(13523), "natural numbers are equal", "naturals equal"),
+    default_shim(
+        zid!(13535),
+        "natural number is less than",
+        "natural less than",
+    ),
+    default_shim(
+        zid!(13536),
+        "natural number is greater than",
+        "natural greater than",
+    ),
+    default_shim(zid!(13524), "natural number to string", "natural to string"),
+    default_shim(zid!(13545), "string to natural number", "string to natural"),
+    default_shim(zid!(16693), "add integers", "add integers"),
+    default_shim(zid!(16700), "subtract integers", "subtract integers"),
+    default_shim(zid!(16701), "multiply integers", "multiply integers"),
+    default_shim(zid!(16688), "integers are equal", "integers equal"),
+    default_shim(zid!(16705), "integer is less than", "integer less than"),
+    default_shim(
+        zid!(16706),
+        "integer is greater than",
+        "integer greater than",
+    ),
+    default_shim(zid!(16704), "negate integer", "negate integer"),
+    default_shim(zid!(16712), "absolute value of integer", "absolute value"),
+    default_shim(
+        zid!(16680),
+        "natural number to integer",
+        "natural to integer",
+    ),
+    default_shim(zid!(16708), "integer to string", "integer to string"),
+    default_shim(zid!(21031), "add float64s", "add float64s"),
+    default_shim(zid!(21032), "subtract float64s", "subtract float64s"),
+    default_shim(zid!(21033), "multiply float64s", "multiply float64s"),
+    default_shim(zid!(21034), "divide float64s", "divide float64s"),
+    default_shim(zid!(21035), "float64s are equal", "float64s equal"),
+    default_shim(zid!(21038), "negate float64", "negate float64"),
+    default_shim(zid!(21040), "float64 is NaN", "float64 is NaN"),
+    default_shim(zid!(20915), "integer to float64", "integer to float64"),
+    default_shim(zid!(19680), "add rational numbers", "add rationals"),
+    default_shim(
+        zid!(19681),
+        "subtract rational numbers",
+        "subtract rationals",
+    ),
+    default_shim(
+        zid!(19682),
+        "multiply rational numbers",
+        "multiply rationals",
+    ),
+    default_shim(zid!(19683), "divide rational numbers", "divide rationals"),
+    default_shim(zid!(19684), "rational numbers are equal", "rationals equal"),
+    default_shim(zid!(19685), "negate rational number", "negate rational"),
+    default_shim(
+        zid!(19687),
+        "rational number to float64",
+        "rational to float64",
+    ),
+];
+
+impl Runner {
+    /// Whether the function declares the argument and return types of the operation
+    pub(crate) fn has_signature_of(
+        &self,
+        function_id: &Zid,
+        operation: &NumberOperation,
+    ) -> Result<bool, EvaluationErrorKind> {
+        let function = self
+            .get_persistent_object::<WfFunction>(function_id)
+            .map_err(|e| e.trace(format!("getting the function {}", function_id)))?
+            .value;
+        let arguments = function
+            .argument_declarations(self)?
+            .iter()
+            .map(|argument| NumberType::of(argument.argument_type.get_entry()))
+            .collect::<Option<Vec<_>>>();
+        let result = NumberType::of(function.return_type.get_entry());
+        Ok(arguments.is_some_and(|arguments| {
+            result.is_some_and(|result| operation.has_signature(&arguments, result))
+        }))
+    }
+
+    /// Run each function of the table with its operation, in place of its code implementations.
+    /// The table is explicit: the declared signature of each function is only checked against the one of its operation, and `check_shims` compares them on the test cases.
+    pub fn register_number_functions(
+        &self,
+        shims: &mut ShimRegistry,
+        functions: &[(Zid, NumberOperation)],
+    ) -> Result<(), EvaluationErrorKind> {
+        for (function_id, operation) in functions {
+            if !self.has_signature_of(function_id, operation)? {
+                return Err(EvaluationErrorKind::Arithmetic(format!(
+                    "{} doesn’t have the signature of {}",
+                    function_id, operation.name
+                )));
+            }
+            let operation = *operation;
+            shims.register(*function_id, move |arguments| operation.apply(arguments));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, sync::Arc};
+
+    use num_bigint::BigInt;
+    use num_traits::One;
+
+    use crate::{
+        DEFAULT_SHIMS, DataEntry, GlobalDatas, NUMBER_FUNCTIONS, NUMBER_OPERATIONS,
+        NumberOperation, NumberType, Runner, RunnerOption, ShimCheck, ShimRegistry, Zid,
+        parse_tool::{WfFunctionCall, WfParse},
+        test_fixture::{add_persistent, add_type, fixture_datas},
+    };
+
+    fn natural(digits: &str) -> String {
+        format!(r#"{{ "Z1K1": "Z13518", "Z13518K1": "{}" }}"#, digits)
+    }
+
+    fn integer(sign: &str, digits: &str) -> DataEntry {
+        serde_json::from_str(&format!(
+            r#"{{ "Z1K1": "Z16683", "Z16683K1": {{ "Z1K1": "Z16659", "Z16659K1": "{}" }}, "Z16683K2": {} }}"#,
+            sign,
+            natural(digits)
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_integer_operations() {
+        let operation = |name: &str| {
+            NUMBER_OPERATIONS
+                .iter()
+                .find(|operation| operation.name == name)
+                .unwrap()
+        };
+        let minus_seven = integer("Z16662", "7");
+        let two = integer("Z16660", "2");
+        // Python floors, JavaScript truncates
+        assert_eq!(
+            operation("floored integer division")
+                .apply(&[minus_seven.clone(), two.clone()])
+                .unwrap(),
+            integer("Z16662", "4")
+        );
+        assert_eq!(
+            operation("truncated integer division")
+                .apply(&[minus_seven.clone(), two.clone()])
+                .unwrap(),
+            integer("Z16662", "3")
+        );
+        assert_eq!(
+            operation("add integers")
+                .apply(&[minus_seven.clone(), integer("Z16660", "7")])
+                .unwrap(),
+            integer("Z16661", "0")
+        );
+        // well beyond 64 bits
+        assert_eq!(
+            operation("multiply integers")
+                .apply(&[
+                    integer("Z16660", "99999999999999999999"),
+                    integer("Z16662", "99999999999999999999")
+                ])
+                .unwrap(),
+            integer("Z16662", "9999999999999999999800000000000000000001")
+        );
+        assert!(
+            operation("subtract naturals")
+                .apply(&[
+                    serde_json::from_str(&natural("1")).unwrap(),
+                    serde_json::from_str(&natural("2")).unwrap()
+                ])
+                .is_err()
+        );
+        assert!(
+            operation("integer modulo")
+                .apply(&[minus_seven, integer("Z16661", "0")])
+                .is_err()
+        );
+    }
+
+    fn float(value: f64) -> DataEntry {
+        super::float_to_entry(value)
+    }
+
+    fn ratio(numerator: &str, denominator: &str) -> DataEntry {
+        let (sign, digits) = match numerator.strip_prefix('-') {
+            Some(digits) => ("Z16662", digits),
+            None if numerator == "0" => ("Z16661", numerator),
+            None => ("Z16660", numerator),
+        };
+        serde_json::from_str(&format!(
+            r#"{{ "Z1K1": "Z19677", "Z19677K1": {}, "Z19677K2": {} }}"#,
+            serde_json::to_string(&integer(sign, digits)).unwrap(),
+            natural(denominator)
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_float_and_rational_operations() {
+        let apply = |name: &str, arguments: &[DataEntry]| {
+            NUMBER_OPERATIONS
+                .iter()
+                .find(|operation| operation.name == name)
+                .unwrap()
+                .apply(arguments)
+        };
+        // the fields of the double
+        assert_eq!(
+            float(-2.5),
+            serde_json::from_str::<DataEntry>(&format!(
+                r#"{{ "Z1K1": "Z20838", "Z20838K1": {{ "Z1K1": "Z16659", "Z16659K1": "Z16662" }}, "Z20838K2": {}, "Z20838K3": {} }}"#,
+                serde_json::to_string(&integer("Z16660", "1")).unwrap(),
+                natural("1125899906842624")
+            ))
+            .unwrap()
+        );
+        assert_eq!(
+            apply("add float64s", &[float(0.1), float(0.2)]).unwrap(),
+            float(0.30000000000000004)
+        );
+        assert_eq!(
+            apply("divide float64s", &[float(-1.0), float(0.0)]).unwrap(),
+            float(f64::NEG_INFINITY)
+        );
+        // the positive quiet NaN, as JavaScript and Python return it
+        assert_eq!(
+            apply("divide float64s", &[float(0.0), float(0.0)]).unwrap(),
+            float(f64::NAN)
+        );
+        let negative_zero = apply("negate float64", &[float(0.0)]).unwrap();
+        assert_eq!(negative_zero, float(-0.0));
+        assert_ne!(negative_zero, float(0.0));
+        let boolean = |value: &str| {
+            serde_json::from_str::<DataEntry>(&format!(
+                r#"{{ "Z1K1": "Z40", "Z40K1": "{}" }}"#,
+                value
+            ))
+            .unwrap()
+        };
+        assert_eq!(
+            apply("float64s equal", &[negative_zero.clone(), float(0.0)]).unwrap(),
+            boolean("Z41")
+        );
+        assert_eq!(
+            apply("float64s identical", &[negative_zero, float(0.0)]).unwrap(),
+            boolean("Z42")
+        );
+        assert_eq!(
+            apply("float64s equal", &[float(f64::NAN), float(f64::NAN)]).unwrap(),
+            boolean("Z42")
+        );
+
+        assert_eq!(
+            apply("add rationals", &[ratio("1", "3"), ratio("1", "6")]).unwrap(),
+            ratio("1", "2")
+        );
+        assert_eq!(
+            apply("subtract rationals", &[ratio("1", "3"), ratio("1", "3")]).unwrap(),
+            ratio("0", "1")
+        );
+        assert_eq!(
+            apply("divide rationals", &[ratio("-2", "3"), ratio("4", "9")]).unwrap(),
+            ratio("-3", "2")
+        );
+        assert!(apply("divide rationals", &[ratio("1", "3"), ratio("0", "1")]).is_err());
+        assert_eq!(
+            apply("rational to float64", &[ratio("1", "3")]).unwrap(),
+            float(1.0 / 3.0)
+        );
+        assert_eq!(
+            apply("rational to float64", &[ratio("-1", "10")]).unwrap(),
+            float(-0.1)
+        );
+        // just below half of the smallest subnormal above it, which rounding to 53 bits first would make a tie
+        assert_eq!(
+            apply(
+                "rational to float64",
+                &[ratio(
+                    &((BigInt::from(3) << 59u8) - 1u8).to_string(),
+                    &(BigInt::one() << 1134u16).to_string()
+                )]
+            )
+            .unwrap(),
+            float(f64::from_bits(1))
+        );
+        // a tie, to even
+        assert_eq!(
+            apply(
+                "rational to float64",
+                &[ratio("3", &(BigInt::one() << 1075u16).to_string())]
+            )
+            .unwrap(),
+            float(f64::from_bits(2))
+        );
+        assert_eq!(
+            apply(
+                "rational to float64",
+                &[ratio(&(BigInt::one() << 1024u16).to_string(), "1")]
+            )
+            .unwrap(),
+            float(f64::INFINITY)
+        );
+        assert_eq!(
+            apply("float64 to rational", &[float(-0.375)]).unwrap(),
+            ratio("-3", "8")
+        );
+        assert!(apply("float64 to rational", &[float(f64::INFINITY)]).is_err());
+    }
+
+    /// A function shaped like the ones of the dump, with the code implementation of ZID {zid}1
+    fn function(zid: &str, arguments: &[&str], result: &str, testers: &[&str]) -> String {
+        let arguments = arguments
+            .iter()
+            .enumerate()
+            .map(|(pos, argument_type)| {
+                format!(
+                    r#"{{ "Z1K1": "Z17", "Z17K1": "{}", "Z17K2": "{}K{}", "Z17K3": {{ "Z1K1": "Z12", "Z12K1": ["Z11"] }} }}"#,
+                    argument_type,
+                    zid,
+                    pos + 1
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        // the first element is the type of the typed list
+        let testers = std::iter::once(&"Z20")
+            .chain(testers)
+            .map(|tester| format!(r#""{}""#, tester))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            r#"{{ "Z1K1": "Z8", "Z8K1": ["Z17", {}], "Z8K2": "{}", "Z8K3": [{}], "Z8K4": ["Z14", "{}1"], "Z8K5": "{}" }}"#,
+            arguments, result, testers, zid, zid
+        )
+    }
+
+    /// JavaScript code returning the body, from the arguments K1 and K2
+    fn implementation(function: &str, body: &str) -> String {
+        format!(
+            r#"{{ "Z1K1": "Z14", "Z14K1": "{}", "Z14K3": {{ "Z1K1": "Z16", "Z16K1": "Z600", "Z16K2": "function {}( {}K1, {}K2 ) {{ return {}; }}" }} }}"#,
+            function, function, function, function, body
+        )
+    }
+
+    #[test]
+    fn test_number_functions() {
+        let mut datas = fixture_datas();
+        let test_case = |function: &str, arguments: [String; 2], validator: &str| {
+            format!(
+                r#"{{ "Z1K1": "Z20", "Z20K1": "{}", "Z20K2": {{ "Z1K1": "Z7", "Z7K1": "{}", "{}K1": {}, "{}K2": {} }}, "Z20K3": {} }}"#,
+                function, function, function, arguments[0], function, arguments[1], validator
+            )
+        };
+        let boolean = |value: &str| {
+            format!(
+                r#"{{ "Z1K1": "Z7", "Z7K1": "Z844", "Z844K2": {{ "Z1K1": "Z40", "Z40K1": "{}" }} }}"#,
+                value
+            )
+        };
+        let natural_equal = |digits: &str| {
+            format!(
+                r#"{{ "Z1K1": "Z7", "Z7K1": "Z10070", "Z10070K2": {} }}"#,
+                natural(digits)
+            )
+        };
+
+        // passed to the code as it is
+        add_type(&mut datas, "Z13518", &[], &[]);
+        // equality of naturals, and their addition, whose test case relies on it
+        add_persistent(
+            &mut datas,
+            "Z10070",
+            "naturals equal",
+            &function(
+                "Z10070",
+                &["Z13518", "Z13518"],
+                "Z40",
+                &["Z10072", "Z10073", "Z10074"],
+            ),
+        );
+        add_persistent(
+            &mut datas,
+            "Z100701",
+            "Z100701",
+            &implementation("Z10070", "Z10070K1.Z13518K1 === Z10070K2.Z13518K1"),
+        );
+        for (zid, arguments, result) in [
+            ("Z10072", ["5", "5"], "Z41"),
+            ("Z10073", ["5", "6"], "Z42"),
+            ("Z10074", ["6", "5"], "Z42"),
+        ] {
+            add_persistent(
+                &mut datas,
+                zid,
+                zid,
+                &test_case("Z10070", arguments.map(natural), &boolean(result)),
+            );
+        }
+        add_persistent(
+            &mut datas,
+            "Z10071",
+            "add naturals",
+            &function("Z10071", &["Z13518", "Z13518"], "Z13518", &["Z10075"]),
+        );
+        add_persistent(
+            &mut datas,
+            "Z100711",
+            "Z100711",
+            &implementation(
+                "Z10071",
+                "{ Z1K1: 'Z13518', Z13518K1: String( BigInt( Z10071K1.Z13518K1 ) + BigInt( Z10071K2.Z13518K1 ) ) }",
+            ),
+        );
+        add_persistent(
+            &mut datas,
+            "Z10075",
+            "Z10075",
+            &test_case(
+                "Z10071",
+                ["123456789012345678901234567890", "1"].map(natural),
+                &natural_equal("123456789012345678901234567891"),
+            ),
+        );
+        let runner = Runner::new(Arc::new(datas));
+
+        let operation = |name: &str| NumberOperation::named(name).unwrap();
+        // the signature of the function must be the one of the operation
+        assert!(
+            runner
+                .register_number_functions(
+                    &mut ShimRegistry::new(),
+                    &[(zid!(10070), operation("add naturals"))]
+                )
+                .is_err()
+        );
+        let mut shims = ShimRegistry::new();
+        runner
+            .register_number_functions(
+                &mut shims,
+                &[
+                    (zid!(10070), operation("naturals equal")),
+                    (zid!(10071), operation("add naturals")),
+                ],
+            )
+            .unwrap();
+        assert_eq!(
+            shims.functions().copied().collect::<Vec<_>>(),
+            vec![zid!(10070), zid!(10071)]
+        );
+
+        let call = serde_json::from_str::<DataEntry>(&format!(
+            r#"{{ "Z1K1": "Z7", "Z7K1": "Z10071", "Z10071K1": {}, "Z10071K2": {} }}"#,
+            natural("18446744073709551615"),
+            natural("18446744073709551615")
+        ))
+        .unwrap();
+        let option = RunnerOption {
+            shims: Some(shims),
+            ..Default::default()
+        };
+        assert_eq!(
+            runner
+                .run_function_call(&WfFunctionCall::parse(&call).unwrap(), &option)
+                .unwrap(),
+            serde_json::from_str::<DataEntry>(&natural("36893488147419103230")).unwrap()
+        );
+
+        // the operations are compared with the code implementations on the calls of the test cases, and a wrong binding is found
+        let checks = runner.check_shims(&option).unwrap();
+        assert_eq!(checks.len(), 4);
+        assert_eq!(
+            checks.iter().all(ShimCheck::agrees),
+            cfg!(feature = "javascript")
+        );
+        let mut shims = ShimRegistry::new();
+        runner
+            .register_number_functions(&mut shims, &[(zid!(10071), operation("multiply naturals"))])
+            .unwrap();
+        let checks = runner
+            .check_shims(&RunnerOption {
+                shims: Some(shims),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(!checks[0].agrees());
+    }
+
+    fn type_zid(number_type: NumberType) -> &'static str {
+        match number_type {
+            NumberType::Natural => "Z13518",
+            NumberType::Integer => "Z16683",
+            NumberType::Float64 => "Z20838",
+            NumberType::Rational => "Z19677",
+            NumberType::Sign => "Z16659",
+            NumberType::Boolean => "Z40",
+            NumberType::String => "Z6",
+        }
+    }
+
+    /// The ZID of the function of NUMBER_FUNCTIONS run by the operation
+    fn number_function(operation: &str) -> String {
+        NUMBER_FUNCTIONS
+            .iter()
+            .find(|number_function| number_function.operation == operation)
+            .unwrap()
+            .function
+            .to_zid()
+    }
+
+    /// The functions of NUMBER_FUNCTIONS, with their label, the signature of their operation, and the given test cases
+    fn add_number_functions(datas: &mut GlobalDatas, testers: &[(&str, &str)]) {
+        for number_function in NUMBER_FUNCTIONS {
+            let testers = testers
+                .iter()
+                .filter(|(operation, _)| *operation == number_function.operation)
+                .map(|(_, tester)| *tester)
+                .collect::<Vec<_>>();
+            let operation = NumberOperation::named(number_function.operation).unwrap();
+            let zid = number_function.function.to_zid();
+            let arguments = operation
+                .arguments
+                .iter()
+                .map(|argument| type_zid(*argument))
+                .collect::<Vec<_>>();
+            add_persistent(
+                datas,
+                &zid,
+                number_function.label,
+                &function(&zid, &arguments, type_zid(operation.result), &testers),
+            );
+        }
+    }
+
+    #[test]
+    fn test_default_number_functions() {
+        assert!(
+            DEFAULT_SHIMS
+                .iter()
+                .chain(NUMBER_FUNCTIONS)
+                .all(|shim| NumberOperation::named(shim.operation).is_some())
+        );
+
+        let mut datas = fixture_datas();
+        add_type(&mut datas, "Z13518", &[], &[]);
+        add_number_functions(&mut datas, &[]);
+        let add = number_function("add naturals");
+        add_persistent(
+            &mut datas,
+            &format!("{}1", add),
+            &format!("{}1", add),
+            &implementation(
+                &add,
+                &format!(
+                    "{{ Z1K1: 'Z13518', Z13518K1: String( BigInt( {add}K1.Z13518K1 ) + BigInt( {add}K2.Z13518K1 ) ) }}"
+                ),
+            ),
+        );
+        let runner = Runner::new(Arc::new(datas));
+        assert_eq!(
+            runner
+                .get_shims()
+                .functions()
+                .copied()
+                .collect::<BTreeSet<_>>(),
+            NUMBER_FUNCTIONS
+                .iter()
+                .map(|number_function| number_function.function)
+                .collect()
+        );
+
+        // beyond 64 bits, without registering anything
+        let call = serde_json::from_str::<DataEntry>(&format!(
+            r#"{{ "Z1K1": "Z7", "Z7K1": "{add}", "{add}K1": {}, "{add}K2": {} }}"#,
+            natural("18446744073709551615"),
+            natural("18446744073709551617")
+        ))
+        .unwrap();
+        assert_eq!(
+            runner
+                .run_function_call(
+                    &WfFunctionCall::parse(&call).unwrap(),
+                    &RunnerOption::default()
+                )
+                .unwrap(),
+            serde_json::from_str::<DataEntry>(&natural("36893488147419103232")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_check_float_and_rational_shims() {
+        let mut datas = fixture_datas();
+        for number_type in ["Z20838", "Z19677"] {
+            add_type(&mut datas, number_type, &[], &[]);
+        }
+        add_number_functions(
+            &mut datas,
+            &[("negate float64", "Z10078"), ("negate rational", "Z10079")],
+        );
+        let negate_float = number_function("negate float64");
+        let negate_rational = number_function("negate rational");
+        let flip = "({ Z16660: 'Z16662', Z16661: 'Z16661', Z16662: 'Z16660' })";
+        add_persistent(
+            &mut datas,
+            &format!("{}1", negate_float),
+            &format!("{}1", negate_float),
+            &implementation(
+                &negate_float,
+                &format!(
+                    "{{ ...{negate_float}K1, Z20838K1: {{ Z1K1: 'Z16659', Z16659K1: {flip}[ {negate_float}K1.Z20838K1.Z16659K1 ] }} }}"
+                ),
+            ),
+        );
+        add_persistent(
+            &mut datas,
+            &format!("{}1", negate_rational),
+            &format!("{}1", negate_rational),
+            &implementation(
+                &negate_rational,
+                &format!(
+                    "{{ ...{negate_rational}K1, Z19677K1: {{ ...{negate_rational}K1.Z19677K1, Z16683K1: {{ Z1K1: 'Z16659', Z16659K1: {flip}[ {negate_rational}K1.Z19677K1.Z16683K1.Z16659K1 ] }} }} }}"
+                ),
+            ),
+        );
+        // the validators aren’t run
+        for (tester, function, argument) in [
+            ("Z10078", &negate_float, float(-2.5)),
+            ("Z10079", &negate_rational, ratio("2", "3")),
+        ] {
+            add_persistent(
+                &mut datas,
+                tester,
+                tester,
+                &format!(
+                    r#"{{ "Z1K1": "Z20", "Z20K1": "{function}", "Z20K2": {{ "Z1K1": "Z7", "Z7K1": "{function}", "{function}K1": {} }}, "Z20K3": {{ "Z1K1": "Z7", "Z7K1": "Z844", "Z844K2": {{ "Z1K1": "Z40", "Z40K1": "Z41" }} }} }}"#,
+                    serde_json::to_string(&argument).unwrap()
+                ),
+            );
+        }
+        let runner = Runner::new(Arc::new(datas));
+
+        let checks = runner.check_shims(&RunnerOption::default()).unwrap();
+        assert_eq!(
+            checks
+                .iter()
+                .map(|check| check.test_case)
+                .collect::<BTreeSet<_>>(),
+            BTreeSet::from([zid!(10078), zid!(10079)])
+        );
+        assert_eq!(
+            checks.iter().all(ShimCheck::agrees),
+            cfg!(feature = "javascript")
+        );
+        assert!(checks.iter().all(|check| check.shim_result.is_ok()));
+    }
+}
@@ -3,19 +3,19 @@ use std::{collections::BTreeMap, fmt::Debug, marker::PhantomData};
 use crate::{DataEntry, EvaluationErrorKind, Runner, Zid};
 
 pub fn parse_zid_string(entry: &DataEntry) -> Result<Zid, EvaluationErrorKind> {
-    Ok(Zid::from_zid(entry.get_str()?).map_err(|e| EvaluationErrorKind::ParseZID(e))?)
+    Zid::from_zid(entry.get_str()?).map_err(EvaluationErrorKind::ParseZID)
 }
 
 pub fn parse_string_type(entry: &DataEntry) -> Result<&str, EvaluationErrorKind> {
     check_type(entry, zid!(6))?;
-    Ok(entry.get_map_entry(&zid!(6, 1))?.get_str()?)
+    entry.get_map_entry(&zid!(6, 1))?.get_str()
 }
 
 pub fn parse_string_permissive(entry: &DataEntry) -> Result<&str, EvaluationErrorKind> {
     if let Ok(v) = entry.get_str() {
-        return Ok(v);
+        Ok(v)
     } else {
-        return parse_string_type(entry);
+        parse_string_type(entry)
     }
 }
 
@@ -34,7 +34,7 @@ pub fn parse_boolean(entry: &DataEntry) -> Result<bool, EvaluationErrorKind> {
     match text {
         "Z41" => Ok(true),
         "Z42" => Ok(false),
-        _ => todo!("error handling invalid boolean"),
+        _ => Err(EvaluationErrorKind::InvalidBoolean(text.to_string())),
     }
 }
 
@@ -43,9 +43,9 @@ pub fn check_type(entry: &DataEntry, id: Zid) -> Result<(), EvaluationErrorKind>
     let read_type = parse_zid_string(entry.get_map_entry(&zid!(1, 1))?)
         .map_err(|e| e.trace_str("parsing the type zid"))?;
     if read_type != id {
-        return Err(EvaluationErrorKind::WrongType(read_type, id));
+        Err(EvaluationErrorKind::WrongType(read_type, id))
     } else {
-        return Ok(());
+        Ok(())
     }
 }
 
@@ -61,12 +61,12 @@ impl<'l, T> MaybeOwned<'l, T> {
     }
 
     pub fn from_reference(reference: &'l T) -> Self {
-        Self::Referenced(&reference)
+        Self::Referenced(reference)
     }
 
     pub fn get(&self) -> &T {
         match self {
-            Self::Owned(o) => &o,
+            Self::Owned(o) => o,
             Self::Referenced(r) => r,
         }
     }
@@ -86,7 +86,7 @@ impl<'l, T: WfParse<'l>> PotentialReference<'l, T> {
     pub fn new(entry: &'l DataEntry) -> Self {
         Self {
             entry,
-            phantom: PhantomData::default(),
+            phantom: PhantomData,
         }
     }
 
@@ -126,6 +126,11 @@ impl<'l, T: WfParse<'l>> PotentialReference<'l, T> {
         })
     }
 
+    /// The entry as written, without following the reference
+    pub fn get_entry(&self) -> &'l DataEntry {
+        self.entry
+    }
+
     pub fn get_reference(&self) -> Result<Zid, EvaluationErrorKind> {
         Ok(match self.entry {
             DataEntry::Array(_) => return Err(EvaluationErrorKind::LowLevelNotAMap),
@@ -150,7 +155,7 @@ impl<'l, T: WfParse<'l>> WfParse<'l> for PotentialReference<'l, T> {
     fn parse(entry: &'l DataEntry) -> Result<Self, EvaluationErrorKind> {
         Ok(Self {
             entry,
-            phantom: PhantomData::default(),
+            phantom: PhantomData,
         })
     }
 }
@@ -183,7 +188,7 @@ pub struct WfPersistentObject<'l, T: WfParse<'l>> {
 
 impl<'l, T: WfParse<'l>> WfParse<'l> for WfPersistentObject<'l, T> {
     fn parse(entry: &'l DataEntry) -> Result<Self, EvaluationErrorKind> {
-        check_type(&entry, zid!(2))?;
+        check_type(entry, zid!(2))?;
         Ok(Self {
             id: Zid::from_zid(
                 parse_string_type(entry.get_map_entry(&zid!(2, 1))?)
@@ -206,7 +211,8 @@ pub const ZID_IMPLEMENTATION_FUNCTION: Zid = zid!(14, 1);
 /// A Z14
 #[derive(Clone, Debug)]
 pub struct WfImplementation<'l> {
-    pub function: PotentialReference<'l, WfFunction<'l>>,
+    /// None for implementations written inside a function literal
+    pub function: Option<PotentialReference<'l, WfFunction<'l>>>,
     pub composition: Option<PotentialReference<'l, WfUntyped<'l>>>,
     pub code: Option<PotentialReference<'l, WfUntyped<'l>>>,
     pub builtin: Option<PotentialReference<'l, WfUntyped<'l>>>, //TODO: A Function?
@@ -215,7 +221,7 @@ pub struct WfImplementation<'l> {
 impl<'l> WfParse<'l> for WfImplementation<'l> {
     fn parse(entry: &'l DataEntry) -> Result<Self, EvaluationErrorKind> {
         Ok(Self {
-            function: entry.get_map_potential_reference(&zid!(14, 1))?,
+            function: entry.get_map_potential_reference_option(&zid!(14, 1))?,
             composition: entry.get_map_potential_reference_option(&zid!(14, 2))?,
             code: entry.get_map_potential_reference_option(&zid!(14, 3))?,
             builtin: entry.get_map_potential_reference_option(&zid!(14, 4))?,
@@ -223,6 +229,24 @@ impl<'l> WfParse<'l> for WfImplementation<'l> {
     }
 }
 
+/// A Z16
+#[derive(Clone, Debug)]
+pub struct WfCode<'l> {
+    pub language: &'l DataEntry,
+    pub source: &'l str,
+}
+
+impl<'l> WfParse<'l> for WfCode<'l> {
+    fn parse(entry: &'l DataEntry) -> Result<Self, EvaluationErrorKind> {
+        check_type(entry, zid!(16))?;
+        Ok(Self {
+            language: entry.get_map_entry(&zid!(16, 1))?,
+            source: parse_string_permissive(entry.get_map_entry(&zid!(16, 2))?)
+                .map_err(|e| e.trace_str("parsing the source code"))?,
+        })
+    }
+}
+
 pub const ZID_FUNCTION_IDENTITY: Zid = zid!(8, 5);
 
 /// A Z8
@@ -232,7 +256,8 @@ pub struct WfFunction<'l> {
     pub return_type: PotentialReference<'l, WfUntyped<'l>>,
     pub testers: PotentialReference<'l, WfUntyped<'l>>,
     pub implementations: PotentialReference<'l, WfUntyped<'l>>,
-    pub identity: PotentialReference<'l, WfFunction<'l>>,
+    /// None for function literals
+    pub identity: Option<PotentialReference<'l, WfFunction<'l>>>,
 }
 
 impl<'l> WfParse<'l> for WfFunction<'l> {
@@ -242,7 +267,78 @@ impl<'l> WfParse<'l> for WfFunction<'l> {
             return_type: entry.get_map_potential_reference(&zid!(8, 2))?,
             testers: entry.get_map_potential_reference(&zid!(8, 3))?,
             implementations: entry.get_map_potential_reference(&zid!(8, 4))?,
-            identity: entry.get_map_potential_reference(&ZID_FUNCTION_IDENTITY)?,
+            identity: entry.get_map_potential_reference_option(&ZID_FUNCTION_IDENTITY)?,
+        })
+    }
+}
+
+impl<'l> WfFunction<'l> {
+    pub fn get_id(&self) -> Result<Option<Zid>, EvaluationErrorKind> {
+        self.identity
+            .as_ref()
+            .map(|identity| identity.get_reference())
+            .transpose()
+    }
+
+    /// A short description of the function for error messages
+    pub fn describe(&self) -> String {
+        match self.get_id() {
+            Ok(Some(id)) => id.to_string(),
+            _ => "a function literal".to_string(),
+        }
+    }
+
+    /// The declarations of the arguments, in order
+    pub fn argument_declarations(
+        &self,
+        runner: &'l Runner,
+    ) -> Result<Vec<WfArgumentDeclaration<'l>>, EvaluationErrorKind> {
+        let arguments = self
+            .arguments
+            .evaluate(runner)
+            .map_err(|e| e.trace_str("getting the argument list"))?;
+        // the first element is the type of the typed list
+        arguments
+            .entry
+            .get_array()?
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(pos, argument)| {
+                WfArgumentDeclaration::parse(argument)
+                    .map_err(|e| e.trace(format!("at argument position {}", pos)))
+            })
+            .collect()
+    }
+
+    /// The keys of the arguments, in declaration order
+    pub fn argument_keys(&self, runner: &'l Runner) -> Result<Vec<Zid>, EvaluationErrorKind> {
+        self.argument_declarations(runner)?
+            .iter()
+            .enumerate()
+            .map(|(pos, argument)| {
+                Zid::from_zid(argument.key_id)
+                    .map_err(EvaluationErrorKind::ParseZID)
+                    .map_err(|e| e.trace(format!("at argument position {}", pos + 1)))
+            })
+            .collect()
+    }
+}
+
+/// A Z17
+#[derive(Clone, Debug)]
+pub struct WfArgumentDeclaration<'l> {
+    pub argument_type: PotentialReference<'l, WfUntyped<'l>>,
+    pub key_id: &'l str,
+    pub label: PotentialReference<'l, WfUntyped<'l>>,
+}
+
+impl<'l> WfParse<'l> for WfArgumentDeclaration<'l> {
+    fn parse(entry: &'l DataEntry) -> Result<Self, EvaluationErrorKind> {
+        Ok(Self {
+            argument_type: entry.get_map_potential_reference(&zid!(17, 1))?,
+            key_id: parse_string_permissive(entry.get_map_entry(&zid!(17, 2))?)?,
+            label: entry.get_map_potential_reference(&zid!(17, 3))?,
         })
     }
 }
@@ -292,7 +388,7 @@ impl<'l> WfParse<'l> for WfFunctionCall<'l> {
             if k == &zid!(1, 1) || k == &zid!(7, 1) {
                 continue;
             }
-            args.insert(k.clone(), v);
+            args.insert(*k, v);
         }
         Ok(Self { function, args })
     }
@@ -304,7 +400,7 @@ pub struct WfType<'l> {
     pub identity: PotentialReference<'l, WfType<'l>>,
     pub keys: PotentialReference<'l, WfUntyped<'l>>, //TODO: typed list
     pub validator: PotentialReference<'l, WfFunction<'l>>,
-    pub equality: PotentialReference<'l, WfFunction<'l>>,
+    pub equality: Option<PotentialReference<'l, WfFunction<'l>>>,
     pub display_function: PotentialReference<'l, WfFunction<'l>>,
     pub reading_function: PotentialReference<'l, WfFunction<'l>>,
     pub type_converters_to_code: PotentialReference<'l, WfUntyped<'l>>,
@@ -317,7 +413,7 @@ impl<'l> WfParse<'l> for WfType<'l> {
             identity: entry.get_map_potential_reference(&zid!(4, 1))?,
             keys: entry.get_map_potential_reference(&zid!(4, 2))?,
             validator: entry.get_map_potential_reference(&zid!(4, 3))?,
-            equality: entry.get_map_potential_reference(&zid!(4, 4))?,
+            equality: entry.get_map_potential_reference_option(&zid!(4, 4))?,
             display_function: entry.get_map_potential_reference(&zid!(4, 5))?,
             reading_function: entry.get_map_potential_reference(&zid!(4, 6))?,
             type_converters_to_code: entry.get_map_potential_reference(&zid!(4, 7))?,
@@ -326,6 +422,30 @@ impl<'l> WfParse<'l> for WfType<'l> {
     }
 }
 
+/// A Z46 (converting a ZObject to a native value) or a Z64 (converting a native value back)
+#[derive(Debug, Clone)]
+pub struct WfTypeConverter<'l> {
+    pub identity: PotentialReference<'l, WfUntyped<'l>>,
+    pub converted_type: PotentialReference<'l, WfUntyped<'l>>,
+    pub code: PotentialReference<'l, WfCode<'l>>,
+}
+
+impl<'l> WfParse<'l> for WfTypeConverter<'l> {
+    fn parse(entry: &'l DataEntry) -> Result<Self, EvaluationErrorKind> {
+        // both have the same keys
+        let converter_type = parse_zid_string(entry.get_map_entry(&zid!(1, 1))?)?;
+        if converter_type != zid!(46) && converter_type != zid!(64) {
+            return Err(EvaluationErrorKind::WrongType(converter_type, zid!(46)));
+        }
+        let key = |key| Zid::from_u64s_panic(converter_type.get_z().map(|z| z.get()), Some(key));
+        Ok(Self {
+            identity: PotentialReference::parse(entry.get_map_entry(&key(1))?)?,
+            converted_type: PotentialReference::parse(entry.get_map_entry(&key(2))?)?,
+            code: PotentialReference::parse(entry.get_map_entry(&key(3))?)?,
+        })
+    }
+}
+
 /// meant as a high level representation of a typed list whose type is known in advance
 #[derive(Debug, Clone)]
 pub struct WfTypedList<'l, T: WfParse<'l>> {
@@ -342,7 +462,7 @@ impl<'l, T: WfParse<'l>> WfParse<'l> for WfTypedList<'l, T> {
         }
         Ok(Self {
             elements: result,
-            phantom: PhantomData::default(),
+            phantom: PhantomData,
         })
     }
 }
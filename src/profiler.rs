@@ -0,0 +1,181 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Arc, Mutex},
+    thread::{self, ThreadId},
+    time::{Duration, Instant},
+};
+
+use serde_json::json;
+
+use crate::{Zid, tracer::OpenCall};
+
+/// The time spent in a function call
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    /// The English label of the function and its ZID, or just the ZID
+    pub name: String,
+    /// The kind of implementation run: composition, builtin or code
+    pub category: &'static str,
+    pub implementation: Option<Zid>,
+    /// Small number identifying the thread the call was performed on
+    pub thread: usize,
+    /// Since the creation of the profiler
+    pub start: Duration,
+    pub duration: Duration,
+    /// The time not spent in the calls it performed
+    pub self_duration: Duration,
+    /// The names of the outer calls and of this one, separated by semicolons
+    pub stack: String,
+}
+
+#[derive(Debug, Default)]
+struct ProfilerState {
+    spans: Vec<Span>,
+    threads: Vec<ThreadId>,
+}
+
+/// Time the calls of the evaluations whose RunnerOption holds it. Clones share the same spans.
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    started_at: Instant,
+    state: Arc<Mutex<ProfilerState>>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            state: Arc::default(),
+        }
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the call, that just ended. The stack holds the names of its callers.
+    pub(crate) fn record(&self, call: &OpenCall, duration: Duration, stack: String) {
+        let mut state = self.state.lock().unwrap();
+        let current_thread = thread::current().id();
+        let thread = match state.threads.iter().position(|id| *id == current_thread) {
+            Some(thread) => thread,
+            None => {
+                state.threads.push(current_thread);
+                state.threads.len() - 1
+            }
+        };
+        state.spans.push(Span {
+            name: call.label.clone(),
+            category: call.category,
+            implementation: call.implementation,
+            thread,
+            start: call.started_at.saturating_duration_since(self.started_at),
+            duration,
+            self_duration: duration.saturating_sub(call.children_duration),
+            stack,
+        });
+    }
+
+    /// The spans recorded so far, in the order they ended
+    pub fn get_spans(&self) -> Vec<Span> {
+        self.state.lock().unwrap().spans.clone()
+    }
+
+    /// In the Chrome trace event format, as read by chrome://tracing, Perfetto or speedscope
+    pub fn to_chrome_trace(&self) -> serde_json::Value {
+        let events = self
+            .get_spans()
+            .iter()
+            .map(|span| {
+                json!({
+                    "name": span.name,
+                    "cat": span.category,
+                    "ph": "X",
+                    "ts": span.start.as_micros() as u64,
+                    "dur": span.duration.as_micros() as u64,
+                    "pid": 1,
+                    "tid": span.thread,
+                    "args": {
+                        "implementation": span.implementation.map(|implementation| implementation.to_zid()),
+                    },
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "traceEvents": events,
+            "displayTimeUnit": "ms",
+        })
+    }
+
+    /// One line per distinct stack, with the total time spent in its innermost call in microseconds, as read by flamegraph tools
+    pub fn to_folded_stacks(&self) -> String {
+        let mut stacks = BTreeMap::<String, u128>::new();
+        for span in self.get_spans() {
+            *stacks.entry(span.stack).or_default() += span.self_duration.as_micros();
+        }
+        let mut output = String::new();
+        for (stack, micros) in stacks {
+            let _ = writeln!(output, "{} {}", stack, micros);
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        DataEntry, Profiler, RunnerOption,
+        parse_tool::{WfFunctionCall, WfParse},
+        test_fixture::fixture_runner,
+    };
+
+    #[test]
+    fn test_profile_spans() {
+        let runner = fixture_runner();
+        let profiler = Profiler::new();
+        let option = RunnerOption {
+            profiler: Some(profiler.clone()),
+            ..Default::default()
+        };
+
+        // xor(false, true): xor, then the if it tail calls, returning Z10002K2 without any other call
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{
+                "Z1K1": "Z7",
+                "Z7K1": "Z10002",
+                "Z10002K1": { "Z1K1": "Z40", "Z40K1": "Z42" },
+                "Z10002K2": { "Z1K1": "Z40", "Z40K1": "Z41" }
+            }"#,
+        )
+        .unwrap();
+        runner
+            .run_function_call(&WfFunctionCall::parse(&call).unwrap(), &option)
+            .unwrap();
+
+        let spans = profiler.get_spans();
+        assert_eq!(spans.len(), 2);
+        // the innermost call ends first
+        assert_eq!(spans[0].name, "if (Z802)");
+        assert_eq!(spans[0].category, "builtin");
+        assert_eq!(spans[0].stack, "xor (Z10002);if (Z802)");
+        assert_eq!(spans[1].name, "xor (Z10002)");
+        assert_eq!(spans[1].category, "composition");
+        assert!(spans[1].duration >= spans[0].duration);
+        assert!(spans[1].start <= spans[0].start);
+
+        let trace = profiler.to_chrome_trace();
+        assert_eq!(trace["traceEvents"][1]["name"], "xor (Z10002)");
+        assert_eq!(trace["traceEvents"][1]["ph"], "X");
+        assert_eq!(trace["traceEvents"][0]["args"]["implementation"], "Z902");
+
+        let folded = profiler.to_folded_stacks();
+        let stacks = folded
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(stacks, ["xor (Z10002)", "xor (Z10002);if (Z802)"]);
+    }
+}
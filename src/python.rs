@@ -0,0 +1,530 @@
+//! Run Python with RustPython, without its standard library. Only a few modules can be imported, and builtins reaching the outside world are removed.
+//! The types of the interpreter’s own I/O and import machinery are hidden from introspection. Restricting Python from the inside is still best effort: the subprocess executor is the one to use for full isolation.
+
+use std::{
+    cell::Cell,
+    rc::Rc,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use rustpython_vm::{
+    AsObject, Interpreter, PyObjectRef, PyResult, Settings, VirtualMachine,
+    builtins::{PyBaseExceptionRef, PyDict, PyFloat, PyInt, PyList, PyStr, PyTuple, PyType},
+    compiler::Mode,
+    function::FuncArgs,
+    signal::{UserSignalSender, user_signal_channel},
+};
+use serde_json::Value;
+
+use crate::{CancellationToken, EvaluationErrorKind, code::CodeLimits};
+
+/// The modules the code may import. The others are either unsafe, non-deterministic, or not available without the standard library.
+const ALLOWED_MODULES: &[&str] = &["itertools", "_collections", "_functools", "_operator"];
+
+/// Enough for the recursion limit of the default limits, in debug builds
+const INTERPRETER_STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// The builtins that reach the outside world, directly or through the loader of the builtins module
+const REMOVED_BUILTINS: &[&str] = &[
+    "open",
+    "input",
+    "breakpoint",
+    "help",
+    "exit",
+    "quit",
+    "__loader__",
+    "__spec__",
+];
+
+/// The modules whose types reach the outside world: the interpreter’s I/O, and the import machinery, that loads any built-in module
+const HIDDEN_MODULES: &[&str] = &["_io", "_frozen_importlib", "_frozen_importlib_external"];
+
+fn exception_message(vm: &VirtualMachine, exception: &PyBaseExceptionRef) -> String {
+    let message = exception
+        .as_object()
+        .str(vm)
+        .map(|message| message.as_str().to_owned())
+        .unwrap_or_default();
+    format!("{}: {}", exception.class().name(), message)
+}
+
+/// Make the next instruction raise an exception
+fn interrupt(signals: &UserSignalSender, message: &'static str) {
+    let _ = signals.send(Box::new(move |vm| {
+        Err(vm.new_exception_msg(
+            vm.ctx.exceptions.runtime_error.to_owned(),
+            message.to_owned(),
+        ))
+    }));
+}
+
+/// Detach the types of the hidden modules from their bases, so that they can’t be found through `__subclasses__`
+fn hide_types(vm: &VirtualMachine) -> PyResult<()> {
+    let modules = vm.sys_module.get_attr("modules", vm)?;
+    for name in HIDDEN_MODULES {
+        let Some(dict) = modules
+            .get_item(*name, vm)
+            .ok()
+            .and_then(|module| module.dict())
+        else {
+            continue;
+        };
+        for (_, value) in &dict {
+            let Some(hidden) = value.downcast_ref::<PyType>() else {
+                continue;
+            };
+            for base in hidden.bases.read().iter() {
+                base.subclasses.write().retain(|subclass| {
+                    subclass
+                        .upgrade()
+                        .is_none_or(|subclass| !subclass.is(hidden))
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn restrict(vm: &VirtualMachine, limits: &CodeLimits, signals: UserSignalSender) -> PyResult<()> {
+    let builtins = vm.builtins.as_object();
+    for name in REMOVED_BUILTINS {
+        let _ = builtins.del_attr(*name, vm);
+    }
+    hide_types(vm)?;
+    let import = builtins.get_attr("__import__", vm)?;
+    let restricted_import =
+        vm.new_function("__import__", move |args: FuncArgs, vm: &VirtualMachine| {
+            let name = args
+                .args
+                .first()
+                .and_then(|name| name.downcast_ref::<PyStr>())
+                .map(|name| name.as_str().to_owned())
+                .unwrap_or_default();
+            if !ALLOWED_MODULES.contains(&name.as_str()) {
+                return Err(vm.new_import_error(
+                    format!("importing {} is not allowed", name),
+                    vm.ctx.new_str(name),
+                ));
+            }
+            // only the name is passed, and errors are replaced, so that nothing of the code runs in the import machinery, whose frames would be reachable from the traceback
+            let module = import
+                .call((vm.ctx.new_str(name.as_str()),), vm)
+                .map_err(|_| {
+                    vm.new_import_error(
+                        format!("importing {} failed", name),
+                        vm.ctx.new_str(name.as_str()),
+                    )
+                })?;
+            // the loader can load any built-in module
+            for attribute in ["__loader__", "__spec__"] {
+                let _ = module.del_attr(attribute, vm);
+            }
+            Ok(module)
+        });
+    builtins.set_attr("__import__", restricted_import, vm)?;
+
+    vm.recursion_limit.set(limits.max_recursion);
+    // the trace function is called at each function call
+    let calls = Rc::new(Cell::new(0));
+    let max_calls = limits.max_calls;
+    let count_call = vm.new_function("count_call", move |_: FuncArgs| {
+        calls.set(calls.get() + 1);
+        if calls.get() == max_calls {
+            interrupt(&signals, "call limit exceeded");
+        }
+    });
+    *vm.trace_func.borrow_mut() = count_call.into();
+    vm.use_tracing.set(true);
+    Ok(())
+}
+
+fn to_python(vm: &VirtualMachine, value: &Value) -> PyObjectRef {
+    match value {
+        Value::Null => vm.ctx.none(),
+        Value::Bool(value) => vm.ctx.new_bool(*value).into(),
+        Value::Number(number) => match number.as_i64() {
+            Some(number) => vm.ctx.new_int(number).into(),
+            None => vm.ctx.new_float(number.as_f64().unwrap_or(f64::NAN)).into(),
+        },
+        Value::String(text) => vm.ctx.new_str(text.as_str()).into(),
+        Value::Array(values) => vm
+            .ctx
+            .new_list(values.iter().map(|value| to_python(vm, value)).collect())
+            .into(),
+        Value::Object(map) => {
+            let dict = vm.ctx.new_dict();
+            for (key, value) in map {
+                // can’t fail for string keys
+                let _ = dict.set_item(key.as_str(), to_python(vm, value), vm);
+            }
+            dict.into()
+        }
+    }
+}
+
+fn from_python(vm: &VirtualMachine, object: &PyObjectRef) -> Result<Value, String> {
+    if vm.is_none(object) {
+        return Ok(Value::Null);
+    }
+    // before int, as bool is a subclass of it
+    if object.class().is(vm.ctx.types.bool_type) {
+        return Ok(Value::Bool(object.is(&vm.ctx.true_value)));
+    }
+    if let Some(int) = object.payload_if_subclass::<PyInt>(vm) {
+        return int
+            .try_to_primitive::<i64>(vm)
+            .map(Value::from)
+            .map_err(|_| "the integer is too large".to_string());
+    }
+    if let Some(float) = object.payload_if_subclass::<PyFloat>(vm) {
+        return Ok(Value::from(float.to_f64()));
+    }
+    if let Some(text) = object.payload_if_subclass::<PyStr>(vm) {
+        return Ok(Value::String(text.as_str().to_owned()));
+    }
+    if let Some(list) = object.payload_if_subclass::<PyList>(vm) {
+        return list
+            .borrow_vec()
+            .iter()
+            .map(|element| from_python(vm, element))
+            .collect::<Result<_, _>>()
+            .map(Value::Array);
+    }
+    if let Some(tuple) = object.payload_if_subclass::<PyTuple>(vm) {
+        return tuple
+            .iter()
+            .map(|element| from_python(vm, element))
+            .collect::<Result<_, _>>()
+            .map(Value::Array);
+    }
+    if let Some(dict) = object.payload_if_subclass::<PyDict>(vm) {
+        let mut map = serde_json::Map::new();
+        for (key, value) in dict {
+            let Some(key) = key.payload_if_subclass::<PyStr>(vm) else {
+                return Err("dictionaries must have string keys".to_string());
+            };
+            map.insert(key.as_str().to_owned(), from_python(vm, &value)?);
+        }
+        return Ok(Value::Object(map));
+    }
+    Err(format!(
+        "can’t convert a {} to a ZObject",
+        object.class().name()
+    ))
+}
+
+/// Run the source in a fresh interpreter, then call the function of that name it defines with the arguments.
+/// It is interrupted past the time limit, or once the token is cancelled.
+pub fn run_python(
+    source: &str,
+    function: &str,
+    arguments: &[Value],
+    limits: &CodeLimits,
+    cancellation_token: &CancellationToken,
+) -> Result<Value, EvaluationErrorKind> {
+    // the interpreter recurses on the native stack, with large frames
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(INTERPRETER_STACK_SIZE)
+            .spawn_scoped(scope, || {
+                run_interpreter(source, function, arguments, limits, cancellation_token)
+            })
+            .map_err(|e| EvaluationErrorKind::CodeExecution(e.to_string()))?
+            .join()
+            .map_err(|_| {
+                EvaluationErrorKind::CodeExecution("the interpreter panicked".to_string())
+            })?
+    })
+}
+
+fn run_interpreter(
+    source: &str,
+    function: &str,
+    arguments: &[Value],
+    limits: &CodeLimits,
+    cancellation_token: &CancellationToken,
+) -> Result<Value, EvaluationErrorKind> {
+    let mut settings = Settings::default();
+    // for a deterministic iteration order of sets and dictionaries of strings
+    settings.hash_seed = Some(0);
+    settings.install_signal_handlers = false;
+    let (signals, signal_receiver) = user_signal_channel();
+    let interpreter = Interpreter::with_init(settings, |vm| {
+        vm.set_user_signal_channel(signal_receiver);
+    });
+
+    // the interpreter can only be interrupted by signals, sent until the run ends as other interpreters running at the same time may consume them
+    let (run_ended, ended) = mpsc::channel::<()>();
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let watchdog = {
+        let signals = signals.clone();
+        let timed_out = timed_out.clone();
+        let cancelled = cancelled.clone();
+        let cancellation_token = cancellation_token.clone();
+        let deadline = Instant::now() + limits.max_duration;
+        thread::spawn(move || {
+            while ended.recv_timeout(Duration::from_millis(10)) == Err(RecvTimeoutError::Timeout) {
+                if cancellation_token.is_cancelled() {
+                    cancelled.store(true, Ordering::Relaxed);
+                    interrupt(&signals, "cancelled");
+                } else if Instant::now() >= deadline {
+                    timed_out.store(true, Ordering::Relaxed);
+                    interrupt(&signals, "time limit exceeded");
+                }
+            }
+        })
+    };
+
+    let result = interpreter.enter(|vm| {
+        let run = || -> PyResult<Result<Value, String>> {
+            restrict(vm, limits, signals)?;
+            let scope = vm.new_scope_with_builtins();
+            let code = vm
+                .compile(source, Mode::Exec, "<code>".to_owned())
+                .map_err(|error| vm.new_syntax_error(&error, Some(source)))?;
+            vm.run_code_obj(code, scope.clone())?;
+            let Some(function) = scope.globals.get_item_opt(function, vm)? else {
+                return Ok(Err(format!(
+                    "the code doesn’t define the function {}",
+                    function
+                )));
+            };
+            let arguments = arguments
+                .iter()
+                .map(|argument| to_python(vm, argument))
+                .collect::<Vec<_>>();
+            let result = function.call(arguments, vm)?;
+            Ok(from_python(vm, &result))
+        };
+        match run() {
+            Ok(result) => result,
+            Err(exception) => Err(exception_message(vm, &exception)),
+        }
+    });
+    drop(run_ended);
+    let _ = watchdog.join();
+
+    if cancelled.load(Ordering::Relaxed) {
+        return Err(EvaluationErrorKind::Cancelled);
+    }
+    if timed_out.load(Ordering::Relaxed) {
+        return Err(EvaluationErrorKind::CodeExecution(format!(
+            "time limit of {:?} exceeded",
+            limits.max_duration
+        )));
+    }
+    result.map_err(EvaluationErrorKind::CodeExecution)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use crate::{
+        CancellationToken, DataEntry, EvaluationErrorKind, Runner, RunnerOption,
+        code::CodeLimits,
+        parse_tool::{WfFunctionCall, WfParse},
+        test_fixture::{add_function, add_persistent, fixture_datas},
+    };
+
+    use super::run_python;
+
+    #[test]
+    fn test_run_python() {
+        let limits = CodeLimits::default();
+        assert_eq!(
+            run_python(
+                "def Z10010(Z10010K1, Z10010K2):\n    return [x + Z10010K2 for x in Z10010K1]",
+                "Z10010",
+                &[json!(["a", "b"]), json!("!")],
+                &limits,
+                &CancellationToken::new(),
+            )
+            .unwrap(),
+            json!(["a!", "b!"])
+        );
+        assert_eq!(
+            run_python(
+                "import itertools\ndef Z10010():\n    return list(itertools.chain([True], [None]))",
+                "Z10010",
+                &[],
+                &limits,
+                &CancellationToken::new(),
+            )
+            .unwrap(),
+            json!([true, null])
+        );
+
+        let fails = |source: &str, limits: &CodeLimits| {
+            matches!(
+                run_python(source, "Z10010", &[], limits, &CancellationToken::new()),
+                Err(EvaluationErrorKind::CodeExecution(_))
+            )
+        };
+        // no function of that name
+        assert!(fails("def Z1():\n    pass", &limits));
+        // imports and builtins reaching the outside world are refused
+        assert!(fails("import posix\ndef Z10010():\n    pass", &limits));
+        assert!(fails(
+            "def Z10010():\n    return open('/etc/passwd').read()",
+            &limits
+        ));
+        // the recursion, call and time limits stop infinite computations
+        assert!(fails("def Z10010():\n    return Z10010()", &limits));
+        assert!(fails(
+            "def f():\n    pass\ndef Z10010():\n    while True:\n        f()",
+            &CodeLimits {
+                max_calls: 1000,
+                ..limits
+            }
+        ));
+        assert!(fails(
+            "def Z10010():\n    while True:\n        pass",
+            &CodeLimits {
+                max_duration: Duration::from_millis(100),
+                ..limits
+            }
+        ));
+    }
+
+    #[test]
+    fn test_run_python_cancelled() {
+        let cancellation_token = CancellationToken::new();
+        let canceller = {
+            let cancellation_token = cancellation_token.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(100));
+                cancellation_token.cancel();
+            })
+        };
+        let result = run_python(
+            "def Z10010():\n    while True:\n        pass",
+            "Z10010",
+            &[],
+            &CodeLimits {
+                max_duration: Duration::from_secs(60),
+                ..Default::default()
+            },
+            &cancellation_token,
+        );
+        canceller.join().unwrap();
+        assert!(matches!(result, Err(EvaluationErrorKind::Cancelled)));
+    }
+
+    #[test]
+    fn test_io_types_hidden() {
+        let limits = CodeLimits::default();
+        // every class reachable from object, looking for the methods of files and loaders
+        let source = "def Z10010():
+    seen = []
+    pending = [object]
+    while pending:
+        found = type.__subclasses__(pending.pop())
+        for cls in found:
+            if cls not in seen:
+                seen.append(cls)
+                pending.append(cls)
+    names = ['fileno', 'readinto', 'load_module', 'find_spec']
+    return [n for cls in seen for n in names if getattr(cls, n, None) is not None]";
+        assert_eq!(
+            run_python(source, "Z10010", &[], &limits, &CancellationToken::new()).unwrap(),
+            json!([])
+        );
+        for expression in [
+            "__builtins__.__loader__",
+            "__builtins__.__spec__",
+            "__import__('itertools').__loader__",
+            "__import__('itertools').__spec__",
+        ] {
+            assert!(
+                run_python(
+                    &format!("def Z10010():\n    return {}", expression),
+                    "Z10010",
+                    &[],
+                    &limits,
+                    &CancellationToken::new()
+                )
+                .is_err(),
+                "{} is reachable",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn test_original_import_hidden() {
+        let limits = CodeLimits::default();
+        for import in [
+            "__import__",
+            "__builtins__.__import__",
+            "__builtins__.__dict__['__import__']",
+            "getattr(__import__, '__self__', None).__import__",
+            "getattr(__import__, '__wrapped__', None)",
+            "getattr(__import__, '__closure__', None)[0].cell_contents",
+            "[v for v in __builtins__.__dict__.values() if callable(v) and getattr(v, '__name__', '') == '__import__'][-1]",
+        ] {
+            assert!(
+                run_python(
+                    &format!("def Z10010():\n    return ({})('posix').getcwd()", import),
+                    "Z10010",
+                    &[],
+                    &limits,
+                    &CancellationToken::new()
+                )
+                .is_err(),
+                "posix is importable through {}",
+                import
+            );
+        }
+    }
+
+    #[test]
+    fn test_python_implementation() {
+        let mut datas = fixture_datas();
+        add_function(
+            &mut datas,
+            "Z10032",
+            "reverse",
+            &["Z10032K1"],
+            "Z6",
+            &["Z10033"],
+        );
+        add_persistent(
+            &mut datas,
+            "Z10033",
+            "Z10033",
+            r#"{
+                "Z1K1": "Z14",
+                "Z14K1": "Z10032",
+                "Z14K3": {
+                    "Z1K1": "Z16",
+                    "Z16K1": { "Z1K1": "Z61", "Z61K1": "python-3" },
+                    "Z16K2": "def Z10032(Z10032K1):\n    return Z10032K1[::-1]"
+                }
+            }"#,
+        );
+        let runner = Runner::new(std::sync::Arc::new(datas));
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{ "Z1K1": "Z7", "Z7K1": "Z10032", "Z10032K1": "abc" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            runner
+                .run_function_call(
+                    &WfFunctionCall::parse(&call).unwrap(),
+                    &RunnerOption::default()
+                )
+                .unwrap(),
+            serde_json::from_str::<DataEntry>(r#"{ "Z1K1": "Z6", "Z6K1": "cba" }"#).unwrap()
+        );
+    }
+}
@@ -1,50 +1,209 @@
 use std::{
-    collections::{BTreeMap, HashMap},
-    sync::Arc,
+    collections::{BTreeMap, BTreeSet, HashMap, btree_map},
+    iter::Enumerate,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::Duration,
+    vec,
 };
 
+use map_macro::btree_map;
+
 use crate::{
-    DataEntry, EvaluationError, EvaluationErrorKind, GlobalDatas, Zid,
+    BuiltinArguments, BuiltinRegistry, CachedCall, CallCache, CallKey, CancellationToken,
+    CodeExecutor, CodeLimits, DEFAULT_SHIMS, DataEntry, Debugger, EmbeddedExecutor,
+    EvaluationContext, EvaluationError, EvaluationErrorKind, GlobalDatas, ImplementationKind,
+    NUMBER_FUNCTIONS, Profiler, ProgrammingLanguage, Scope, SelectionNote, SelectionPolicy,
+    ShimRegistry, Tracer, Zid,
+    call_cache::{read_log_position, reads_since, record_read, truncate_read_log},
+    composition_tool::{
+        ZID_CLOSURE_SCOPE, closure_scope, collect_free_arguments, contains_closure,
+        replace_free_arguments,
+    },
     evaluation_error::TraceInfo,
+    evaluation_stack::{Step, Trace, force_arguments},
     parse_tool::{
-        WfFunction, WfFunctionCall, WfImplementation, WfParse, WfPersistentObject, WfTestCase,
-        WfType, WfUntyped, ZID_FUNCTION_CALL_FUNCTION, ZID_FUNCTION_IDENTITY,
-        ZID_IMPLEMENTATION_FUNCTION, ZID_PERSISTENT_OBJECT_VALUE, ZID_TEST_CASE_CALL,
-        ZID_TEST_CASE_RESULT_VALIDATION, parse_boolean,
+        PotentialReference, WfCode, WfFunction, WfFunctionCall, WfImplementation, WfParse,
+        WfPersistentObject, WfTestCase, WfType, WfUntyped, ZID_FUNCTION_CALL_FUNCTION,
+        ZID_FUNCTION_IDENTITY, ZID_IMPLEMENTATION_FUNCTION, ZID_PERSISTENT_OBJECT_VALUE,
+        ZID_TEST_CASE_CALL, ZID_TEST_CASE_RESULT_VALIDATION, parse_boolean,
+        parse_string_permissive, parse_zid_string,
     },
-    recurse_and_replace_placeholder,
+    shim::Shim,
+    tracer::OpenCall,
 };
 
-#[derive(Default, Debug)]
+/// In which order and how many times the arguments of a function call are evaluated
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvaluationStrategy {
+    /// Call-by-value. All arguments are evaluated before the function is run, except for the lazy arguments of built-ins (like the branches of Z802)
+    Strict,
+    /// Call-by-need. Arguments are evaluated the first time they are used, and the result is reused afterward
+    #[default]
+    Lazy,
+    /// Mimic the function orchestrator. Built-ins get their (non-lazy) arguments evaluated beforehand, while compositions evaluate an argument again at each use
+    Orchestrator,
+}
+
+#[derive(Default, Debug, Clone)]
 pub struct RunnerOption {
     pub force_use_impl: Option<HashMap<Zid, Zid>>,
+    pub evaluation_strategy: EvaluationStrategy,
+    /// Maximum number of function calls performed by a single evaluation
+    pub max_steps: Option<u64>,
+    /// Maximum number of nested function calls
+    pub max_depth: Option<usize>,
+    /// Maximum wall-clock duration of a single evaluation
+    pub timeout: Option<Duration>,
+    /// Checked regularly during the evaluation, to abort it early
+    pub cancellation_token: CancellationToken,
+    /// Record the function calls performed
+    pub tracer: Option<Tracer>,
+    /// Time the function calls performed
+    pub profiler: Option<Profiler>,
+    /// Stop at function calls, to inspect them
+    pub debugger: Option<Debugger>,
+    /// Resources the code implementations may use
+    pub code_limits: CodeLimits,
+    /// Runs the code implementations, instead of the embedded engines
+    pub code_executor: Option<Arc<dyn CodeExecutor>>,
+    /// Rust functions run in place of the code implementations of some functions, instead of the shims of the runner
+    pub shims: Option<ShimRegistry>,
+    /// Which implementation to run for each function
+    pub selection: SelectionPolicy,
+}
+
+impl RunnerOption {
+    pub fn code_executor(&self) -> &dyn CodeExecutor {
+        self.code_executor.as_deref().unwrap_or(&EmbeddedExecutor)
+    }
+
+    fn get_shim<'s>(&'s self, runner: &'s Runner, function_id: Option<Zid>) -> Option<&'s Shim> {
+        self.shims
+            .as_ref()
+            .unwrap_or(&runner.shims)
+            .get(&function_id?)
+    }
+}
+
+/// An implementation picked to run a function. Implementations written inside a function literal have no id.
+#[derive(Debug, Clone)]
+pub struct ChosenImplementation<'l> {
+    pub id: Option<Zid>,
+    pub value: WfImplementation<'l>,
+    /// Why each implementation of the function was chosen or skipped
+    pub selection: Vec<SelectionNote>,
 }
 
 pub struct Runner {
     datas: Arc<GlobalDatas>,
+    call_cache: Option<CallCache>,
+    builtins: BuiltinRegistry,
+    shims: ShimRegistry,
+    /// Whether each implementation passes the testers of its function, for the selection policy
+    pub(crate) tester_results: Arc<Mutex<HashMap<Zid, bool>>>,
 }
 
 impl Runner {
+    /// The runner shims the functions of DEFAULT_SHIMS and NUMBER_FUNCTIONS found in the datas
     pub fn new(datas: Arc<GlobalDatas>) -> Self {
-        Self { datas }
+        let mut runner = Self {
+            datas,
+            call_cache: None,
+            builtins: BuiltinRegistry::default(),
+            shims: ShimRegistry::new(),
+            tester_results: Default::default(),
+        };
+        runner.shims = runner.shims_of(DEFAULT_SHIMS.iter().chain(NUMBER_FUNCTIONS));
+        runner
+    }
+
+    /// Run the built-ins of the registry, instead of the default ones
+    pub fn with_builtins(mut self, builtins: BuiltinRegistry) -> Self {
+        self.builtins = builtins;
+        self
+    }
+
+    /// Run the shims of the registry when the option has none, instead of the default ones
+    pub fn with_shims(mut self, shims: ShimRegistry) -> Self {
+        self.shims = shims;
+        self
+    }
+
+    /// Reuse the results of the function calls with the same arguments. Cached calls get all their arguments evaluated beforehand, whatever the evaluation strategy.
+    pub fn with_call_cache(mut self, call_cache: CallCache) -> Self {
+        self.call_cache = Some(call_cache);
+        self
+    }
+
+    pub fn get_datas(&self) -> &GlobalDatas {
+        &self.datas
+    }
+
+    pub fn get_shims(&self) -> &ShimRegistry {
+        &self.shims
+    }
+
+    pub fn get_call_cache(&self) -> Option<&CallCache> {
+        self.call_cache.as_ref()
+    }
+
+    /// Whether the persistent objects read are to be logged, for the results persisted on disk to know what they depend on
+    fn records_reads(&self) -> bool {
+        self.call_cache
+            .as_ref()
+            .is_some_and(CallCache::has_disk_cache)
+    }
+
+    /// Look for the result of a function call in the call cache
+    fn get_from_call_cache(&self, key: &CallKey) -> Option<DataEntry> {
+        let call = self.call_cache.as_ref()?.get(key, &self.datas)?;
+        // the calls this one is part of depend on the same objects
+        if self.records_reads() {
+            for zid in call.dependencies {
+                record_read(zid);
+            }
+        }
+        Some(call.value)
+    }
+
+    pub(crate) fn store_in_call_cache(&self, key: CallKey, value: DataEntry, read_position: usize) {
+        let Some(call_cache) = &self.call_cache else {
+            return;
+        };
+        let mut dependencies = BTreeSet::new();
+        if self.records_reads() {
+            // the function and its implementation were read before the call started
+            dependencies = reads_since(read_position);
+            dependencies.insert(key.function);
+            dependencies.extend(key.implementation);
+        }
+        call_cache.insert(
+            key,
+            CachedCall {
+                value,
+                dependencies,
+            },
+            &self.datas,
+        );
     }
 
     //TODO: check that it isn’t used outside of get_persistent_object
     fn get_entry_for_reference(&self, reference: &Zid) -> Result<&DataEntry, EvaluationErrorKind> {
-        self.datas.get(reference).map_or(
-            Err(EvaluationErrorKind::MissingKey(reference.clone())),
-            |v| Ok(v),
-        )
+        if self.records_reads() {
+            record_read(*reference);
+        }
+        self.datas
+            .get(reference)
+            .ok_or(EvaluationErrorKind::MissingKey(*reference))
     }
 
     pub fn get_persistent_object<'l, T: WfParse<'l>>(
         &'l self,
         reference: &Zid,
     ) -> Result<WfPersistentObject<'l, T>, EvaluationErrorKind> {
-        Ok(
-            WfPersistentObject::parse(self.get_entry_for_reference(reference)?)
-                .map_err(|e| e.trace(format!("For object {}", reference)))?,
-        )
+        WfPersistentObject::parse(self.get_entry_for_reference(reference)?)
+            .map_err(|e| e.trace(format!("For object {}", reference)))
     }
 
     pub fn get_true(&self) -> Result<&DataEntry, EvaluationErrorKind> {
@@ -79,11 +238,95 @@ impl Runner {
         if b { self.get_true() } else { self.get_false() }
     }
 
+    /// Compare two evaluated values. Use the equality function (Z4K4) of their type if it has one, and compare their normalized form otherwise.
+    pub fn values_equal(
+        &self,
+        left: &DataEntry,
+        right: &DataEntry,
+        context: &EvaluationContext,
+    ) -> Result<bool, EvaluationErrorKind> {
+        let left = left.normalize();
+        let right = right.normalize();
+
+        match (&left, &right) {
+            (DataEntry::Array(left_array), DataEntry::Array(right_array)) => {
+                if left_array.len() != right_array.len() {
+                    return Ok(false);
+                }
+                // the first element is the type of the typed list. Compare it structurally.
+                if left_array.first() != right_array.first() {
+                    return Ok(false);
+                }
+                for (pos, (left_element, right_element)) in left_array
+                    .iter()
+                    .zip(right_array.iter())
+                    .enumerate()
+                    .skip(1)
+                {
+                    context.check_cancelled()?;
+                    context.check_limits()?;
+                    if !self
+                        .values_equal(left_element, right_element, context)
+                        .map_err(|e| e.trace(format!("comparing list elements at {}", pos)))?
+                    {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            (DataEntry::IdMap(left_map), DataEntry::IdMap(right_map)) => {
+                let left_type = left_map.get(&zid!(1, 1));
+                if left_type != right_map.get(&zid!(1, 1)) {
+                    return Ok(false);
+                }
+                // generic types (whose type is a function call) have no persisted Z4 to look at
+                if let Some(DataEntry::String(_)) = left_type {
+                    let object_type = self
+                        .get_object_type(&left)
+                        .map_err(|e| e.trace_str("getting the type to compare"))?;
+                    if let Some(equality) = object_type.equality {
+                        let equality_function = equality
+                            .evaluate(self)
+                            .map_err(|e| e.trace_str("getting the equality function"))?;
+                        let argument_keys = equality_function
+                            .argument_keys(self)
+                            .map_err(|e| e.trace_str("getting the equality function arguments"))?;
+                        let [left_key, right_key] = argument_keys[..] else {
+                            return Err(EvaluationErrorKind::Unimplemented(format!(
+                                "equality function with {} arguments",
+                                argument_keys.len()
+                            )));
+                        };
+                        let function_call = WfFunctionCall {
+                            function: equality,
+                            args: btree_map! {
+                                left_key => &left,
+                                right_key => &right,
+                            },
+                        };
+                        let result = self
+                            .run_function_call_in_scope(
+                                &function_call,
+                                &Rc::new(Scope::default()),
+                                context,
+                            )
+                            .map_err(|e| e.trace_str("running the equality function"))?;
+                        return parse_boolean(&result)
+                            .map_err(|e| e.trace_str("parsing the equality function result"));
+                    }
+                }
+                Ok(left == right)
+            }
+            _ => Ok(left == right),
+        }
+    }
+
     // return an error whether an error occur or the test result is incorrect
     pub fn run_test_case<'l>(
         &self,
         test_case_persistent: &WfPersistentObject<'l, WfTestCase<'l>>,
-        implementation_persistent: &WfPersistentObject<'l, WfImplementation<'l>>,
+        implementation: &ChosenImplementation<'l>,
+        option: &RunnerOption,
     ) -> Result<(), EvaluationError> {
         let function_identifier = EvaluationError::run_with_frame_fun_multiple(
             || {
@@ -93,15 +336,24 @@ impl Runner {
                     TraceInfo::InsideMap(ZID_IMPLEMENTATION_FUNCTION),
                 ]
             },
-            || Ok(implementation_persistent.value.function.get_reference()?),
+            || {
+                Ok(implementation
+                    .value
+                    .function
+                    .as_ref()
+                    .ok_or(EvaluationErrorKind::MissingKey(ZID_IMPLEMENTATION_FUNCTION))?
+                    .get_reference()?)
+            },
         )?;
+        let implementation_id = implementation.id.ok_or_else(|| {
+            EvaluationErrorKind::Unimplemented("testing an implementation without id".to_string())
+        })?;
 
-        let mut runner_option = RunnerOption::default();
-        runner_option.force_use_impl = Some({
-            let mut m = HashMap::new();
-            m.insert(function_identifier, implementation_persistent.id);
-            m
-        });
+        let mut runner_option = option.clone();
+        runner_option
+            .force_use_impl
+            .get_or_insert_with(HashMap::new)
+            .insert(function_identifier, implementation_id);
 
         let test_fn_result = EvaluationError::run_with_frame_fun_multiple(
             || {
@@ -112,7 +364,7 @@ impl Runner {
                 ]
             },
             || {
-                let function_call = test_case_persistent.value.call.evaluate(&self)?;
+                let function_call = test_case_persistent.value.call.evaluate(self)?;
                 Ok(self.run_function_call(&function_call, &runner_option)?)
             },
         )?;
@@ -136,18 +388,22 @@ impl Runner {
                         let validator: WfFunctionCall<'_> = test_case_persistent
                             .value
                             .result_validation
-                            .evaluate(&self)?;
+                            .evaluate(self)?;
 
                         // validator is a function call. replace first parameter with the result
 
                         let validator_function_id = EvaluationError::run_with_frame(
                             TraceInfo::InsideMap(ZID_FUNCTION_CALL_FUNCTION),
                             || {
-                                let validator_function = validator.function.evaluate(&self)?;
+                                let validator_function = validator.function.evaluate(self)?;
 
                                 EvaluationError::run_with_frame(
                                     TraceInfo::InsideMap(ZID_FUNCTION_IDENTITY),
-                                    || Ok(validator_function.identity.get_reference()?),
+                                    || {
+                                        Ok(validator_function.get_id()?.ok_or(
+                                            EvaluationErrorKind::MissingKey(ZID_FUNCTION_IDENTITY),
+                                        )?)
+                                    },
                                 )
                             },
                         )?;
@@ -165,7 +421,7 @@ impl Runner {
                             .insert(inserted_validation_ref, &test_fn_result);
 
                         let validator_result = self
-                            .run_function_call(&validator_modified, &RunnerOption::default())
+                            .run_function_call(&validator_modified, option)
                             .map_err(|e| e.trace_str("running the validator function"))?;
 
                         Ok(parse_boolean(&validator_result)
@@ -182,71 +438,203 @@ impl Runner {
         )
     }
 
+    /// Pick the implementation to run, following the selection policy of the option
     pub fn get_preferred_implementation<'l>(
         &'l self,
         function: &WfFunction<'l>,
         option: &RunnerOption,
-    ) -> Result<WfPersistentObject<'l, WfImplementation<'l>>, EvaluationErrorKind> {
+    ) -> Result<ChosenImplementation<'l>, EvaluationErrorKind> {
         let function_id = function
-            .identity
-            .get_reference()
+            .get_id()
             .map_err(|e| e.trace_str("getting id of function"))?;
 
         if let Some(force_use_impl) = &option.force_use_impl
+            && let Some(function_id) = function_id
             && let Some(implementation_id) = force_use_impl.get(&function_id)
         {
-            Ok(self
-                .get_persistent_object(&implementation_id)
-                .map_err(|e| {
-                    e.trace("loading specifically specified implementation".to_string())
-                })?)
-        } else {
-            let implementations_raw = function
-                .implementations
-                .evaluate(&self)
-                .map_err(|e| e.trace("getting implementations".to_string()))?;
-
-            let implementations_ref = implementations_raw
-                .entry
-                .get_array()
-                .map_err(|e| e.trace("getting implementations".to_string()))?;
-
-            // It appears connected functions are just function that are directly referenced by it (as opposed to inverse reference)
-            // TODO: better handling of typed array
-            // TODO: prioritize composition, then built-in, then finally code
-            for implementation_key_text in implementations_ref.iter().skip(1) {
-                let implementation_key = Zid::from_zid(
-                    implementation_key_text
-                        .get_str()
-                        .map_err(|e| e.trace("Parsing implementation list".to_string()))?,
+            return Ok(ChosenImplementation {
+                id: Some(*implementation_id),
+                value: self
+                    .get_persistent_object(implementation_id)
+                    .map_err(|e| {
+                        e.trace("loading specifically specified implementation".to_string())
+                    })?
+                    .value,
+                selection: vec![SelectionNote::new(Some(*implementation_id), true, "forced")],
+            });
+        }
+
+        let implementations_raw = function
+            .implementations
+            .evaluate(self)
+            .map_err(|e| e.trace("getting implementations".to_string()))?;
+
+        let implementations_ref = implementations_raw
+            .entry
+            .get_array()
+            .map_err(|e| e.trace("getting implementations".to_string()))?;
+
+        // It appears connected functions are just function that are directly referenced by it (as opposed to inverse reference)
+        // TODO: better handling of typed array
+        let policy = &option.selection;
+        // the implementations, with the reason they were skipped
+        let mut candidates = Vec::new();
+        for implementation_entry in implementations_ref.iter().skip(1) {
+            let (id, value) = if let DataEntry::IdMap(map) = implementation_entry
+                && map.get(&zid!(1, 1)) == Some(&DataEntry::String("Z14".to_string()))
+            {
+                // written inside a function literal
+                (
+                    None,
+                    WfImplementation::parse(implementation_entry)
+                        .map_err(|e| e.trace_str("parsing an inline implementation"))?,
                 )
-                .map_err(EvaluationErrorKind::ParseZID)
-                .map_err(|e| e.trace("processing an implementation reference".to_string()))?;
+            } else {
+                let implementation_key =
+                    PotentialReference::<WfImplementation>::new(implementation_entry)
+                        .get_reference()
+                        .map_err(|e| {
+                            e.trace("processing an implementation reference".to_string())
+                        })?;
+                (
+                    Some(implementation_key),
+                    self.get_persistent_object::<WfImplementation>(&implementation_key)
+                        .map_err(|e| {
+                            e.trace("trying to get a referrenced implementation".to_string())
+                        })?
+                        .value,
+                )
+            };
 
-                let implementation_persistant = self
-                    .get_persistent_object::<WfImplementation>(&implementation_key)
-                    .map_err(|e| {
-                        e.trace("trying to get a referrenced implementation".to_string())
-                    })?;
+            let kind = ImplementationKind::of(&value);
+            let skipped = match (id, kind) {
+                (Some(id), _) if policy.deny.contains(&id) => Some("denied".to_string()),
+                (id, _)
+                    if policy
+                        .allow
+                        .as_ref()
+                        .is_some_and(|allow| !id.is_some_and(|id| allow.contains(&id))) =>
+                {
+                    Some("not allowed".to_string())
+                }
+                (_, None) => Some("no composition, builtin or code".to_string()),
+                (_, Some(kind)) if !policy.kind_order.contains(&kind) => {
+                    Some(format!("{} implementations are not selected", kind))
+                }
+                (_, Some(ImplementationKind::Code))
+                    if !self.is_runnable_code(function_id, &value, option) =>
+                {
+                    Some("the code can’t be run".to_string())
+                }
+                _ => None,
+            };
+            candidates.push((id, value, kind, skipped));
+        }
 
-                // check if it have a composition implementation
-                if let Some(_) = implementation_persistant.value.composition {
-                    // composition implementation
-                    return Ok(implementation_persistant);
+        if policy.prefer_passing_testers
+            && let Some(function_id) = function_id
+        {
+            let passing = candidates
+                .iter()
+                .map(|(id, _, _, skipped)| {
+                    skipped.is_none()
+                        && id.is_some_and(|id| self.passes_testers(&function_id, &id, option))
+                })
+                .collect::<Vec<_>>();
+            if passing.contains(&true) {
+                for ((id, _, _, skipped), passes) in candidates.iter_mut().zip(passing) {
+                    if skipped.is_none() && !passes {
+                        *skipped = Some(match id {
+                            Some(_) => "fails some of its testers".to_string(),
+                            None => "can’t be tested, being inline".to_string(),
+                        });
+                    }
                 }
+            }
+        }
 
-                if let Some(_) = implementation_persistant.value.builtin {
-                    // builtin implementation
-                    return Ok(implementation_persistant);
+        let selectable = candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, _, _, skipped))| skipped.is_none())
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let ranks = candidates
+            .iter()
+            .map(|(_, _, kind, _)| policy.kind_order.iter().position(|k| Some(*k) == *kind))
+            .collect::<Vec<_>>();
+        let rank = |index: usize| ranks[index];
+        let chosen = match &policy.random {
+            _ if selectable.is_empty() => None,
+            Some(random) => Some(selectable[random.next_index(selectable.len())]),
+            None => selectable.iter().copied().min_by_key(|index| rank(*index)),
+        };
+
+        let mut selection = Vec::new();
+        let mut chosen_implementation = None;
+        for (index, (id, value, kind, skipped)) in candidates.into_iter().enumerate() {
+            let (is_chosen, reason) = match (skipped, chosen) {
+                (Some(reason), _) => (false, reason),
+                (None, Some(chosen)) if chosen == index => {
+                    let reason = match (&policy.random, kind) {
+                        (Some(_), _) => "picked at random".to_string(),
+                        (None, Some(kind)) => format!("first {} implementation", kind),
+                        (None, None) => unreachable!("skipped above"),
+                    };
+                    (true, reason)
+                }
+                (None, Some(chosen)) => {
+                    let reason = if policy.random.is_some() {
+                        "not picked at random".to_string()
+                    } else if rank(index) == rank(chosen) {
+                        "listed after the chosen one".to_string()
+                    } else {
+                        "its kind ranks lower".to_string()
+                    };
+                    (false, reason)
                 }
+                (None, None) => unreachable!("one is chosen when any is selectable"),
+            };
+            selection.push(SelectionNote::new(id, is_chosen, reason));
+            if is_chosen {
+                chosen_implementation = Some((id, value));
             }
+        }
+
+        match chosen_implementation {
+            Some((id, value)) => Ok(ChosenImplementation {
+                id,
+                value,
+                selection,
+            }),
+            None => Err(EvaluationErrorKind::Unimplemented(format!(
+                "no selectable implementation (for {}){}",
+                function.describe(),
+                selection
+                    .iter()
+                    .map(|note| format!(", {}", note))
+                    .collect::<String>()
+            ))),
+        }
+    }
 
-            // TODO: code
-            return Err(EvaluationErrorKind::Unimplemented(format!(
-                "code and builtins (and fail if none found) (for {})",
-                function_id
-            )));
+    /// Whether the implementation is code in a language the executor can run, or has a shim
+    fn is_runnable_code(
+        &self,
+        function_id: Option<Zid>,
+        implementation: &WfImplementation,
+        option: &RunnerOption,
+    ) -> bool {
+        if implementation.code.is_some() && option.get_shim(self, function_id).is_some() {
+            return true;
         }
+        implementation
+            .code
+            .as_ref()
+            .and_then(|code| code.evaluate(self).ok())
+            .and_then(|code| code.entry.get_map_entry(&zid!(16, 1)).ok().cloned())
+            .and_then(|language| ProgrammingLanguage::parse(&language).ok())
+            .is_some_and(|language| option.code_executor().supports(language))
     }
 
     pub fn run_function_call(
@@ -254,216 +642,1239 @@ impl Runner {
         function_call: &WfFunctionCall<'_>,
         option: &RunnerOption,
     ) -> Result<DataEntry, EvaluationErrorKind> {
-        let function = function_call
-            .function
-            .evaluate(&self)
+        let read_position = read_log_position();
+        let context = EvaluationContext::new(option);
+        let result = self
+            .run_function_call_in_scope(function_call, &Rc::new(Scope::default()), &context)
+            .and_then(|value| Self::write_out_closures(&value, &context));
+        // the cached results of this evaluation already got the reads they depend on
+        truncate_read_log(read_position);
+        result
+    }
+
+    /// Call a function value (a reference or a function literal) with already evaluated arguments, given in declaration order
+    pub fn call_function_value(
+        &self,
+        function: &DataEntry,
+        arguments: &[DataEntry],
+        context: &EvaluationContext,
+    ) -> Result<DataEntry, EvaluationErrorKind> {
+        self.evaluate(
+            Self::function_value_call(function.clone(), arguments.to_vec()),
+            context,
+        )
+    }
+
+    fn function_value_call(function: DataEntry, arguments: Vec<DataEntry>) -> Step {
+        Step::Call {
+            function,
+            arguments: arguments
+                .into_iter()
+                .enumerate()
+                .map(|(pos, argument)| (Zid::from_u64s_panic(None, Some(pos as u64 + 1)), argument))
+                .collect(),
+            scope: Rc::new(Scope::default()),
+        }
+    }
+
+    /// Run a function call whose arguments are to be evaluated in the given scope
+    pub fn run_function_call_in_scope(
+        &self,
+        function_call: &WfFunctionCall<'_>,
+        scope: &Rc<Scope>,
+        context: &EvaluationContext,
+    ) -> Result<DataEntry, EvaluationErrorKind> {
+        self.evaluate(
+            Step::Call {
+                function: function_call.function.get_entry().clone(),
+                arguments: function_call
+                    .args
+                    .iter()
+                    .map(|(key, value)| (*key, (*value).clone()))
+                    .collect(),
+                scope: scope.clone(),
+            },
+            context,
+        )
+    }
+
+    /// Evaluate the entry, resolving argument references in the given scope
+    pub fn recurse_call_function(
+        &self,
+        entry: &DataEntry,
+        scope: &Rc<Scope>,
+        context: &EvaluationContext,
+    ) -> Result<DataEntry, EvaluationErrorKind> {
+        self.evaluate(Step::Evaluate(entry.clone(), scope.clone()), context)
+    }
+
+    pub(crate) fn call_step(
+        function: DataEntry,
+        arguments: BTreeMap<Zid, DataEntry>,
+        scope: Rc<Scope>,
+    ) -> Step {
+        // the function might be a reference, a function literal, an argument or the result of another function call
+        Step::then(
+            Step::traced(
+                Trace::Message("evaluating function linked to function call"),
+                Step::Evaluate(function, scope.clone()),
+            ),
+            move |runner, context, function_value| {
+                runner.enter_function(&function_value, arguments, &scope, context)
+            },
+        )
+    }
+
+    fn enter_function(
+        &self,
+        function_value: &DataEntry,
+        arguments: BTreeMap<Zid, DataEntry>,
+        scope: &Rc<Scope>,
+        context: &EvaluationContext,
+    ) -> Result<Step, EvaluationErrorKind> {
+        let function = PotentialReference::<WfFunction>::new(function_value)
+            .evaluate(self)
             .map_err(|e| e.trace_str("getting function linked to function call"))?;
 
-        let implementation_persistant = self.get_preferred_implementation(&function, option)?;
+        let implementation_persistant =
+            self.get_preferred_implementation(&function, context.option)?;
 
-        return Ok(self
-            .run_implementation(&implementation_persistant.value, &function_call, option)
-            .map_err(|e| {
-                e.trace(format!(
-                    "calling implementation {:?}",
-                    implementation_persistant.id
-                ))
-            })?);
+        let argument_keys = function
+            .argument_keys(self)
+            .map_err(|e| e.trace(format!("getting arguments of {}", function.describe())))?;
+
+        // the orchestrator substitutes the unevaluated arguments in compositions
+        let memoize = context.option.evaluation_strategy != EvaluationStrategy::Orchestrator
+            || implementation_persistant.value.composition.is_none();
+        // the references of a function literal to the arguments of the enclosing calls are resolved in the scope it was evaluated in
+        let captured = match function_value {
+            DataEntry::IdMap(map) => closure_scope(map).and_then(|index| context.captured(index)),
+            _ => None,
+        };
+        let arguments =
+            Rc::new(Scope::bind(arguments, &argument_keys, scope, memoize).with_parent(captured));
+
+        let trace = match implementation_persistant.id {
+            Some(id) => format!("calling implementation {:?}", id),
+            None => format!("calling an implementation of {}", function.describe()),
+        };
+        let body = self
+            .run_implementation(
+                &implementation_persistant.value,
+                &function,
+                arguments.clone(),
+                context,
+            )
+            .map_err(|e| e.trace(trace.clone()))?;
+
+        let traced_call = context.records_calls().then(|| {
+            let label = match function.get_id().ok().flatten().and_then(|id| {
+                self.datas
+                    .english_label(&id)
+                    .map(|label| format!("{} ({})", label, id))
+            }) {
+                Some(label) => label,
+                None => function.describe(),
+            };
+            let category = match ImplementationKind::of(&implementation_persistant.value) {
+                Some(ImplementationKind::Composition) => "composition",
+                Some(ImplementationKind::Builtin) => "builtin",
+                _ if context
+                    .option
+                    .get_shim(self, function.get_id().ok().flatten())
+                    .is_some() =>
+                {
+                    "shim"
+                }
+                _ => "code",
+            };
+            let composition = match (
+                &context.option.debugger,
+                &implementation_persistant.value.composition,
+            ) {
+                (Some(_), Some(composition)) => composition
+                    .evaluate(self)
+                    .ok()
+                    .map(|composition| composition.entry.clone()),
+                _ => None,
+            };
+            OpenCall::new(
+                function.describe(),
+                function.get_id().ok().flatten(),
+                label,
+                category,
+                implementation_persistant.id,
+                arguments.clone(),
+            )
+            .with_composition(composition)
+            .with_selection(implementation_persistant.selection.clone())
+            .into()
+        });
+        let call = Step::Enter {
+            description: function.describe(),
+            trace,
+            body: Box::new(body),
+            traced_call,
+        };
+
+        let Some(call_cache) = &self.call_cache else {
+            return Ok(call);
+        };
+        let Some(function_id) = function.get_id()? else {
+            return Ok(call);
+        };
+        let runs_code = implementation_persistant.value.code.is_some();
+        let shimmed = runs_code && context.option.get_shim(self, Some(function_id)).is_some();
+        // shims are native code, that doesn’t depend on anything but its arguments
+        if call_cache.is_impure(&function_id, runs_code && !shimmed) {
+            context.mark_impure_call();
+            return Ok(call);
+        }
+        // they share the key of the code implementation, that they are checked against
+        if shimmed {
+            return Ok(call);
+        }
+        // the key can’t be known without evaluating the arguments, that lazy arguments might not need
+        if let Some(builtin) = implementation_persistant.value.builtin.as_ref()
+            && self.builtin_has_lazy_arguments(&Self::builtin_id(builtin.evaluate(self)?.entry)?)
+        {
+            return Ok(call);
+        }
+
+        let implementation_id = implementation_persistant.id;
+        let thunks = arguments
+            .iter()
+            .map(|(key, thunk)| (*key, thunk.clone()))
+            .collect();
+        force_arguments(thunks, self, context, move |runner, _, values| {
+            // a function literal depends on the scope it is closed over, that only exists in this evaluation
+            if values.values().any(contains_closure) {
+                return Ok(call);
+            }
+            let key = CallKey::new(function_id, implementation_id, &values);
+            Ok(match runner.get_from_call_cache(&key) {
+                Some(value) => Step::Value(value),
+                None => Step::Cached(key, Box::new(call)),
+            })
+        })
     }
 
-    pub fn run_implementation(
+    pub(crate) fn run_implementation(
         &self,
         implementation: &WfImplementation,
-        function_call: &WfFunctionCall,
-        option: &RunnerOption,
-    ) -> Result<DataEntry, EvaluationErrorKind> {
+        function: &WfFunction,
+        arguments: Rc<Scope>,
+        context: &EvaluationContext,
+    ) -> Result<Step, EvaluationErrorKind> {
         if let Some(composition) = implementation.composition.as_ref() {
             return self.run_composition(
                 composition
-                    .evaluate(&self)
+                    .evaluate(self)
                     .map_err(|e| e.trace_str("getting the composition implementation"))?
-                    .entry,
-                function_call,
-                option,
+                    .entry
+                    .clone(),
+                arguments,
+                context,
             );
         };
 
         if let Some(builtin) = implementation.builtin.as_ref() {
             return self.run_builtin(
                 builtin
-                    .evaluate(&self)
+                    .evaluate(self)
                     .map_err(|e| e.trace_str("getting the builtin implementation"))?
                     .entry,
-                function_call,
-                option,
+                function,
+                arguments,
+                context,
             );
         }
 
-        todo!("code implementation and error if no impl are present")
+        if let Some(code) = implementation.code.as_ref() {
+            let code = code
+                .evaluate(self)
+                .map_err(|e| e.trace_str("getting the code implementation"))?
+                .entry
+                .clone();
+            let function_id = function.get_id()?.ok_or_else(|| {
+                EvaluationErrorKind::Unimplemented(
+                    "code implementations of function literals".to_string(),
+                )
+            })?;
+            let argument_keys = function.argument_keys(self)?;
+            let argument_types = function
+                .argument_declarations(self)?
+                .iter()
+                .map(|argument| argument.argument_type.get_entry().clone())
+                .collect::<Vec<_>>();
+            let return_type = function.return_type.get_entry().clone();
+            // code only takes evaluated arguments
+            let thunks = arguments
+                .iter()
+                .map(|(key, thunk)| (*key, thunk.clone()))
+                .collect();
+            return force_arguments(thunks, self, context, move |runner, context, values| {
+                let values = argument_keys
+                    .iter()
+                    .map(|key| {
+                        values
+                            .get(key)
+                            .cloned()
+                            .ok_or(EvaluationErrorKind::UnboundArgument(*key))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if let Some(shim) = context.option.get_shim(runner, Some(function_id)) {
+                    return shim(&values)
+                        .map(Step::Value)
+                        .map_err(|e| e.trace(format!("running the shim of {}", function_id)));
+                }
+                let code = WfCode::parse(&code)?;
+                runner
+                    .run_code(
+                        &code,
+                        function_id,
+                        &values,
+                        &argument_types,
+                        &return_type,
+                        context,
+                    )
+                    .map(Step::Value)
+            });
+        }
+
+        Err(EvaluationErrorKind::Unimplemented(
+            "implementations without composition, code or builtin".to_string(),
+        ))
     }
 
-    pub fn run_composition(
+    pub(crate) fn run_composition(
         &self,
-        composition: &DataEntry,
-        function_call: &WfFunctionCall<'_>,
-        option: &RunnerOption,
-    ) -> Result<DataEntry, EvaluationErrorKind> {
-        // algorithm:
-        // 1. replace all Z18 by their actual value
-        // 2. work top-down, recursively. If entry is Z7, perform the function call. If not, recurse deeper
-
-        let composition_with_substitution_done =
-            recurse_and_replace_placeholder(composition, &function_call.args)?;
+        composition: DataEntry,
+        arguments: Rc<Scope>,
+        context: &EvaluationContext,
+    ) -> Result<Step, EvaluationErrorKind> {
+        // the body is evaluated in place of the call, so that a composition that directly returns another call is a tail call
+        let body = Step::Evaluate(composition, arguments.clone());
 
-        let function_id = function_call
-            .function
-            .evaluate(&self)?
-            .identity
-            .get_reference()?;
+        if context.option.evaluation_strategy == EvaluationStrategy::Strict {
+            let thunks = arguments
+                .iter()
+                .map(|(key, thunk)| (*key, thunk.clone()))
+                .collect();
+            return force_arguments(thunks, self, context, move |_, _, _| Ok(body));
+        }
 
-        Ok(self
-            .recurse_call_function(&composition_with_substitution_done, option)
-            .map_err(|e| e.trace(format!("Calling the composition from {:?}", function_id)))?)
+        Ok(body)
     }
 
-    pub fn recurse_call_function(
+    /// Evaluate one level of the entry. If it is Z7, perform the function call. If it is Z18, evaluate the argument (only once). If not, evaluate its content.
+    pub(crate) fn evaluate_entry(
         &self,
-        entry: &DataEntry,
-        option: &RunnerOption,
-    ) -> Result<DataEntry, EvaluationErrorKind> {
+        entry: DataEntry,
+        scope: Rc<Scope>,
+        context: &EvaluationContext,
+    ) -> Result<Step, EvaluationErrorKind> {
         const Z1K1: Zid = Zid::from_u64s_panic(Some(1), Some(1));
+        const Z7K1: Zid = Zid::from_u64s_panic(Some(7), Some(1));
+        const Z18K1: Zid = Zid::from_u64s_panic(Some(18), Some(1));
 
         match entry {
-            DataEntry::IdMap(map) => {
-                if let Some(object_type) = map.get(&Z1K1) {
-                    if object_type
-                        .get_str()
-                        .map_err(|e| e.trace("Inside Z1K1".to_string()))?
-                        == "Z7"
-                    {
-                        return self.run_function_call(
-                            &WfFunctionCall::parse(entry)
-                                .map_err(|e| e.trace_str("parsing a function call"))?,
-                            option,
-                        );
+            DataEntry::IdMap(mut map) => {
+                let object_type = match map.get(&Z1K1) {
+                    Some(object_type) => Some(
+                        object_type
+                            .get_str()
+                            .map_err(|e| e.trace("Inside Z1K1".to_string()))?
+                            .to_string(),
+                    ),
+                    None => None,
+                };
+                match object_type.as_deref() {
+                    Some("Z7") => {
+                        map.remove(&Z1K1);
+                        let function = map
+                            .remove(&Z7K1)
+                            .ok_or(EvaluationErrorKind::MissingKey(Z7K1))
+                            .map_err(|e| e.trace_str("parsing a function call"))?;
+                        Ok(Self::call_step(function, map, scope))
+                    }
+                    Some("Z18") => {
+                        let key = map
+                            .remove(&Z18K1)
+                            .ok_or(EvaluationErrorKind::MissingKey(Z18K1))?;
+                        // the key might itself be computed
+                        if let DataEntry::String(_) = key {
+                            return Self::resolve_argument(&key, &scope);
+                        }
+                        Ok(Step::then(
+                            Step::traced(
+                                Trace::Message("inside a Z18K1"),
+                                Step::Evaluate(key, scope.clone()),
+                            ),
+                            move |_, _, key| Self::resolve_argument(&key, &scope),
+                        ))
                     }
+                    // a function literal, that becomes a closure over the current scope. Its body is only evaluated when it is called.
+                    Some("Z8") => {
+                        if closure_scope(&map).is_none() {
+                            map.insert(
+                                ZID_CLOSURE_SCOPE,
+                                DataEntry::String(context.capture(&scope).to_string()),
+                            );
+                        }
+                        Ok(Step::Value(DataEntry::IdMap(map)))
+                    }
+                    _ => Ok(Self::evaluate_map(map.into_iter(), BTreeMap::new(), scope)),
+                }
+            }
+            DataEntry::Array(array) => Ok(Self::evaluate_array(
+                array.into_iter().enumerate(),
+                Vec::new(),
+                scope,
+            )),
+            DataEntry::String(s) => Ok(Step::Value(DataEntry::String(s))),
+        }
+    }
+
+    fn resolve_argument(key: &DataEntry, scope: &Rc<Scope>) -> Result<Step, EvaluationErrorKind> {
+        let key =
+            Zid::from_zid(parse_string_permissive(key).map_err(|e| e.trace_str("inside a Z18K1"))?)
+                .map_err(EvaluationErrorKind::ParseZID)
+                .map_err(|e| e.trace_str("inside a Z18K1"))?;
+        let thunk = scope
+            .get(&key)
+            .map_err(|e| e.trace(format!("evaluating argument {}", key)))?;
+        Ok(Step::traced(
+            Trace::Argument(key),
+            Step::Force(thunk.clone()),
+        ))
+    }
+
+    fn evaluate_map(
+        mut remaining: btree_map::IntoIter<Zid, DataEntry>,
+        mut done: BTreeMap<Zid, DataEntry>,
+        scope: Rc<Scope>,
+    ) -> Step {
+        loop {
+            match remaining.next() {
+                None => return Step::Value(DataEntry::IdMap(done)),
+                // nothing to evaluate
+                Some((key, DataEntry::String(value))) => {
+                    done.insert(key, DataEntry::String(value));
                 }
+                Some((key, value)) => {
+                    return Step::then(
+                        Step::traced(Trace::InsideKey(key), Step::Evaluate(value, scope.clone())),
+                        move |_, _, value| {
+                            done.insert(key, value);
+                            Ok(Self::evaluate_map(remaining, done, scope))
+                        },
+                    );
+                }
+            }
+        }
+    }
 
-                let mut new_map = BTreeMap::new();
-                for (key, value) in map.iter() {
-                    new_map.insert(
-                        key.to_owned(),
-                        self.recurse_call_function(value, option)
-                            .map_err(|e| e.trace(format!("Inside {}", key)))?,
+    fn evaluate_array(
+        mut remaining: Enumerate<vec::IntoIter<DataEntry>>,
+        mut done: Vec<DataEntry>,
+        scope: Rc<Scope>,
+    ) -> Step {
+        loop {
+            match remaining.next() {
+                None => return Step::Value(DataEntry::Array(done)),
+                Some((_, DataEntry::String(value))) => done.push(DataEntry::String(value)),
+                Some((pos, value)) => {
+                    return Step::then(
+                        Step::traced(
+                            Trace::ArrayPosition(pos),
+                            Step::Evaluate(value, scope.clone()),
+                        ),
+                        move |_, _, value| {
+                            done.push(value);
+                            Ok(Self::evaluate_array(remaining, done, scope))
+                        },
                     );
                 }
-                return Ok(DataEntry::IdMap(new_map));
             }
-            DataEntry::Array(array) => {
-                let mut new_array = Vec::new();
+        }
+    }
 
-                for (pos, entry) in array.iter().enumerate() {
-                    new_array.push(
-                        self.recurse_call_function(entry, option)
-                            .map_err(|e| e.trace(format!("At array position {}", pos)))?,
-                    )
+    /// Write the function literals of a value leaving the evaluation without the scope they are closed over: the arguments they capture are replaced with their value, or with their expression if they were never evaluated
+    pub(crate) fn write_out_closures(
+        entry: &DataEntry,
+        context: &EvaluationContext,
+    ) -> Result<DataEntry, EvaluationErrorKind> {
+        match entry {
+            DataEntry::IdMap(map) => {
+                if let Some(index) = closure_scope(map) {
+                    let scope = context.captured(index).ok_or_else(|| {
+                        EvaluationErrorKind::Unimplemented(format!(
+                            "a function literal closed over the unknown scope {}",
+                            index
+                        ))
+                    })?;
+                    let mut literal = map.clone();
+                    literal.remove(&ZID_CLOSURE_SCOPE);
+                    return Self::write_out_expression(&DataEntry::IdMap(literal), &scope, context)
+                        .map_err(|e| e.trace_str("writing out a function literal"));
                 }
+                map.iter()
+                    .map(|(key, value)| Ok((*key, Self::write_out_closures(value, context)?)))
+                    .collect::<Result<_, _>>()
+                    .map(DataEntry::IdMap)
+            }
+            DataEntry::Array(array) => array
+                .iter()
+                .map(|value| Self::write_out_closures(value, context))
+                .collect::<Result<_, _>>()
+                .map(DataEntry::Array),
+            DataEntry::String(_) => Ok(entry.clone()),
+        }
+    }
+
+    /// Replace the references of the expression to the arguments of the scope, so that it no longer depends on it
+    fn write_out_expression(
+        expression: &DataEntry,
+        scope: &Rc<Scope>,
+        context: &EvaluationContext,
+    ) -> Result<DataEntry, EvaluationErrorKind> {
+        let mut free_arguments = BTreeSet::new();
+        collect_free_arguments(expression, &BTreeSet::new(), &mut free_arguments)?;
 
-                Ok(DataEntry::Array(new_array))
+        let mut values = BTreeMap::new();
+        for key in free_arguments {
+            // unbound references are kept as-is, and will cause an error if they are ever evaluated
+            let Ok(thunk) = scope.get(&key) else {
+                continue;
+            };
+            let value = match thunk.get_value() {
+                Some(value) => Self::write_out_closures(value, context),
+                None => {
+                    let (expression, caller) = thunk
+                        .get_expression()
+                        .expect("a thunk without expression should have a value");
+                    Self::write_out_expression(expression, caller, context)
+                }
             }
-            DataEntry::String(s) => Ok(DataEntry::String(s.to_string())),
+            .map_err(|e| e.trace(format!("writing out argument {}", key)))?;
+            values.insert(key, value);
         }
+
+        Self::write_out_closures(
+            &replace_free_arguments(expression, &BTreeSet::new(), &values)?,
+            context,
+        )
+    }
+
+    fn builtin_id(builtin: &DataEntry) -> Result<Zid, EvaluationErrorKind> {
+        parse_zid_string(builtin.get_map_entry(&zid!(6, 1))?)
+            .map_err(|e| e.trace("Getting the implementation id to run".to_string()))
+    }
+
+    /// Whether the built-in has arguments that must only be evaluated when it needs them, whatever the evaluation strategy
+    fn builtin_has_lazy_arguments(&self, implementation_id: &Zid) -> bool {
+        self.builtins
+            .get(implementation_id)
+            .is_some_and(|builtin| !builtin.lazy_arguments().is_empty())
     }
 
-    pub fn run_builtin(
+    pub(crate) fn run_builtin(
         &self,
         builtin: &DataEntry,
-        function_call: &WfFunctionCall<'_>,
-        option: &RunnerOption,
-    ) -> Result<DataEntry, EvaluationErrorKind> {
-        let implementation_id = builtin
-            .get_map_entry(&zid!(6, 1))
-            .map_err(|e| e.trace("Getting the implementation id to run".to_string()))?
-            .get_str()
-            .map_err(|e| e.trace("Getting the implementation id to run".to_string()))?;
-        // let’s force the use of composition implementation as much as posible to reduce the built-ins that needs to be implemented
-        let impl_to_use = match implementation_id {
-            // string equality
-            "Z966" => Some(Zid::from_u64s_panic(Some(17569), None)),
-            // list equality. Some weird behavior around typed list. Might be a problem in certain cases.
-            "Z989" => Some(Zid::from_u64s_panic(Some(15872), None)),
-            _ => None,
-        };
+        function: &WfFunction,
+        arguments: Rc<Scope>,
+        context: &EvaluationContext,
+    ) -> Result<Step, EvaluationErrorKind> {
+        let implementation_id = Self::builtin_id(builtin)?;
 
-        if let Some(impl_to_use) = impl_to_use {
+        if let Some(impl_to_use) = self.builtins.get_alias(&implementation_id) {
             let implementation_persistant = self
                 .get_persistent_object(&impl_to_use)
                 .map_err(|e| e.trace("Getting the implementation to run".to_string()))?;
 
             return self.run_implementation(
                 &implementation_persistant.value,
-                function_call,
-                option,
+                function,
+                arguments,
+                context,
             );
         }
 
-        match implementation_id {
-            // If
-            "Z902" => {
-                let condition = self
-                    .recurse_call_function(function_call.get_arg(&zid!(802, 1))?, option)
-                    .map_err(|e| e.trace_str("parsing condition"))?;
-                let condition =
-                    parse_boolean(&condition).map_err(|e| e.trace_str("parsing condition"))?;
-
-                let entry_to_use = if condition {
-                    zid!(802, 2)
-                } else {
-                    zid!(802, 3)
-                };
+        let builtin = self.builtins.get(&implementation_id).ok_or_else(|| {
+            EvaluationErrorKind::Unimplemented(format!("built-in {}", implementation_id))
+        })?;
+        // built-ins use all their arguments, except the lazy ones. They are evaluated beforehand, on the evaluation stack.
+        let lazy_arguments = builtin.lazy_arguments();
+        let thunks = builtin
+            .argument_keys()
+            .iter()
+            .filter(|key| !lazy_arguments.contains(key))
+            .map(|key| Ok((*key, arguments.get(key)?.clone())))
+            .collect::<Result<_, EvaluationErrorKind>>()?;
 
-                let result = self
-                    .recurse_call_function(function_call.get_arg(&entry_to_use)?, option)
-                    .map_err(|e| e.trace(format!("evaluating result for {:?}", condition)))?;
+        force_arguments(thunks, self, context, move |runner, context, values| {
+            let builtin = runner
+                .builtins
+                .get(&implementation_id)
+                .expect("the built-in was found before");
+            builtin.apply(runner, BuiltinArguments::new(values, arguments), context)
+        })
+    }
 
-                return Ok(result);
-            }
-            // Reify
-            "Z905" => {
-                let input = function_call.get_arg(&zid!(805, 1))?;
-                return Ok(input
-                    .reify(&self)
-                    .map_err(|e| e.trace_str("running built-in reify"))?);
-            }
-            // Is empty (typed) list
-            "Z913" => {
-                let list =
-                    self.recurse_call_function(function_call.get_arg(&zid!(813, 1))?, option)?;
+    pub(crate) fn take_array(entry: DataEntry) -> Result<Vec<DataEntry>, EvaluationErrorKind> {
+        match entry {
+            DataEntry::Array(array) => Ok(array),
+            _ => Err(EvaluationErrorKind::LowLevelNotAnArray),
+        }
+    }
 
-                // <= 1 cause typed list store the type as the first index
-                return Ok(self.get_bool(list.get_array()?.len() <= 1)?.clone());
-            }
-            // boolean equality
-            "Z944" => {
-                let boolean1 = self
-                    .recurse_call_function(function_call.get_arg(&zid!(844, 1))?, option)
-                    .map_err(|e| e.trace_str("parsing first boolean"))?;
-                let boolean1 =
-                    parse_boolean(&boolean1).map_err(|e| e.trace_str("parsing first boolean"))?;
-                let boolean2 = self
-                    .recurse_call_function(function_call.get_arg(&zid!(844, 2))?, option)
-                    .map_err(|e| e.trace_str("parsing second boolean"))?;
-                let boolean2 =
-                    parse_boolean(&boolean2).map_err(|e| e.trace_str("parsing first boolean"))?;
-
-                return Ok(self.get_bool(boolean1 == boolean2)?.clone());
-            }
-            _ => {
-                return Err(EvaluationErrorKind::Unimplemented(format!(
-                    "built-in {}",
-                    implementation_id
-                )));
+    /// Call the function on each element of the list in turn (positions start at 1, after the type of the typed list), and fold the results into the state
+    pub(crate) fn fold_list<S: 'static>(
+        function: DataEntry,
+        mut remaining: Enumerate<vec::IntoIter<DataEntry>>,
+        state: S,
+        description: &'static str,
+        call_arguments: fn(&S, &DataEntry) -> Vec<DataEntry>,
+        fold: fn(S, DataEntry, DataEntry) -> Result<S, EvaluationErrorKind>,
+        finish: fn(S) -> DataEntry,
+    ) -> Step {
+        let Some((pos, element)) = remaining.next() else {
+            return Step::Value(finish(state));
+        };
+        let call = Self::function_value_call(function.clone(), call_arguments(&state, &element));
+        Step::then(
+            Step::traced(
+                Trace::Formatted(format!("{} at {}", description, pos + 1)),
+                call,
+            ),
+            move |_, context, result| {
+                context.check_cancelled()?;
+                context.check_limits()?;
+                let state = fold(state, element, result)
+                    .map_err(|e| e.trace(format!("{} at {}", description, pos + 1)))?;
+                Ok(Self::fold_list(
+                    function,
+                    remaining,
+                    state,
+                    description,
+                    call_arguments,
+                    fold,
+                    finish,
+                ))
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    use crate::{
+        BuiltinRegistry, CallCache, DataEntry, EvaluationContext, EvaluationErrorKind,
+        EvaluationLimit, EvaluationStrategy, FnBuiltin, Runner, RunnerOption, Step, Zid,
+        parse_tool::WfFunctionCall,
+        parse_tool::WfParse,
+        test_fixture::{
+            add_builtin, add_composition, add_function, add_persistent, fixture_datas,
+            fixture_runner,
+        },
+    };
+
+    fn run_with_option(runner: &Runner, call: &WfFunctionCall) -> DataEntry {
+        runner
+            .run_function_call(call, &RunnerOption::default())
+            .unwrap()
+    }
+
+    fn run(call: &str, evaluation_strategy: EvaluationStrategy) -> DataEntry {
+        let runner = fixture_runner();
+        let call = serde_json::from_str::<DataEntry>(call).unwrap();
+        let option = RunnerOption {
+            evaluation_strategy,
+            ..Default::default()
+        };
+        runner
+            .run_function_call(&WfFunctionCall::parse(&call).unwrap(), &option)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_evaluation_strategies_agree() {
+        // xor(true, not(false)) and xor(false, not(false))
+        for (first, expected) in [("Z41", "Z42"), ("Z42", "Z41")] {
+            let first = format!(r#"{{ "Z1K1": "Z40", "Z40K1": "{first}" }}"#);
+            let call = format!(
+                r#"{{
+                    "Z1K1": "Z7",
+                    "Z7K1": "Z10002",
+                    "Z10002K1": {first},
+                    "Z10002K2": {{
+                        "Z1K1": "Z7",
+                        "Z7K1": "Z10001",
+                        "Z10001K1": {{ "Z1K1": "Z40", "Z40K1": "Z42" }}
+                    }}
+                }}"#
+            );
+            for strategy in [
+                EvaluationStrategy::Strict,
+                EvaluationStrategy::Lazy,
+                EvaluationStrategy::Orchestrator,
+            ] {
+                assert_eq!(
+                    run(&call, strategy),
+                    serde_json::from_str::<DataEntry>(&format!(
+                        r#"{{ "Z1K1": "Z40", "Z40K1": "{expected}" }}"#
+                    ))
+                    .unwrap(),
+                    "with {:?}",
+                    strategy
+                );
             }
         }
     }
+
+    #[test]
+    fn test_function_literal_captures_its_scope() {
+        let result = run(
+            r#"{
+                "Z1K1": "Z7",
+                "Z7K1": "Z10005",
+                "Z10005K1": {
+                    "Z1K1": "Z7",
+                    "Z7K1": "Z10001",
+                    "Z10001K1": { "Z1K1": "Z40", "Z40K1": "Z41" }
+                }
+            }"#,
+            EvaluationStrategy::Lazy,
+        );
+        let body = result
+            .get_map_entry(&zid!(8, 4))
+            .unwrap()
+            .get_array()
+            .unwrap()[1]
+            .get_map_entry(&zid!(14, 2))
+            .unwrap();
+        // the argument of the literal is kept, while the one of the enclosing function is replaced by its (still unevaluated) expression
+        assert_eq!(
+            body.get_map_entry(&zid!(802, 2)).unwrap(),
+            &serde_json::from_str::<DataEntry>(r#"{ "Z1K1": "Z18", "Z18K1": "K1" }"#).unwrap()
+        );
+        assert_eq!(
+            body.get_map_entry(&zid!(802, 3)).unwrap(),
+            &serde_json::from_str::<DataEntry>(
+                r#"{
+                    "Z1K1": "Z7",
+                    "Z7K1": "Z10001",
+                    "Z10001K1": { "Z1K1": "Z40", "Z40K1": "Z41" }
+                }"#
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_function_literal_captures_lazily() {
+        // apply(or with(loop(true)), true): the captured argument never terminates, but the literal doesn’t use it
+        for evaluation_strategy in [EvaluationStrategy::Lazy, EvaluationStrategy::Orchestrator] {
+            assert_eq!(
+                run(
+                    r#"{
+                        "Z1K1": "Z7",
+                        "Z7K1": "Z10006",
+                        "Z10006K1": {
+                            "Z1K1": "Z7",
+                            "Z7K1": "Z10005",
+                            "Z10005K1": {
+                                "Z1K1": "Z7",
+                                "Z7K1": "Z10003",
+                                "Z10003K1": { "Z1K1": "Z40", "Z40K1": "Z41" }
+                            }
+                        },
+                        "Z10006K2": { "Z1K1": "Z40", "Z40K1": "Z41" }
+                    }"#,
+                    evaluation_strategy,
+                ),
+                serde_json::from_str::<DataEntry>(r#"{ "Z1K1": "Z40", "Z40K1": "Z41" }"#).unwrap(),
+                "with {:?}",
+                evaluation_strategy
+            );
+        }
+    }
+
+    #[test]
+    fn test_function_literal_shares_captured_arguments() {
+        let mut datas = fixture_datas();
+        // Z10110 returns true, counting how many times it is called
+        add_function(
+            &mut datas,
+            "Z10110",
+            "counted true",
+            &["Z10110K1"],
+            "Z40",
+            &["Z10111"],
+        );
+        add_builtin(&mut datas, "Z10111", "Z10110");
+        // Z10112: xor(apply(Z10112K1, false), apply(Z10112K1, false)), which calls its function twice
+        add_function(
+            &mut datas,
+            "Z10112",
+            "apply twice",
+            &["Z10112K1"],
+            "Z40",
+            &["Z10113"],
+        );
+        add_composition(
+            &mut datas,
+            "Z10113",
+            "Z10112",
+            r#"{
+                "Z1K1": "Z7",
+                "Z7K1": "Z10002",
+                "Z10002K1": {
+                    "Z1K1": "Z7",
+                    "Z7K1": "Z10006",
+                    "Z10006K1": { "Z1K1": "Z18", "Z18K1": "Z10112K1" },
+                    "Z10006K2": { "Z1K1": "Z40", "Z40K1": "Z42" }
+                },
+                "Z10002K2": {
+                    "Z1K1": "Z7",
+                    "Z7K1": "Z10006",
+                    "Z10006K1": { "Z1K1": "Z18", "Z18K1": "Z10112K1" },
+                    "Z10006K2": { "Z1K1": "Z40", "Z40K1": "Z42" }
+                }
+            }"#,
+        );
+        let count = Arc::new(AtomicUsize::new(0));
+        let mut builtins = BuiltinRegistry::default();
+        let counter = count.clone();
+        builtins.register(
+            zid!(10111),
+            FnBuiltin::new([zid!(10110, 1)], move |runner, _, _| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(Step::Value(runner.get_true()?.clone()))
+            }),
+        );
+        let runner = Runner::new(Arc::new(datas)).with_builtins(builtins);
+        // apply twice(or with(counted true)): both calls of the literal need the captured argument
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{
+                "Z1K1": "Z7",
+                "Z7K1": "Z10112",
+                "Z10112K1": {
+                    "Z1K1": "Z7",
+                    "Z7K1": "Z10005",
+                    "Z10005K1": { "Z1K1": "Z7", "Z7K1": "Z10110", "Z10110K1": "unused" }
+                }
+            }"#,
+        )
+        .unwrap();
+        let call = WfFunctionCall::parse(&call).unwrap();
+
+        for (evaluation_strategy, expected_count) in [
+            (EvaluationStrategy::Lazy, 1),
+            (EvaluationStrategy::Orchestrator, 2),
+        ] {
+            count.store(0, Ordering::SeqCst);
+            let option = RunnerOption {
+                evaluation_strategy,
+                ..Default::default()
+            };
+            assert_eq!(
+                &runner.run_function_call(&call, &option).unwrap(),
+                runner.get_false().unwrap()
+            );
+            assert_eq!(
+                count.load(Ordering::SeqCst),
+                expected_count,
+                "with {:?}",
+                evaluation_strategy
+            );
+        }
+    }
+
+    #[test]
+    fn test_function_literal_binders_dont_capture() {
+        let mut datas = fixture_datas();
+        // Z10114(Z10114K1): a literal K1 -> Z10114K1, whose own K1 must not capture the outer reference
+        add_function(
+            &mut datas,
+            "Z10114",
+            "constant",
+            &["Z10114K1"],
+            "Z8",
+            &["Z10115"],
+        );
+        add_composition(
+            &mut datas,
+            "Z10115",
+            "Z10114",
+            r#"{
+                "Z1K1": "Z8",
+                "Z8K1": ["Z17", { "Z1K1": "Z17", "Z17K1": "Z40", "Z17K2": "K1", "Z17K3": { "Z1K1": "Z12", "Z12K1": ["Z11"] } }],
+                "Z8K2": "Z40",
+                "Z8K3": ["Z20"],
+                "Z8K4": ["Z14", {
+                    "Z1K1": "Z14",
+                    "Z14K2": { "Z1K1": "Z18", "Z18K1": "Z10114K1" }
+                }]
+            }"#,
+        );
+        let runner = Runner::new(Arc::new(datas));
+        // apply(constant(K1), false), where the outer K1 is unbound
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{
+                "Z1K1": "Z7",
+                "Z7K1": "Z10006",
+                "Z10006K1": {
+                    "Z1K1": "Z7",
+                    "Z7K1": "Z10114",
+                    "Z10114K1": { "Z1K1": "Z18", "Z18K1": "K1" }
+                },
+                "Z10006K2": { "Z1K1": "Z40", "Z40K1": "Z42" }
+            }"#,
+        )
+        .unwrap();
+        let call = WfFunctionCall::parse(&call).unwrap();
+        assert!(matches!(
+            runner
+                .run_function_call(&call, &RunnerOption::default())
+                .unwrap_err()
+                .root(),
+            EvaluationErrorKind::UnboundArgument(_)
+        ));
+    }
+
+    #[test]
+    fn test_higher_order_functions() {
+        let true_value =
+            serde_json::from_str::<DataEntry>(r#"{ "Z1K1": "Z40", "Z40K1": "Z41" }"#).unwrap();
+        let false_value =
+            serde_json::from_str::<DataEntry>(r#"{ "Z1K1": "Z40", "Z40K1": "Z42" }"#).unwrap();
+
+        // a persisted function passed as argument
+        assert_eq!(
+            run(
+                r#"{
+                    "Z1K1": "Z7",
+                    "Z7K1": "Z10006",
+                    "Z10006K1": "Z10001",
+                    "Z10006K2": { "Z1K1": "Z40", "Z40K1": "Z41" }
+                }"#,
+                EvaluationStrategy::Lazy
+            ),
+            false_value
+        );
+
+        // a function returned by a function call
+        assert_eq!(
+            run(
+                r#"{
+                    "Z1K1": "Z7",
+                    "Z7K1": {
+                        "Z1K1": "Z7",
+                        "Z7K1": "Z10005",
+                        "Z10005K1": { "Z1K1": "Z40", "Z40K1": "Z41" }
+                    },
+                    "K1": { "Z1K1": "Z40", "Z40K1": "Z42" }
+                }"#,
+                EvaluationStrategy::Lazy
+            ),
+            true_value
+        );
+
+        // a function literal
+        assert_eq!(
+            run(
+                r#"{
+                    "Z1K1": "Z7",
+                    "Z7K1": {
+                        "Z1K1": "Z8",
+                        "Z8K1": ["Z17", { "Z1K1": "Z17", "Z17K1": "Z40", "Z17K2": "K1", "Z17K3": { "Z1K1": "Z12", "Z12K1": ["Z11"] } }],
+                        "Z8K2": "Z40",
+                        "Z8K3": ["Z20"],
+                        "Z8K4": ["Z14", {
+                            "Z1K1": "Z14",
+                            "Z14K2": {
+                                "Z1K1": "Z7",
+                                "Z7K1": "Z10001",
+                                "Z10001K1": { "Z1K1": "Z18", "Z18K1": "K1" }
+                            }
+                        }]
+                    },
+                    "K1": { "Z1K1": "Z40", "Z40K1": "Z41" }
+                }"#,
+                EvaluationStrategy::Lazy
+            ),
+            false_value
+        );
+    }
+
+    #[test]
+    fn test_list_higher_order_builtins() {
+        const TRUE: &str = r#"{ "Z1K1": "Z40", "Z40K1": "Z41" }"#;
+        const FALSE: &str = r#"{ "Z1K1": "Z40", "Z40K1": "Z42" }"#;
+        let list = format!(r#"["Z40", {TRUE}, {FALSE}, {TRUE}]"#);
+
+        assert_eq!(
+            run(
+                &format!(
+                    r#"{{ "Z1K1": "Z7", "Z7K1": "Z873", "Z873K1": "Z10001", "Z873K2": {list} }}"#
+                ),
+                EvaluationStrategy::Lazy
+            ),
+            serde_json::from_str::<DataEntry>(&format!(r#"["Z40", {FALSE}, {TRUE}, {FALSE}]"#))
+                .unwrap()
+        );
+
+        assert_eq!(
+            run(
+                &format!(
+                    r#"{{ "Z1K1": "Z7", "Z7K1": "Z872", "Z872K1": "Z10001", "Z872K2": {list} }}"#
+                ),
+                EvaluationStrategy::Lazy
+            ),
+            serde_json::from_str::<DataEntry>(&format!(r#"["Z40", {FALSE}]"#)).unwrap()
+        );
+
+        // false xor true xor false xor true
+        assert_eq!(
+            run(
+                &format!(
+                    r#"{{ "Z1K1": "Z7", "Z7K1": "Z876", "Z876K1": "Z10002", "Z876K2": {list}, "Z876K3": {FALSE} }}"#
+                ),
+                EvaluationStrategy::Lazy
+            ),
+            serde_json::from_str::<DataEntry>(FALSE).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_unused_argument_is_not_evaluated() {
+        let call = r#"{
+            "Z1K1": "Z7",
+            "Z7K1": "Z10004",
+            "Z10004K1": "Z41",
+            "Z10004K2": { "Z1K1": "Z7", "Z7K1": "Z10003", "Z10003K1": "Z41" }
+        }"#;
+        for strategy in [EvaluationStrategy::Lazy, EvaluationStrategy::Orchestrator] {
+            assert_eq!(run(call, strategy), DataEntry::String("Z41".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_non_terminating_call_is_aborted() {
+        let runner = fixture_runner();
+        let tail_loop = serde_json::from_str::<DataEntry>(
+            r#"{ "Z1K1": "Z7", "Z7K1": "Z10003", "Z10003K1": "Z41" }"#,
+        )
+        .unwrap();
+        let deep_loop = serde_json::from_str::<DataEntry>(
+            r#"{ "Z1K1": "Z7", "Z7K1": "Z10007", "Z10007K1": "Z41" }"#,
+        )
+        .unwrap();
+
+        for (call, option, expected_limit, expected_depth) in [
+            // far deeper than what the native stack could hold
+            (
+                &deep_loop,
+                RunnerOption {
+                    max_depth: Some(20_000),
+                    ..Default::default()
+                },
+                EvaluationLimit::Depth(20_000),
+                20_001,
+            ),
+            // tail calls don’t make the call stack grow
+            (
+                &tail_loop,
+                RunnerOption {
+                    max_steps: Some(1000),
+                    ..Default::default()
+                },
+                EvaluationLimit::Steps(1000),
+                1,
+            ),
+            (
+                &tail_loop,
+                RunnerOption {
+                    timeout: Some(Duration::ZERO),
+                    ..Default::default()
+                },
+                EvaluationLimit::Time(Duration::ZERO),
+                1,
+            ),
+        ] {
+            let error = runner
+                .run_function_call(&WfFunctionCall::parse(call).unwrap(), &option)
+                .unwrap_err();
+            let EvaluationErrorKind::LimitExceeded(limit, call_stack) = error.root() else {
+                panic!("unexpected error {:?}", error);
+            };
+            assert_eq!(*limit, expected_limit);
+            assert_eq!(call_stack.len(), expected_depth);
+            assert_eq!(
+                error.to_zobject().get_map_entry(&zid!(5, 1)).unwrap(),
+                &DataEntry::String("Z507".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_cancelled_evaluation() {
+        let runner = fixture_runner();
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{ "Z1K1": "Z7", "Z7K1": "Z10001", "Z10001K1": { "Z1K1": "Z40", "Z40K1": "Z41" } }"#,
+        )
+        .unwrap();
+        let call = WfFunctionCall::parse(&call).unwrap();
+
+        let option = RunnerOption::default();
+        let result = runner.run_function_call(&call, &option).unwrap();
+
+        option.cancellation_token.clone().cancel();
+        assert!(matches!(
+            runner.run_function_call(&call, &option).unwrap_err().root(),
+            EvaluationErrorKind::Cancelled
+        ));
+
+        // the runner is still usable with another token
+        assert_eq!(
+            runner
+                .run_function_call(&call, &RunnerOption::default())
+                .unwrap(),
+            result
+        );
+    }
+
+    #[test]
+    fn test_call_cache() {
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{
+                "Z1K1": "Z7",
+                "Z7K1": "Z10002",
+                "Z10002K1": { "Z1K1": "Z40", "Z40K1": "Z41" },
+                "Z10002K2": {
+                    "Z1K1": "Z7",
+                    "Z7K1": "Z10001",
+                    "Z10001K1": { "Z1K1": "Z40", "Z40K1": "Z42" }
+                }
+            }"#,
+        )
+        .unwrap();
+        let call = WfFunctionCall::parse(&call).unwrap();
+        let expected = run_with_option(&fixture_runner(), &call);
+
+        let runner = fixture_runner().with_call_cache(CallCache::new(100));
+        assert_eq!(run_with_option(&runner, &call), expected);
+        let first_stats = runner.get_call_cache().unwrap().stats();
+        assert_eq!(first_stats.hits, 0);
+        assert!(first_stats.entries > 0);
+
+        // not(false) is reused to build the key of xor, whose result is then reused
+        assert_eq!(run_with_option(&runner, &call), expected);
+        let second_stats = runner.get_call_cache().unwrap().stats();
+        assert_eq!(second_stats.hits, 2);
+        assert_eq!(second_stats.misses, first_stats.misses);
+
+        // xor calls not, so it can’t be cached either
+        let runner =
+            fixture_runner().with_call_cache(CallCache::new(100).with_impure_function(zid!(10001)));
+        assert_eq!(run_with_option(&runner, &call), expected);
+        assert_eq!(runner.get_call_cache().unwrap().stats().entries, 0);
+    }
+
+    #[test]
+    fn test_values_equal() {
+        let mut datas = fixture_datas();
+        // Z10080 compares with Z10081, which tells any two values equal. Z10082 has no equality function.
+        for (zid, equality) in [("Z10080", r#", "Z4K4": "Z10081""#), ("Z10082", "")] {
+            add_persistent(
+                &mut datas,
+                zid,
+                zid,
+                &format!(
+                    r#"{{
+                        "Z1K1": "Z4",
+                        "Z4K1": "{zid}",
+                        "Z4K2": ["Z3", {{ "Z1K1": "Z3", "Z3K1": "Z6", "Z3K2": "{zid}K1", "Z3K3": {{ "Z1K1": "Z12", "Z12K1": ["Z11"] }} }}],
+                        "Z4K3": "Z101",
+                        "Z4K5": "Z101",
+                        "Z4K6": "Z101",
+                        "Z4K7": ["Z46"],
+                        "Z4K8": ["Z64"]{equality}
+                    }}"#
+                ),
+            );
+        }
+        add_function(
+            &mut datas,
+            "Z10081",
+            "always equal",
+            &["Z10081K1", "Z10081K2"],
+            "Z40",
+            &["Z100811"],
+        );
+        add_composition(
+            &mut datas,
+            "Z100811",
+            "Z10081",
+            r#"{ "Z1K1": "Z40", "Z40K1": "Z41" }"#,
+        );
+        let runner = Runner::new(Arc::new(datas));
+        let option = RunnerOption::default();
+        let context = EvaluationContext::new(&option);
+
+        let value = |type_zid: &str, content: &str| {
+            serde_json::from_str::<DataEntry>(&format!(
+                r#"{{ "Z1K1": "{type_zid}", "{type_zid}K1": "{content}" }}"#
+            ))
+            .unwrap()
+        };
+        let equal = |left: &DataEntry, right: &DataEntry| {
+            runner.values_equal(left, right, &context).unwrap()
+        };
+
+        assert!(equal(&value("Z10080", "a"), &value("Z10080", "b")));
+        assert!(equal(&value("Z10082", "a"), &value("Z10082", "a")));
+        assert!(!equal(&value("Z10082", "a"), &value("Z10082", "b")));
+        assert!(!equal(&value("Z10080", "a"), &value("Z10082", "a")));
+        // list elements are compared with the equality of their type too
+        let list = |type_zid: &str, content: &str| {
+            DataEntry::Array(vec![
+                DataEntry::String(type_zid.to_string()),
+                value(type_zid, content),
+            ])
+        };
+        assert!(equal(&list("Z10080", "a"), &list("Z10080", "b")));
+        assert!(!equal(&list("Z10082", "a"), &list("Z10082", "b")));
+    }
+
+    #[test]
+    fn test_values_equal_checks_limits() {
+        let runner = fixture_runner();
+        let list = serde_json::from_str::<DataEntry>(
+            r#"["Z6", { "Z1K1": "Z6", "Z6K1": "a" }, { "Z1K1": "Z6", "Z6K1": "b" }]"#,
+        )
+        .unwrap();
+        // comparing lists doesn’t enter calls, but still stops once the time is up
+        let option = RunnerOption {
+            timeout: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        let error = runner
+            .values_equal(&list, &list, &EvaluationContext::new(&option))
+            .unwrap_err();
+        assert!(matches!(
+            error.root(),
+            EvaluationErrorKind::LimitExceeded(EvaluationLimit::Time(_), _)
+        ));
+    }
 }
@@ -0,0 +1,262 @@
+//! How the implementation run for a function is picked among its implementations
+
+use std::{
+    collections::BTreeSet,
+    fmt::Display,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use crate::{Runner, RunnerOption, Zid, parse_tool::WfImplementation};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ImplementationKind {
+    Composition,
+    Builtin,
+    Code,
+}
+
+impl ImplementationKind {
+    pub fn of(implementation: &WfImplementation) -> Option<Self> {
+        if implementation.composition.is_some() {
+            Some(Self::Composition)
+        } else if implementation.builtin.is_some() {
+            Some(Self::Builtin)
+        } else if implementation.code.is_some() {
+            Some(Self::Code)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for ImplementationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Composition => "composition",
+            Self::Builtin => "builtin",
+            Self::Code => "code",
+        })
+    }
+}
+
+/// Picks implementations at random, from a seed, for fuzzing. Clones share the same sequence.
+#[derive(Debug, Clone)]
+pub struct RandomChoice {
+    state: Arc<AtomicU64>,
+}
+
+impl RandomChoice {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Arc::new(AtomicU64::new(seed)),
+        }
+    }
+
+    /// SplitMix64
+    pub(crate) fn next_index(&self, len: usize) -> usize {
+        let mut z = self
+            .state
+            .fetch_add(0x9e3779b97f4a7c15, Ordering::Relaxed)
+            .wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        (z % len as u64) as usize
+    }
+}
+
+/// Which implementations may be picked, and in which order of preference
+#[derive(Debug, Clone)]
+pub struct SelectionPolicy {
+    /// The implementations of the kinds not listed are never picked
+    pub kind_order: Vec<ImplementationKind>,
+    /// Only pick these implementations, if set
+    pub allow: Option<BTreeSet<Zid>>,
+    pub deny: BTreeSet<Zid>,
+    /// Prefer the implementations that pass all the testers of their function, if any does
+    pub prefer_passing_testers: bool,
+    /// Pick at random among the implementations left, whatever their kind
+    pub random: Option<RandomChoice>,
+}
+
+impl Default for SelectionPolicy {
+    fn default() -> Self {
+        Self {
+            kind_order: vec![
+                ImplementationKind::Composition,
+                ImplementationKind::Builtin,
+                ImplementationKind::Code,
+            ],
+            allow: None,
+            deny: BTreeSet::new(),
+            prefer_passing_testers: false,
+            random: None,
+        }
+    }
+}
+
+/// Why an implementation was chosen, or skipped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectionNote {
+    /// None for the implementations written inside a function literal
+    pub implementation: Option<Zid>,
+    pub chosen: bool,
+    pub reason: String,
+}
+
+impl SelectionNote {
+    pub(crate) fn new(
+        implementation: Option<Zid>,
+        chosen: bool,
+        reason: impl Into<String>,
+    ) -> Self {
+        Self {
+            implementation,
+            chosen,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl Display for SelectionNote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.implementation {
+            Some(implementation) => write!(f, "{}", implementation)?,
+            None => f.write_str("inline implementation")?,
+        }
+        let decision = if self.chosen { "chosen" } else { "skipped" };
+        write!(f, " {}: {}", decision, self.reason)
+    }
+}
+
+impl Runner {
+    /// Whether the implementation passes all the test cases of its function. The result is kept for the next calls.
+    pub fn passes_testers(
+        &self,
+        function_id: &Zid,
+        implementation_id: &Zid,
+        option: &RunnerOption,
+    ) -> bool {
+        if let Some(passes) = self.tester_results.lock().unwrap().get(implementation_id) {
+            return *passes;
+        }
+        // the testers are run like the test runner would, without recording them
+        let option = RunnerOption {
+            selection: SelectionPolicy {
+                prefer_passing_testers: false,
+                ..option.selection.clone()
+            },
+            tracer: None,
+            profiler: None,
+            debugger: None,
+            ..option.clone()
+        };
+        let passes = self.test_matrix(function_id).is_ok_and(|jobs| {
+            jobs.iter()
+                .filter(|job| job.implementation == Some(*implementation_id))
+                .all(|job| self.run_test_job(job, &option).is_ok())
+        });
+        self.tester_results
+            .lock()
+            .unwrap()
+            .insert(*implementation_id, passes);
+        passes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, sync::Arc};
+
+    use crate::{
+        ImplementationKind, RandomChoice, Runner, RunnerOption, SelectionPolicy, Zid,
+        parse_tool::WfFunction,
+        test_fixture::{add_boolean_identity, fixture_datas},
+    };
+
+    #[test]
+    fn test_selection_policy() {
+        let mut datas = fixture_datas();
+        // a function with a code implementation listed first, then a wrong composition, then a right one
+        add_boolean_identity(
+            &mut datas,
+            "Z10060",
+            &[
+                ("Z10061", None),
+                (
+                    "Z10062",
+                    Some(
+                        r#"{ "Z1K1": "Z7", "Z7K1": "Z10001", "Z10001K1": { "Z1K1": "Z18", "Z18K1": "Z10060K1" } }"#,
+                    ),
+                ),
+                ("Z10063", Some(r#"{ "Z1K1": "Z18", "Z18K1": "Z10060K1" }"#)),
+            ],
+            "Z10064",
+            true,
+        );
+        let runner = Runner::new(Arc::new(datas));
+        let function = runner
+            .get_persistent_object::<WfFunction>(&zid!(10060))
+            .unwrap()
+            .value;
+        let chosen = |selection: SelectionPolicy| {
+            let option = RunnerOption {
+                selection,
+                ..Default::default()
+            };
+            runner
+                .get_preferred_implementation(&function, &option)
+                .map(|implementation| implementation.id.unwrap())
+        };
+
+        // compositions first, in list order
+        assert_eq!(chosen(SelectionPolicy::default()).unwrap(), zid!(10062));
+        assert_eq!(
+            chosen(SelectionPolicy {
+                deny: BTreeSet::from([zid!(10062)]),
+                ..Default::default()
+            })
+            .unwrap(),
+            zid!(10063)
+        );
+        assert_eq!(
+            chosen(SelectionPolicy {
+                prefer_passing_testers: true,
+                ..Default::default()
+            })
+            .unwrap(),
+            zid!(10063)
+        );
+        // nothing left
+        assert!(
+            chosen(SelectionPolicy {
+                kind_order: vec![ImplementationKind::Builtin],
+                ..Default::default()
+            })
+            .is_err()
+        );
+        // the choice is among the allowed ones
+        let random = RandomChoice::new(1);
+        for _ in 0..10 {
+            assert_ne!(
+                chosen(SelectionPolicy {
+                    allow: Some(BTreeSet::from([zid!(10062), zid!(10063)])),
+                    random: Some(random.clone()),
+                    ..Default::default()
+                })
+                .unwrap(),
+                zid!(10061)
+            );
+        }
+
+        // the reasons are recorded
+        let chosen = runner
+            .get_preferred_implementation(&function, &RunnerOption::default())
+            .unwrap();
+        assert_eq!(chosen.selection.len(), 3);
+        assert!(chosen.selection[1].chosen);
+    }
+}
@@ -0,0 +1,344 @@
+//! Rust functions standing in for the code implementations of functions. They get the evaluated arguments in declaration order, and return the result, like a built-in would.
+
+use std::{collections::BTreeMap, fmt::Debug, sync::Arc};
+
+use crate::{
+    DataEntry, EvaluationErrorKind, NumberOperation, Runner, RunnerOption, Zid,
+    parse_tool::{WfImplementation, WfTestCase},
+};
+
+pub(crate) type Shim = dyn Fn(&[DataEntry]) -> Result<DataEntry, EvaluationErrorKind> + Send + Sync;
+
+/// The shims to use, by function. Clones share the registered shims.
+#[derive(Clone, Default)]
+pub struct ShimRegistry {
+    shims: BTreeMap<Zid, Arc<Shim>>,
+}
+
+impl Debug for ShimRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.shims.keys()).finish()
+    }
+}
+
+impl ShimRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run the shim in place of the code implementations of the function. It replaces the previous one.
+    pub fn register(
+        &mut self,
+        function: Zid,
+        shim: impl Fn(&[DataEntry]) -> Result<DataEntry, EvaluationErrorKind> + Send + Sync + 'static,
+    ) {
+        self.shims.insert(function, Arc::new(shim));
+    }
+
+    pub fn get(&self, function: &Zid) -> Option<&Shim> {
+        self.shims.get(function).map(|shim| shim.as_ref())
+    }
+
+    pub fn functions(&self) -> impl Iterator<Item = &Zid> {
+        self.shims.keys()
+    }
+}
+
+/// A function of Wikifunctions run by an operation of NUMBER_OPERATIONS, unless the function of that id lacks its English label or its signature
+#[derive(Debug, Clone, Copy)]
+pub struct DefaultShim {
+    pub function: Zid,
+    pub label: &'static str,
+    pub operation: &'static str,
+}
+
+pub(crate) const fn default_shim(
+    function: Zid,
+    label: &'static str,
+    operation: &'static str,
+) -> DefaultShim {
+    DefaultShim {
+        function,
+        label,
+        operation,
+    }
+}
+
+/// The popular string and boolean functions the runner shims by default
+pub const DEFAULT_SHIMS: &[DefaultShim] = &[
+    default_shim(zid!(10000), "join two strings", "join strings"),
+    default_shim(zid!(10012), "reverse string", "reverse string"),
+    default_shim(zid!(11040), "length of string", "string length"),
+    default_shim(zid!(10008), "is empty string", "string is empty"),
+    default_shim(zid!(10018), "to uppercase", "uppercase"),
+    default_shim(zid!(10047), "to lowercase", "lowercase"),
+    default_shim(zid!(10174), "and", "and"),
+    default_shim(zid!(10184), "or", "or"),
+    default_shim(zid!(10216), "not", "not"),
+];
+
+/// The results of the shim and of a code implementation, on the call of a test case
+#[derive(Debug)]
+pub struct ShimCheck {
+    pub function: Zid,
+    pub test_case: Zid,
+    pub implementation: Zid,
+    pub shim_result: Result<DataEntry, String>,
+    pub code_result: Result<DataEntry, String>,
+}
+
+impl ShimCheck {
+    /// Both return the same value
+    pub fn agrees(&self) -> bool {
+        match (&self.shim_result, &self.code_result) {
+            (Ok(shim), Ok(code)) => shim.normalize() == code.normalize(),
+            _ => false,
+        }
+    }
+
+    /// Neither returns a value, so the call checks nothing
+    pub fn both_fail(&self) -> bool {
+        self.shim_result.is_err() && self.code_result.is_err()
+    }
+}
+
+impl Runner {
+    /// The shims of the table whose function is in the datas, with the English label and the signature expected. The others run their code.
+    pub fn shims_of<'t>(&self, table: impl IntoIterator<Item = &'t DefaultShim>) -> ShimRegistry {
+        let mut shims = ShimRegistry::new();
+        for default in table {
+            let Some(operation) = NumberOperation::named(default.operation) else {
+                continue;
+            };
+            let labelled = self
+                .get_datas()
+                .english_label(&default.function)
+                .is_some_and(|label| label.eq_ignore_ascii_case(default.label));
+            if labelled
+                && self
+                    .has_signature_of(&default.function, &operation)
+                    .unwrap_or(false)
+            {
+                shims.register(default.function, move |arguments| {
+                    operation.apply(arguments)
+                });
+            }
+        }
+        shims
+    }
+
+    /// Run the calls of the testers of each function with a shim, with the shim and with each code implementation of the function
+    pub fn check_shims(
+        &self,
+        option: &RunnerOption,
+    ) -> Result<Vec<ShimCheck>, EvaluationErrorKind> {
+        let shims = option.shims.as_ref().unwrap_or(self.get_shims());
+        let without_shims = RunnerOption {
+            shims: Some(ShimRegistry::new()),
+            ..option.clone()
+        };
+        let mut checks = Vec::new();
+        for function in shims.functions() {
+            for job in self.test_matrix(function)? {
+                let Some(implementation) = job.implementation else {
+                    continue;
+                };
+                let is_code = self
+                    .get_persistent_object::<WfImplementation>(&implementation)
+                    .map_err(|e| e.trace(format!("getting the implementation {}", implementation)))?
+                    .value
+                    .code
+                    .is_some();
+                if !is_code {
+                    continue;
+                }
+                let test_case = self
+                    .get_persistent_object::<WfTestCase>(&job.test_case)
+                    .map_err(|e| e.trace(format!("getting the test case {}", job.test_case)))?;
+                let call = test_case.value.call.evaluate(self)?;
+                let run = |option: &RunnerOption| {
+                    let mut option = option.clone();
+                    option
+                        .force_use_impl
+                        .get_or_insert_with(Default::default)
+                        .insert(*function, implementation);
+                    self.run_function_call(&call, &option)
+                        .map_err(|e| e.root().to_string())
+                };
+                checks.push(ShimCheck {
+                    function: *function,
+                    test_case: job.test_case,
+                    implementation,
+                    shim_result: run(option),
+                    code_result: run(&without_shims),
+                });
+            }
+        }
+        Ok(checks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        DataEntry, EvaluationErrorKind, GlobalDatas, Runner, RunnerOption, ShimCheck, ShimRegistry,
+        Zid,
+        parse_tool::{WfFunctionCall, WfParse, raw_string_to_object_string},
+        test_fixture::{add_persistent, fixture_datas},
+    };
+
+    #[test]
+    fn test_shims() {
+        let mut datas = fixture_datas();
+        // reverse, with a code implementation and a test case
+        add_persistent(
+            &mut datas,
+            "Z10050",
+            "reverse",
+            r#"{
+                "Z1K1": "Z8",
+                "Z8K1": ["Z17", { "Z1K1": "Z17", "Z17K1": "Z6", "Z17K2": "Z10050K1", "Z17K3": { "Z1K1": "Z12", "Z12K1": ["Z11"] } }],
+                "Z8K2": "Z6",
+                "Z8K3": ["Z20", "Z10052"],
+                "Z8K4": ["Z14", "Z10051"],
+                "Z8K5": "Z10050"
+            }"#,
+        );
+        add_persistent(
+            &mut datas,
+            "Z10051",
+            "Z10051",
+            r#"{
+                "Z1K1": "Z14",
+                "Z14K1": "Z10050",
+                "Z14K3": {
+                    "Z1K1": "Z16",
+                    "Z16K1": "Z600",
+                    "Z16K2": "function Z10050( Z10050K1 ) { return [ ...Z10050K1 ].reverse().join( '' ); }"
+                }
+            }"#,
+        );
+        add_persistent(
+            &mut datas,
+            "Z10052",
+            "Z10052",
+            r#"{
+                "Z1K1": "Z20",
+                "Z20K1": "Z10050",
+                "Z20K2": { "Z1K1": "Z7", "Z7K1": "Z10050", "Z10050K1": "abc" },
+                "Z20K3": { "Z1K1": "Z7", "Z7K1": "Z866", "Z866K2": "cba" }
+            }"#,
+        );
+        let runner = Runner::new(Arc::new(datas));
+
+        let mut shims = ShimRegistry::new();
+        shims.register(zid!(10050), |arguments| {
+            let DataEntry::String(text) = arguments[0].normalize() else {
+                return Err(EvaluationErrorKind::Unimplemented(
+                    "reversing a non-string".to_string(),
+                ));
+            };
+            Ok(raw_string_to_object_string(text.chars().rev().collect()))
+        });
+        let option = RunnerOption {
+            shims: Some(shims),
+            ..Default::default()
+        };
+        // the code implementation is picked even when its language can’t be run, and the shim runs in its place
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{ "Z1K1": "Z7", "Z7K1": "Z10050", "Z10050K1": "abc" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            runner
+                .run_function_call(&WfFunctionCall::parse(&call).unwrap(), &option)
+                .unwrap(),
+            raw_string_to_object_string("cba".to_string())
+        );
+
+        let checks = runner.check_shims(&option).unwrap();
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].agrees(), cfg!(feature = "javascript"));
+    }
+
+    fn join_strings_datas(label: &str) -> GlobalDatas {
+        let mut datas = fixture_datas();
+        add_persistent(
+            &mut datas,
+            "Z10000",
+            label,
+            r#"{
+                "Z1K1": "Z8",
+                "Z8K1": [
+                    "Z17",
+                    { "Z1K1": "Z17", "Z17K1": "Z6", "Z17K2": "Z10000K1", "Z17K3": { "Z1K1": "Z12", "Z12K1": ["Z11"] } },
+                    { "Z1K1": "Z17", "Z17K1": "Z6", "Z17K2": "Z10000K2", "Z17K3": { "Z1K1": "Z12", "Z12K1": ["Z11"] } }
+                ],
+                "Z8K2": "Z6",
+                "Z8K3": ["Z20"],
+                "Z8K4": ["Z14", "Z10053"],
+                "Z8K5": "Z10000"
+            }"#,
+        );
+        add_persistent(
+            &mut datas,
+            "Z10053",
+            "Z10053",
+            r#"{
+                "Z1K1": "Z14",
+                "Z14K1": "Z10000",
+                "Z14K3": {
+                    "Z1K1": "Z16",
+                    "Z16K1": "Z600",
+                    "Z16K2": "function Z10000( Z10000K1, Z10000K2 ) { return Z10000K1 + Z10000K2; }"
+                }
+            }"#,
+        );
+        datas
+    }
+
+    #[test]
+    fn test_default_shims() {
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{ "Z1K1": "Z7", "Z7K1": "Z10000", "Z10000K1": "ab", "Z10000K2": "c" }"#,
+        )
+        .unwrap();
+        let call = WfFunctionCall::parse(&call).unwrap();
+
+        let runner = Runner::new(Arc::new(join_strings_datas("Join two strings")));
+        assert_eq!(
+            runner
+                .run_function_call(&call, &RunnerOption::default())
+                .unwrap(),
+            raw_string_to_object_string("abc".to_string())
+        );
+        // the option replaces the shims of the runner
+        let without_shims = RunnerOption {
+            shims: Some(ShimRegistry::new()),
+            ..Default::default()
+        };
+        assert_eq!(
+            runner.run_function_call(&call, &without_shims).is_ok(),
+            cfg!(feature = "javascript")
+        );
+
+        // another function got that id
+        let runner = Runner::new(Arc::new(join_strings_datas("join")));
+        assert!(runner.get_shims().get(&zid!(10000)).is_none());
+    }
+
+    #[test]
+    fn test_both_fail() {
+        let check = ShimCheck {
+            function: zid!(10000),
+            test_case: zid!(10054),
+            implementation: zid!(10053),
+            shim_result: Err("Z507".to_string()),
+            code_result: Err("Z507".to_string()),
+        };
+        assert!(!check.agrees());
+        assert!(check.both_fail());
+    }
+}
@@ -0,0 +1,463 @@
+//! A tiny set of ZObjects, shaped like the ones of the dump, to test the runner without it
+
+use std::sync::Arc;
+
+use crate::{DataEntry, GlobalDatas, Runner};
+
+/// Wrap the value in a Z2, and add it to the datas
+pub fn add_persistent(datas: &mut GlobalDatas, zid: &str, label: &str, value: &str) {
+    let content = format!(
+        r#"{{
+            "Z1K1": "Z2",
+            "Z2K1": {{ "Z1K1": "Z6", "Z6K1": "{zid}" }},
+            "Z2K2": {value},
+            "Z2K3": {{ "Z1K1": "Z12", "Z12K1": ["Z11", {{ "Z1K1": "Z11", "Z11K1": "Z1002", "Z11K2": "{label}" }}] }},
+            "Z2K4": {{ "Z1K1": "Z32", "Z32K1": ["Z31"] }},
+            "Z2K5": {{ "Z1K1": "Z12", "Z12K1": ["Z11"] }}
+        }}"#
+    );
+    datas.add_entry(zid, &content).unwrap();
+}
+
+/// Add a function with the given argument keys (all of type Z1) and implementations
+pub fn add_function(
+    datas: &mut GlobalDatas,
+    zid: &str,
+    label: &str,
+    argument_keys: &[&str],
+    return_type: &str,
+    implementations: &[&str],
+) {
+    let arguments = argument_keys
+        .iter()
+        .map(|key| {
+            format!(
+                r#"{{ "Z1K1": "Z17", "Z17K1": "Z1", "Z17K2": "{key}", "Z17K3": {{ "Z1K1": "Z12", "Z12K1": ["Z11"] }} }}"#
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let implementations = implementations
+        .iter()
+        .map(|implementation| format!(r#""{implementation}""#))
+        .collect::<Vec<_>>()
+        .join(", ");
+    add_persistent(
+        datas,
+        zid,
+        label,
+        &format!(
+            r#"{{
+                "Z1K1": "Z8",
+                "Z8K1": ["Z17", {arguments}],
+                "Z8K2": "{return_type}",
+                "Z8K3": ["Z20"],
+                "Z8K4": ["Z14", {implementations}],
+                "Z8K5": "{zid}"
+            }}"#
+        ),
+    );
+}
+
+/// Add a type with a single key of type Z6, and the given type converters
+pub fn add_type(datas: &mut GlobalDatas, zid: &str, to_code: &[&str], from_code: &[&str]) {
+    let references = |converters: &[&str]| {
+        converters
+            .iter()
+            .map(|converter| format!(r#", "{converter}""#))
+            .collect::<String>()
+    };
+    let to_code = references(to_code);
+    let from_code = references(from_code);
+    add_persistent(
+        datas,
+        zid,
+        zid,
+        &format!(
+            r#"{{
+                "Z1K1": "Z4",
+                "Z4K1": "{zid}",
+                "Z4K2": ["Z3", {{ "Z1K1": "Z3", "Z3K1": "Z6", "Z3K2": "{zid}K1", "Z3K3": {{ "Z1K1": "Z12", "Z12K1": ["Z11"] }} }}],
+                "Z4K3": "Z101",
+                "Z4K5": "Z101",
+                "Z4K6": "Z101",
+                "Z4K7": ["Z46"{to_code}],
+                "Z4K8": ["Z64"{from_code}]
+            }}"#
+        ),
+    );
+}
+
+/// Add a Z46 (or a Z64) converting the type with code
+pub fn add_type_converter(
+    datas: &mut GlobalDatas,
+    zid: &str,
+    converter_type: &str,
+    converted_type: &str,
+    language: &str,
+    source: &str,
+) {
+    let source = serde_json::to_string(source).unwrap();
+    add_persistent(
+        datas,
+        zid,
+        zid,
+        &format!(
+            r#"{{
+                "Z1K1": "{converter_type}",
+                "{converter_type}K1": "{zid}",
+                "{converter_type}K2": "{converted_type}",
+                "{converter_type}K3": {{ "Z1K1": "Z16", "Z16K1": "{language}", "Z16K2": {source} }},
+                "{converter_type}K4": {{ "Z1K1": "Z6", "Z6K1": "native" }}
+            }}"#
+        ),
+    );
+}
+
+pub fn add_builtin(datas: &mut GlobalDatas, zid: &str, function: &str) {
+    add_persistent(
+        datas,
+        zid,
+        zid,
+        &format!(
+            r#"{{ "Z1K1": "Z14", "Z14K1": "{function}", "Z14K4": {{ "Z1K1": "Z6", "Z6K1": "{zid}" }} }}"#
+        ),
+    );
+}
+
+pub fn add_composition(datas: &mut GlobalDatas, zid: &str, function: &str, composition: &str) {
+    add_persistent(
+        datas,
+        zid,
+        zid,
+        &format!(r#"{{ "Z1K1": "Z14", "Z14K1": "{function}", "Z14K2": {composition} }}"#),
+    );
+}
+
+/// Add the identity on booleans, with a test case calling it on the input. Its implementations are listed in the given order: each is a composition, or with None JavaScript code returning the argument.
+pub fn add_boolean_identity(
+    datas: &mut GlobalDatas,
+    zid: &str,
+    implementations: &[(&str, Option<&str>)],
+    test_case: &str,
+    input: bool,
+) {
+    let implementation_list = implementations
+        .iter()
+        .map(|(implementation, _)| format!(r#""{implementation}""#))
+        .collect::<Vec<_>>()
+        .join(", ");
+    add_persistent(
+        datas,
+        zid,
+        "identity",
+        &format!(
+            r#"{{
+                "Z1K1": "Z8",
+                "Z8K1": ["Z17", {{ "Z1K1": "Z17", "Z17K1": "Z40", "Z17K2": "{zid}K1", "Z17K3": {{ "Z1K1": "Z12", "Z12K1": ["Z11"] }} }}],
+                "Z8K2": "Z40",
+                "Z8K3": ["Z20", "{test_case}"],
+                "Z8K4": ["Z14", {implementation_list}],
+                "Z8K5": "{zid}"
+            }}"#
+        ),
+    );
+    for (implementation, composition) in implementations {
+        match composition {
+            Some(composition) => add_composition(datas, implementation, zid, composition),
+            None => add_persistent(
+                datas,
+                implementation,
+                implementation,
+                &format!(
+                    r#"{{
+                        "Z1K1": "Z14",
+                        "Z14K1": "{zid}",
+                        "Z14K3": {{ "Z1K1": "Z16", "Z16K1": "Z600", "Z16K2": "function {zid}( {zid}K1 ) {{ return {zid}K1; }}" }}
+                    }}"#
+                ),
+            ),
+        }
+    }
+    let boolean = if input { "Z41" } else { "Z42" };
+    add_persistent(
+        datas,
+        test_case,
+        test_case,
+        &format!(
+            r#"{{
+                "Z1K1": "Z20",
+                "Z20K1": "{zid}",
+                "Z20K2": {{ "Z1K1": "Z7", "Z7K1": "{zid}", "{zid}K1": {{ "Z1K1": "Z40", "Z40K1": "{boolean}" }} }},
+                "Z20K3": {{ "Z1K1": "Z7", "Z7K1": "Z844", "Z844K2": {{ "Z1K1": "Z40", "Z40K1": "{boolean}" }} }}
+            }}"#
+        ),
+    );
+}
+
+/// The booleans, Z802 (if), Z844 (boolean equality), Z872 (filter), Z873 (map), Z876 (reduce), and a few compositions over them:
+/// - Z10001 (not): if(Z10001K1, false, true)
+/// - Z10002 (xor): if(Z10002K1, not(Z10002K2), Z10002K2)
+/// - Z10003 (loop): call itself forever. Only terminate when not evaluated
+/// - Z10004 (first): return Z10004K1, ignoring Z10004K2
+/// - Z10005 (or with): return the function K1 -> if(K1, K1, Z10005K1), as a function literal
+/// - Z10006 (apply): call the function Z10006K1 with Z10006K2 as its first argument
+/// - Z10007 (deep loop): not(deep loop(Z10007K1)). Like Z10003, but the recursive call isn’t a tail call
+pub fn fixture_datas() -> GlobalDatas {
+    let mut datas = GlobalDatas::default();
+
+    add_persistent(
+        &mut datas,
+        "Z40",
+        "Boolean",
+        r#"{
+            "Z1K1": "Z4",
+            "Z4K1": "Z40",
+            "Z4K2": ["Z3"],
+            "Z4K3": "Z140",
+            "Z4K4": "Z844",
+            "Z4K5": "Z140",
+            "Z4K6": "Z140",
+            "Z4K7": ["Z46"],
+            "Z4K8": ["Z64"]
+        }"#,
+    );
+    add_persistent(
+        &mut datas,
+        "Z41",
+        "true",
+        r#"{ "Z1K1": "Z40", "Z40K1": "Z41" }"#,
+    );
+    add_persistent(
+        &mut datas,
+        "Z42",
+        "false",
+        r#"{ "Z1K1": "Z40", "Z40K1": "Z42" }"#,
+    );
+
+    add_function(
+        &mut datas,
+        "Z802",
+        "if",
+        &["Z802K1", "Z802K2", "Z802K3"],
+        "Z1",
+        &["Z902"],
+    );
+    add_builtin(&mut datas, "Z902", "Z802");
+    add_function(
+        &mut datas,
+        "Z844",
+        "Boolean equality",
+        &["Z844K1", "Z844K2"],
+        "Z40",
+        &["Z944"],
+    );
+    add_builtin(&mut datas, "Z944", "Z844");
+    add_function(
+        &mut datas,
+        "Z872",
+        "filter",
+        &["Z872K1", "Z872K2"],
+        "Z881",
+        &["Z972"],
+    );
+    add_builtin(&mut datas, "Z972", "Z872");
+    add_function(
+        &mut datas,
+        "Z873",
+        "map",
+        &["Z873K1", "Z873K2"],
+        "Z881",
+        &["Z973"],
+    );
+    add_builtin(&mut datas, "Z973", "Z873");
+    add_function(
+        &mut datas,
+        "Z876",
+        "reduce",
+        &["Z876K1", "Z876K2", "Z876K3"],
+        "Z1",
+        &["Z976"],
+    );
+    add_builtin(&mut datas, "Z976", "Z876");
+
+    add_function(
+        &mut datas,
+        "Z10001",
+        "not",
+        &["Z10001K1"],
+        "Z40",
+        &["Z10011"],
+    );
+    add_composition(
+        &mut datas,
+        "Z10011",
+        "Z10001",
+        r#"{
+            "Z1K1": "Z7",
+            "Z7K1": "Z802",
+            "Z802K1": { "Z1K1": "Z18", "Z18K1": "Z10001K1" },
+            "Z802K2": { "Z1K1": "Z40", "Z40K1": "Z42" },
+            "Z802K3": { "Z1K1": "Z40", "Z40K1": "Z41" }
+        }"#,
+    );
+
+    add_function(
+        &mut datas,
+        "Z10002",
+        "xor",
+        &["Z10002K1", "Z10002K2"],
+        "Z40",
+        &["Z10012"],
+    );
+    add_composition(
+        &mut datas,
+        "Z10012",
+        "Z10002",
+        r#"{
+            "Z1K1": "Z7",
+            "Z7K1": "Z802",
+            "Z802K1": { "Z1K1": "Z18", "Z18K1": "Z10002K1" },
+            "Z802K2": {
+                "Z1K1": "Z7",
+                "Z7K1": "Z10001",
+                "Z10001K1": { "Z1K1": "Z18", "Z18K1": "Z10002K2" }
+            },
+            "Z802K3": { "Z1K1": "Z18", "Z18K1": "Z10002K2" }
+        }"#,
+    );
+
+    add_function(
+        &mut datas,
+        "Z10003",
+        "loop",
+        &["Z10003K1"],
+        "Z40",
+        &["Z10013"],
+    );
+    add_composition(
+        &mut datas,
+        "Z10013",
+        "Z10003",
+        r#"{
+            "Z1K1": "Z7",
+            "Z7K1": "Z10003",
+            "Z10003K1": { "Z1K1": "Z18", "Z18K1": "Z10003K1" }
+        }"#,
+    );
+
+    add_function(
+        &mut datas,
+        "Z10004",
+        "first",
+        &["Z10004K1", "Z10004K2"],
+        "Z1",
+        &["Z10014"],
+    );
+    add_composition(
+        &mut datas,
+        "Z10014",
+        "Z10004",
+        r#"{ "Z1K1": "Z18", "Z18K1": "Z10004K1" }"#,
+    );
+
+    add_function(
+        &mut datas,
+        "Z10005",
+        "or with",
+        &["Z10005K1"],
+        "Z8",
+        &["Z10015"],
+    );
+    add_composition(
+        &mut datas,
+        "Z10015",
+        "Z10005",
+        r#"{
+            "Z1K1": "Z8",
+            "Z8K1": ["Z17", { "Z1K1": "Z17", "Z17K1": "Z40", "Z17K2": "K1", "Z17K3": { "Z1K1": "Z12", "Z12K1": ["Z11"] } }],
+            "Z8K2": "Z40",
+            "Z8K3": ["Z20"],
+            "Z8K4": ["Z14", {
+                "Z1K1": "Z14",
+                "Z14K2": {
+                    "Z1K1": "Z7",
+                    "Z7K1": "Z802",
+                    "Z802K1": { "Z1K1": "Z18", "Z18K1": "K1" },
+                    "Z802K2": { "Z1K1": "Z18", "Z18K1": "K1" },
+                    "Z802K3": { "Z1K1": "Z18", "Z18K1": "Z10005K1" }
+                }
+            }]
+        }"#,
+    );
+
+    add_function(
+        &mut datas,
+        "Z10006",
+        "apply",
+        &["Z10006K1", "Z10006K2"],
+        "Z1",
+        &["Z10016"],
+    );
+    add_composition(
+        &mut datas,
+        "Z10016",
+        "Z10006",
+        r#"{
+            "Z1K1": "Z7",
+            "Z7K1": { "Z1K1": "Z18", "Z18K1": "Z10006K1" },
+            "K1": { "Z1K1": "Z18", "Z18K1": "Z10006K2" }
+        }"#,
+    );
+
+    add_function(
+        &mut datas,
+        "Z10007",
+        "deep loop",
+        &["Z10007K1"],
+        "Z40",
+        &["Z10017"],
+    );
+    add_composition(
+        &mut datas,
+        "Z10017",
+        "Z10007",
+        r#"{
+            "Z1K1": "Z7",
+            "Z7K1": "Z10001",
+            "Z10001K1": {
+                "Z1K1": "Z7",
+                "Z7K1": "Z10007",
+                "Z10007K1": { "Z1K1": "Z18", "Z18K1": "Z10007K1" }
+            }
+        }"#,
+    );
+
+    datas
+}
+
+/// first(not(false), loop(true)): it terminates, with true, as long as the loop isn’t evaluated.
+/// first is called at depth 1, then not and the if it tail calls at depth 2.
+pub fn first_not_loop_call() -> DataEntry {
+    serde_json::from_str(
+        r#"{
+            "Z1K1": "Z7",
+            "Z7K1": "Z10004",
+            "Z10004K1": {
+                "Z1K1": "Z7",
+                "Z7K1": "Z10001",
+                "Z10001K1": { "Z1K1": "Z40", "Z40K1": "Z42" }
+            },
+            "Z10004K2": {
+                "Z1K1": "Z7",
+                "Z7K1": "Z10003",
+                "Z10003K1": { "Z1K1": "Z40", "Z40K1": "Z41" }
+            }
+        }"#,
+    )
+    .unwrap()
+}
+
+pub fn fixture_runner() -> Runner {
+    Runner::new(Arc::new(fixture_datas()))
+}
@@ -0,0 +1,301 @@
+use std::{
+    any::Any,
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    ChosenImplementation, EvaluationError, EvaluationErrorKind, Runner, RunnerOption,
+    SelectionNote, Zid,
+    call_cache::{read_log_position, truncate_read_log},
+    parse_tool::{PotentialReference, WfFunction, WfImplementation, WfTestCase, WfUntyped},
+};
+
+/// A test case to run against an implementation of its function, or against the preferred one if None
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TestJob {
+    pub test_case: Zid,
+    pub implementation: Option<Zid>,
+}
+
+#[derive(Debug)]
+pub enum TestOutcome {
+    Passed,
+    Failed(EvaluationError),
+    Panicked(String),
+}
+
+#[derive(Debug)]
+pub struct TestReport {
+    pub job: TestJob,
+    pub outcome: TestOutcome,
+    pub duration: Duration,
+}
+
+impl Runner {
+    /// The ids in a list of a function, like its test cases or implementations
+    pub(crate) fn listed_references<'l>(
+        &'l self,
+        list: &PotentialReference<'l, WfUntyped<'l>>,
+        name: &str,
+    ) -> Result<Vec<Zid>, EvaluationErrorKind> {
+        let entry = list
+            .evaluate(self)
+            .map_err(|e| e.trace(format!("getting the {}", name)))?
+            .entry;
+        Ok(entry
+            .get_array()
+            .map_err(|e| e.trace(format!("getting the {}", name)))?
+            .iter()
+            .skip(1)
+            // the ones written inline have no id to refer to
+            .filter_map(|entry| {
+                PotentialReference::<WfImplementation>::new(entry)
+                    .get_reference()
+                    .ok()
+            })
+            .collect::<Vec<_>>())
+    }
+
+    /// Every test case of the function, against every implementation of it that has an id
+    pub fn test_matrix(&self, function_id: &Zid) -> Result<Vec<TestJob>, EvaluationErrorKind> {
+        let function = self
+            .get_persistent_object::<WfFunction>(function_id)
+            .map_err(|e| e.trace(format!("getting the function {}", function_id)))?
+            .value;
+        let test_cases = self.listed_references(&function.testers, "test cases")?;
+        let implementations =
+            self.listed_references(&function.implementations, "implementations")?;
+
+        Ok(test_cases
+            .iter()
+            .flat_map(|test_case| {
+                implementations.iter().map(|implementation| TestJob {
+                    test_case: *test_case,
+                    implementation: Some(*implementation),
+                })
+            })
+            .collect())
+    }
+
+    pub fn run_test_job(
+        &self,
+        job: &TestJob,
+        option: &RunnerOption,
+    ) -> Result<(), EvaluationError> {
+        let test_case = self
+            .get_persistent_object::<WfTestCase>(&job.test_case)
+            .map_err(|e| e.trace(format!("getting the test case {}", job.test_case)))?;
+        let implementation = match job.implementation {
+            Some(implementation) => ChosenImplementation {
+                id: Some(implementation),
+                value: self
+                    .get_persistent_object(&implementation)
+                    .map_err(|e| e.trace(format!("getting the implementation {}", implementation)))?
+                    .value,
+                selection: vec![SelectionNote::new(Some(implementation), true, "tested")],
+            },
+            None => {
+                let function = test_case
+                    .value
+                    .function
+                    .evaluate(self)
+                    .map_err(|e| e.trace_str("getting the tested function"))?;
+                self.get_preferred_implementation(&function, option)?
+            }
+        };
+        self.run_test_case(&test_case, &implementation, option)
+    }
+
+    /// Run the jobs on the given number of threads. The reports are in the same order as the jobs.
+    pub fn run_test_jobs(
+        &self,
+        jobs: &[TestJob],
+        option: &RunnerOption,
+        threads: NonZeroUsize,
+    ) -> Vec<TestReport> {
+        let next_job = AtomicUsize::new(0);
+        let mut reports = thread::scope(|scope| {
+            let workers = (0..threads.get().min(jobs.len()))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut reports = Vec::new();
+                        loop {
+                            let index = next_job.fetch_add(1, Ordering::Relaxed);
+                            let Some(job) = jobs.get(index) else {
+                                break;
+                            };
+                            reports.push((index, self.run_test_job_isolated(job, option)));
+                        }
+                        reports
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("panics are caught for each job"))
+                .collect::<Vec<_>>()
+        });
+        reports.sort_by_key(|(index, _)| *index);
+        reports.into_iter().map(|(_, report)| report).collect()
+    }
+
+    /// Run the job, turning a panic into a report
+    fn run_test_job_isolated(&self, job: &TestJob, option: &RunnerOption) -> TestReport {
+        let started_at = Instant::now();
+        let read_position = read_log_position();
+        let outcome = match panic::catch_unwind(AssertUnwindSafe(|| self.run_test_job(job, option)))
+        {
+            Ok(Ok(())) => TestOutcome::Passed,
+            Ok(Err(error)) => TestOutcome::Failed(error),
+            Err(payload) => {
+                // an unwound evaluation didn’t clean up after itself
+                truncate_read_log(read_position);
+                TestOutcome::Panicked(panic_message(payload))
+            }
+        };
+        TestReport {
+            job: *job,
+            outcome,
+            duration: started_at.elapsed(),
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "panic with a non-string payload".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{num::NonZeroUsize, sync::Arc};
+
+    use crate::{
+        BuiltinRegistry, FnBuiltin, Runner, RunnerOption, TestJob, TestOutcome, Zid,
+        test_fixture::{add_builtin, add_composition, add_function, add_persistent, fixture_datas},
+    };
+
+    /// Z10010 is not, with a composition (Z10020) and a code (Z10021) implementation, and a passing (Z10101) and a failing (Z10102) test case.
+    /// Z10103 isn’t one of its testers, and its argument is a call to Z10104, a built-in that panics.
+    fn matrix_runner() -> Runner {
+        let mut datas = fixture_datas();
+        add_persistent(
+            &mut datas,
+            "Z10010",
+            "not, again",
+            r#"{
+                "Z1K1": "Z8",
+                "Z8K1": ["Z17", { "Z1K1": "Z17", "Z17K1": "Z40", "Z17K2": "Z10010K1", "Z17K3": { "Z1K1": "Z12", "Z12K1": ["Z11"] } }],
+                "Z8K2": "Z40",
+                "Z8K3": ["Z20", "Z10101", "Z10102"],
+                "Z8K4": ["Z14", "Z10020", "Z10021"],
+                "Z8K5": "Z10010"
+            }"#,
+        );
+        add_composition(
+            &mut datas,
+            "Z10020",
+            "Z10010",
+            r#"{
+                "Z1K1": "Z7",
+                "Z7K1": "Z10001",
+                "Z10001K1": { "Z1K1": "Z18", "Z18K1": "Z10010K1" }
+            }"#,
+        );
+        add_persistent(
+            &mut datas,
+            "Z10021",
+            "Z10021",
+            r#"{ "Z1K1": "Z14", "Z14K1": "Z10010", "Z14K3": { "Z1K1": "Z16", "Z16K1": "Z600", "Z16K2": "" } }"#,
+        );
+        add_function(
+            &mut datas,
+            "Z10104",
+            "panic",
+            &["Z10104K1"],
+            "Z40",
+            &["Z101041"],
+        );
+        add_builtin(&mut datas, "Z101041", "Z10104");
+        let mut builtins = BuiltinRegistry::default();
+        builtins.register(
+            zid!(101041),
+            FnBuiltin::new([zid!(10104, 1)], |_, _, _| panic!("deliberate panic")),
+        );
+        for (test_case, argument) in [
+            ("Z10101", r#"{ "Z1K1": "Z40", "Z40K1": "Z41" }"#),
+            ("Z10102", r#"{ "Z1K1": "Z40", "Z40K1": "Z42" }"#),
+            (
+                "Z10103",
+                r#"{ "Z1K1": "Z7", "Z7K1": "Z10104", "Z10104K1": "Z41" }"#,
+            ),
+        ] {
+            add_persistent(
+                &mut datas,
+                test_case,
+                test_case,
+                &format!(
+                    r#"{{
+                        "Z1K1": "Z20",
+                        "Z20K1": "Z10010",
+                        "Z20K2": {{ "Z1K1": "Z7", "Z7K1": "Z10010", "Z10010K1": {argument} }},
+                        "Z20K3": {{ "Z1K1": "Z7", "Z7K1": "Z844", "Z844K2": {{ "Z1K1": "Z40", "Z40K1": "Z42" }} }}
+                    }}"#
+                ),
+            );
+        }
+        Runner::new(Arc::new(datas)).with_builtins(builtins)
+    }
+
+    #[test]
+    fn test_run_test_matrix() {
+        let runner = matrix_runner();
+        let jobs = runner.test_matrix(&zid!(10010)).unwrap();
+        assert_eq!(
+            jobs,
+            [
+                (zid!(10101), zid!(10020)),
+                (zid!(10101), zid!(10021)),
+                (zid!(10102), zid!(10020)),
+                (zid!(10102), zid!(10021)),
+            ]
+            .map(|(test_case, implementation)| TestJob {
+                test_case,
+                implementation: Some(implementation),
+            })
+        );
+
+        let mut all_jobs = jobs.clone();
+        all_jobs.push(TestJob {
+            test_case: zid!(10103),
+            implementation: Some(zid!(10020)),
+        });
+        let reports = runner.run_test_jobs(
+            &all_jobs,
+            &RunnerOption::default(),
+            NonZeroUsize::new(3).unwrap(),
+        );
+        assert_eq!(
+            reports.iter().map(|report| report.job).collect::<Vec<_>>(),
+            all_jobs
+        );
+        // the code implementation is empty (or can’t be run without the javascript feature)
+        assert!(matches!(reports[0].outcome, TestOutcome::Passed));
+        assert!(matches!(reports[1].outcome, TestOutcome::Failed(_)));
+        assert!(matches!(reports[2].outcome, TestOutcome::Failed(_)));
+        assert!(matches!(reports[3].outcome, TestOutcome::Failed(_)));
+        // a panic doesn’t prevent the other tests from running
+        assert!(
+            matches!(&reports[4].outcome, TestOutcome::Panicked(message) if message == "deliberate panic")
+        );
+    }
+}
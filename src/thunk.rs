@@ -0,0 +1,265 @@
+use std::{borrow::Cow, cell::OnceCell, collections::BTreeMap, rc::Rc};
+
+use crate::{DataEntry, EvaluationContext, EvaluationErrorKind, Runner, Zid};
+
+/// An argument of a function call. It is evaluated the first time it is needed, and the result is then reused (call-by-need), unless memoization is disabled
+#[derive(Debug)]
+pub struct Thunk {
+    /// The unevaluated argument, with the scope of the caller it should be evaluated in. None if the thunk was created from an already evaluated value.
+    expression: Option<(DataEntry, Rc<Scope>)>,
+    memoize: bool,
+    value: OnceCell<DataEntry>,
+}
+
+impl Thunk {
+    pub fn new(expression: DataEntry, scope: Rc<Scope>, memoize: bool) -> Self {
+        Self {
+            expression: Some((expression, scope)),
+            memoize,
+            value: OnceCell::new(),
+        }
+    }
+
+    pub fn from_value(value: DataEntry) -> Self {
+        Self {
+            expression: None,
+            memoize: true,
+            value: OnceCell::from(value),
+        }
+    }
+
+    pub fn get_value(&self) -> Option<&DataEntry> {
+        self.value.get()
+    }
+
+    /// The unevaluated argument and the scope to evaluate it in, if it wasn’t created from a value
+    pub fn get_expression(&self) -> Option<(&DataEntry, &Rc<Scope>)> {
+        self.expression
+            .as_ref()
+            .map(|(expression, scope)| (expression, scope))
+    }
+
+    pub fn is_memoized(&self) -> bool {
+        self.memoize
+    }
+
+    /// Store the value once computed. Ignored if the thunk isn’t memoized, or already has a value.
+    pub fn set_value(&self, value: DataEntry) {
+        if self.memoize {
+            let _ = self.value.set(value);
+        }
+    }
+
+    /// Evaluate the argument outside of an evaluation stack
+    pub fn force(
+        &self,
+        runner: &Runner,
+        context: &EvaluationContext,
+    ) -> Result<Cow<'_, DataEntry>, EvaluationErrorKind> {
+        if let Some(value) = self.value.get() {
+            return Ok(Cow::Borrowed(value));
+        }
+        let (expression, scope) = self
+            .expression
+            .as_ref()
+            .expect("a thunk without expression should have a value");
+        // errors are not memoized. They will be computed again if the argument is used again.
+        let value = runner.recurse_call_function(expression, scope, context)?;
+        if self.memoize {
+            Ok(Cow::Borrowed(self.value.get_or_init(|| value)))
+        } else {
+            Ok(Cow::Owned(value))
+        }
+    }
+}
+
+/// The arguments bound to a function call, that Z18 argument references are resolved against.
+/// The call of a function literal has the scope the literal was evaluated in as parent, for the references to the arguments of the enclosing calls.
+#[derive(Debug, Default)]
+pub struct Scope {
+    arguments: BTreeMap<Zid, Rc<Thunk>>,
+    parent: Option<Rc<Scope>>,
+}
+
+impl Scope {
+    /// Bind the arguments of the function call, to be evaluated in the scope of the caller.
+    /// Local keys (like K1) are bound to the argument declared at the same position, as the caller might not know the keys of a function passed as argument.
+    pub fn bind(
+        arguments: BTreeMap<Zid, DataEntry>,
+        argument_keys: &[Zid],
+        caller: &Rc<Scope>,
+        memoize: bool,
+    ) -> Self {
+        Self {
+            arguments: arguments
+                .into_iter()
+                .map(|(k, v)| {
+                    let key = match (k.get_z(), k.get_k()) {
+                        (None, Some(position)) => argument_keys
+                            .get(position.get() as usize - 1)
+                            .copied()
+                            .unwrap_or(k),
+                        _ => k,
+                    };
+                    (key, Rc::new(Thunk::new(v, caller.clone(), memoize)))
+                })
+                .collect(),
+            parent: None,
+        }
+    }
+
+    /// Resolve the arguments that aren’t bound here in the parent scope
+    pub fn with_parent(mut self, parent: Option<Rc<Scope>>) -> Self {
+        self.parent = parent;
+        self
+    }
+
+    /// The argument bound here, or in the closest parent scope binding it
+    pub fn get(&self, key: &Zid) -> Result<&Rc<Thunk>, EvaluationErrorKind> {
+        let mut scope = self;
+        loop {
+            if let Some(thunk) = scope.arguments.get(key) {
+                return Ok(thunk);
+            }
+            scope = scope
+                .parent
+                .as_deref()
+                .ok_or(EvaluationErrorKind::UnboundArgument(*key))?;
+        }
+    }
+
+    /// The keys of the arguments bound here, without the ones of the parent scopes
+    pub fn keys(&self) -> impl Iterator<Item = &Zid> {
+        self.arguments.keys()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Zid, &Rc<Thunk>)> {
+        self.arguments.iter()
+    }
+
+    pub fn force(
+        &self,
+        key: &Zid,
+        runner: &Runner,
+        context: &EvaluationContext,
+    ) -> Result<Cow<'_, DataEntry>, EvaluationErrorKind> {
+        self.get(key)?
+            .force(runner, context)
+            .map_err(|e| e.trace(format!("evaluating argument {}", key)))
+    }
+}
+
+impl Drop for Scope {
+    /// Scopes form chains through the arguments of recursive calls and through their parents, which could overflow the stack if dropped recursively
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        let release = |scope: &mut Scope, pending: &mut Vec<Rc<Scope>>| {
+            for (_, thunk) in std::mem::take(&mut scope.arguments) {
+                if let Ok(mut thunk) = Rc::try_unwrap(thunk)
+                    && let Some((_, scope)) = thunk.expression.take()
+                {
+                    pending.push(scope);
+                }
+            }
+            pending.extend(scope.parent.take());
+        };
+        release(self, &mut pending);
+        while let Some(scope) = pending.pop() {
+            if let Ok(mut scope) = Rc::try_unwrap(scope) {
+                release(&mut scope, &mut pending);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use crate::{
+        BuiltinRegistry, DataEntry, EvaluationStrategy, FnBuiltin, Runner, RunnerOption, Step, Zid,
+        parse_tool::{WfFunctionCall, WfParse},
+        test_fixture::{add_builtin, add_composition, add_function, fixture_datas},
+    };
+
+    #[test]
+    fn test_argument_evaluated_once() {
+        let mut datas = fixture_datas();
+        // Z10090 returns true, counting how many times it is called
+        add_function(
+            &mut datas,
+            "Z10090",
+            "counted true",
+            &["Z10090K1"],
+            "Z40",
+            &["Z10091"],
+        );
+        add_builtin(&mut datas, "Z10091", "Z10090");
+        // Z10092: xor(Z10092K1, Z10092K1), which uses its argument twice
+        add_function(
+            &mut datas,
+            "Z10092",
+            "xor with itself",
+            &["Z10092K1"],
+            "Z40",
+            &["Z100921"],
+        );
+        add_composition(
+            &mut datas,
+            "Z100921",
+            "Z10092",
+            r#"{
+                "Z1K1": "Z7",
+                "Z7K1": "Z10002",
+                "Z10002K1": { "Z1K1": "Z18", "Z18K1": "Z10092K1" },
+                "Z10002K2": { "Z1K1": "Z18", "Z18K1": "Z10092K1" }
+            }"#,
+        );
+        let count = Arc::new(AtomicUsize::new(0));
+        let mut builtins = BuiltinRegistry::default();
+        let counter = count.clone();
+        builtins.register(
+            zid!(10091),
+            FnBuiltin::new([zid!(10090, 1)], move |runner, _, _| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(Step::Value(runner.get_true()?.clone()))
+            }),
+        );
+        let runner = Runner::new(Arc::new(datas)).with_builtins(builtins);
+        let call = serde_json::from_str::<DataEntry>(
+            r#"{
+                "Z1K1": "Z7",
+                "Z7K1": "Z10092",
+                "Z10092K1": { "Z1K1": "Z7", "Z7K1": "Z10090", "Z10090K1": "unused" }
+            }"#,
+        )
+        .unwrap();
+        let call = WfFunctionCall::parse(&call).unwrap();
+
+        for (evaluation_strategy, expected_count) in [
+            (EvaluationStrategy::Lazy, 1),
+            (EvaluationStrategy::Strict, 1),
+            // the orchestrator evaluates the argument again at each use
+            (EvaluationStrategy::Orchestrator, 2),
+        ] {
+            count.store(0, Ordering::SeqCst);
+            let option = RunnerOption {
+                evaluation_strategy,
+                ..Default::default()
+            };
+            assert_eq!(
+                &runner.run_function_call(&call, &option).unwrap(),
+                runner.get_false().unwrap()
+            );
+            assert_eq!(
+                count.load(Ordering::SeqCst),
+                expected_count,
+                "with {:?}",
+                evaluation_strategy
+            );
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde_json::json;
+
+use crate::{DataEntry, EvaluationErrorKind, Profiler, Scope, SelectionNote, Zid};
+
+/// A function call performed by the runner, with the calls it performed itself
+#[derive(Debug, Clone, PartialEq)]
+pub struct TracedCall {
+    pub function: String,
+    pub implementation: Option<Zid>,
+    /// Why each implementation of the function was chosen or skipped
+    pub selection: Vec<SelectionNote>,
+    /// The arguments that were evaluated. None for the ones that weren’t, or whose value wasn’t kept.
+    pub arguments: BTreeMap<Zid, Option<DataEntry>>,
+    /// The error message if the call failed
+    pub result: Result<DataEntry, String>,
+    pub duration: Duration,
+    pub children: Vec<TracedCall>,
+}
+
+impl TracedCall {
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut result = json!({
+            "function": self.function,
+            "implementation": self.implementation.map(|implementation| implementation.to_zid()),
+            "arguments": self
+                .arguments
+                .iter()
+                .map(|(key, value)| (key.to_zid(), json!(value)))
+                .collect::<serde_json::Map<_, _>>(),
+            "selection": self
+                .selection
+                .iter()
+                .map(|note| json!({
+                    "implementation": note.implementation.map(|implementation| implementation.to_zid()),
+                    "chosen": note.chosen,
+                    "reason": note.reason,
+                }))
+                .collect::<Vec<_>>(),
+            "duration_us": self.duration.as_micros() as u64,
+            "children": self.children.iter().map(Self::to_json).collect::<Vec<_>>(),
+        });
+        match &self.result {
+            Ok(value) => result["result"] = json!(value),
+            Err(error) => result["error"] = json!(error),
+        }
+        result
+    }
+
+    /// One line per call, indented by depth
+    pub fn write_text(&self, depth: usize, output: &mut String) {
+        let compact = |value: &DataEntry| serde_json::to_string(value).unwrap_or_default();
+        let _ = write!(output, "{}{}", "  ".repeat(depth), self.function);
+        if let Some(implementation) = self.implementation {
+            let _ = write!(output, " ({})", implementation);
+        }
+        for (key, value) in &self.arguments {
+            match value {
+                Some(value) => {
+                    let _ = write!(output, " {}={}", key, compact(value));
+                }
+                None => {
+                    let _ = write!(output, " {}=?", key);
+                }
+            }
+        }
+        match &self.result {
+            Ok(value) => {
+                let _ = write!(output, " -> {}", compact(value));
+            }
+            Err(error) => {
+                let _ = write!(output, " -> error: {}", error);
+            }
+        }
+        let _ = writeln!(output, " [{:?}]", self.duration);
+        // only the implementations passed over, the chosen one is already shown
+        for note in self.selection.iter().filter(|note| !note.chosen) {
+            let _ = writeln!(output, "{}  # {}", "  ".repeat(depth), note);
+        }
+        for child in &self.children {
+            child.write_text(depth + 1, output);
+        }
+    }
+}
+
+/// Record the calls of the evaluations whose RunnerOption holds it. Clones share the same records.
+#[derive(Debug, Clone, Default)]
+pub struct Tracer {
+    calls: Arc<Mutex<Vec<TracedCall>>>,
+}
+
+impl Tracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record(&self, call: TracedCall) {
+        self.calls.lock().unwrap().push(call);
+    }
+
+    /// The outermost calls recorded so far, in the order they ended
+    pub fn get_calls(&self) -> Vec<TracedCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    pub fn clear(&self) {
+        self.calls.lock().unwrap().clear();
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!(
+            self.get_calls()
+                .iter()
+                .map(TracedCall::to_json)
+                .collect::<Vec<_>>()
+        )
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut output = String::new();
+        for call in self.get_calls() {
+            call.write_text(0, &mut output);
+        }
+        output
+    }
+}
+
+/// A call that started but didn’t end yet
+#[derive(Debug)]
+pub struct OpenCall {
+    pub function: String,
+    pub function_id: Option<Zid>,
+    /// The English label of the function and its ZID, for the profiler
+    pub label: String,
+    /// The kind of implementation: composition, builtin or code
+    pub category: &'static str,
+    pub implementation: Option<Zid>,
+    pub arguments: Rc<Scope>,
+    /// The composition run, for the debugger
+    pub composition: Option<DataEntry>,
+    /// Why the implementation was chosen, for the tracer
+    pub selection: Vec<SelectionNote>,
+    pub(crate) started_at: Instant,
+    pub(crate) children_duration: Duration,
+    children: Vec<TracedCall>,
+}
+
+impl OpenCall {
+    pub fn new(
+        function: String,
+        function_id: Option<Zid>,
+        label: String,
+        category: &'static str,
+        implementation: Option<Zid>,
+        arguments: Rc<Scope>,
+    ) -> Self {
+        Self {
+            function,
+            function_id,
+            label,
+            category,
+            implementation,
+            arguments,
+            composition: None,
+            selection: Vec::new(),
+            started_at: Instant::now(),
+            children_duration: Duration::ZERO,
+            children: Vec::new(),
+        }
+    }
+}
+
+impl OpenCall {
+    pub fn with_composition(mut self, composition: Option<DataEntry>) -> Self {
+        self.composition = composition;
+        self
+    }
+
+    pub fn with_selection(mut self, selection: Vec<SelectionNote>) -> Self {
+        self.selection = selection;
+        self
+    }
+}
+
+/// The calls of an evaluation currently running, recorded for the tracer, the profiler or the debugger
+#[derive(Debug, Default)]
+pub struct TraceStack {
+    open_calls: Vec<OpenCall>,
+}
+
+impl TraceStack {
+    /// The calls currently running, the outermost first
+    pub fn get_open_calls(&self) -> &[OpenCall] {
+        &self.open_calls
+    }
+
+    pub fn open(&mut self, mut call: OpenCall) {
+        call.started_at = Instant::now();
+        self.open_calls.push(call);
+    }
+
+    /// End the innermost open call. It is added to its caller, or to the tracer if it is an outermost call.
+    pub fn close(
+        &mut self,
+        result: Result<&DataEntry, &EvaluationErrorKind>,
+        tracer: Option<&Tracer>,
+        profiler: Option<&Profiler>,
+    ) {
+        let Some(call) = self.open_calls.pop() else {
+            return;
+        };
+        let duration = call.started_at.elapsed();
+        if let Some(profiler) = profiler {
+            let mut stack = String::new();
+            for caller in &self.open_calls {
+                stack.push_str(&caller.label);
+                stack.push(';');
+            }
+            stack.push_str(&call.label);
+            profiler.record(&call, duration, stack);
+        }
+        if let Some(caller) = self.open_calls.last_mut() {
+            caller.children_duration += duration;
+        }
+        let Some(tracer) = tracer else {
+            return;
+        };
+        let call = TracedCall {
+            function: call.function,
+            implementation: call.implementation,
+            selection: call.selection,
+            arguments: call
+                .arguments
+                .iter()
+                .map(|(key, thunk)| (*key, thunk.get_value().cloned()))
+                .collect(),
+            result: result.cloned().map_err(|error| error.root().to_string()),
+            duration,
+            children: call.children,
+        };
+        match self.open_calls.last_mut() {
+            Some(caller) => caller.children.push(call),
+            None => tracer.record(call),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        DataEntry, RunnerOption, Tracer, Zid,
+        parse_tool::{WfFunctionCall, WfParse},
+        test_fixture::{first_not_loop_call, fixture_runner},
+    };
+
+    #[test]
+    fn test_trace_call_tree() {
+        let runner = fixture_runner();
+        let tracer = Tracer::new();
+        let option = RunnerOption {
+            tracer: Some(tracer.clone()),
+            ..Default::default()
+        };
+        let true_value =
+            serde_json::from_str::<DataEntry>(r#"{ "Z1K1": "Z40", "Z40K1": "Z41" }"#).unwrap();
+
+        // the loop is never evaluated
+        let call = first_not_loop_call();
+        let result = runner
+            .run_function_call(&WfFunctionCall::parse(&call).unwrap(), &option)
+            .unwrap();
+        assert_eq!(result, true_value);
+
+        let calls = tracer.get_calls();
+        assert_eq!(calls.len(), 1);
+        let first = &calls[0];
+        assert_eq!(first.function, "Z10004");
+        assert_eq!(first.implementation, Some(zid!(10014)));
+        assert_eq!(first.result, Ok(true_value.clone()));
+        assert_eq!(first.arguments[&zid!(10004, 1)], Some(true_value.clone()));
+        assert_eq!(first.arguments[&zid!(10004, 2)], None);
+
+        // not, then the if it tail calls
+        assert_eq!(first.children.len(), 1);
+        let not = &first.children[0];
+        assert_eq!(not.function, "Z10001");
+        assert_eq!(not.children[0].function, "Z802");
+        assert_eq!(not.children[0].result, Ok(true_value));
+
+        let text = tracer.to_text();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("Z10004 (Z10014) Z10004K1="));
+        assert!(lines[0].contains("Z10004K2=?"));
+        assert!(lines[1].starts_with("  Z10001 (Z10011)"));
+        assert!(lines[2].starts_with("    Z802 (Z902)"));
+
+        let json = tracer.to_json();
+        assert_eq!(json[0]["function"], "Z10004");
+        assert_eq!(json[0]["arguments"]["Z10004K2"], serde_json::Value::Null);
+        assert_eq!(
+            json[0]["children"][0]["children"][0]["result"]["Z40K1"],
+            "Z41"
+        );
+    }
+}
@@ -0,0 +1,30 @@
+// Worker of the subprocess executor: reads a request from stdin, and writes the response to stdout. See src/executor.rs for the protocol.
+'use strict';
+const chunks = [];
+process.stdin.on( 'data', ( chunk ) => chunks.push( chunk ) );
+process.stdin.on( 'end', () => {
+	const request = JSON.parse( Buffer.concat( chunks ).toString( 'utf8' ) );
+	// what the code prints must not mix with the response
+	console.log = console.error;
+	let response;
+	try {
+		// indirect, so that the declarations are global
+		( 0, eval )( request.source );
+		const fn = globalThis[ request.function ];
+		if ( typeof fn !== 'function' ) {
+			throw new ReferenceError( 'the code doesn’t define the function ' + request.function );
+		}
+		const result = fn( ...request.arguments );
+		// undefined is the unit, like null
+		response = { result: result === undefined ? null : result };
+	} catch ( error ) {
+		response = { error: String( error ) };
+	}
+	let text;
+	try {
+		text = JSON.stringify( response );
+	} catch ( error ) {
+		text = JSON.stringify( { error: 'can’t serialize the result: ' + String( error ) } );
+	}
+	process.stdout.write( text + '\n' );
+} );
@@ -0,0 +1,22 @@
+# Worker of the subprocess executor: reads a request from stdin, and writes the response to stdout. See src/executor.rs for the protocol.
+import json
+import sys
+
+request = json.load(sys.stdin)
+protocol_output = sys.stdout
+# what the code prints must not mix with the response
+sys.stdout = sys.stderr
+try:
+    namespace = {}
+    exec(request["source"], namespace)
+    if request["function"] not in namespace:
+        raise NameError("the code doesn’t define the function " + request["function"])
+    response = {"result": namespace[request["function"]](*request["arguments"])}
+except BaseException as error:
+    response = {"error": type(error).__name__ + ": " + str(error)}
+try:
+    text = json.dumps(response, ensure_ascii=False, allow_nan=False)
+except (TypeError, ValueError) as error:
+    text = json.dumps({"error": "can’t serialize the result: " + str(error)})
+protocol_output.write(text + "\n")
+protocol_output.flush()
@@ -36,7 +36,9 @@ impl Zid {
                 bail!("First character should be Z");
             }
             Some(
-                u64::from_str_radix(char_id_iter.as_str(), 10)
+                char_id_iter
+                    .as_str()
+                    .parse::<u64>()
                     .context("Can’t convert the first number part of the ZID to a u64 number")?,
             )
         } else {
@@ -45,7 +47,8 @@ impl Zid {
 
         let k = if let Some(second_part) = k_splitted.next() {
             Some(
-                u64::from_str_radix(second_part, 10)
+                second_part
+                    .parse::<u64>()
                     .context("Could not parse post-key text as u64")?,
             )
         } else {
@@ -56,7 +59,7 @@ impl Zid {
             bail!("Text contain extra characters")
         }
 
-        Ok(Zid::from_u64s(z, k)?)
+        Zid::from_u64s(z, k)
     }
 
     pub fn from_u64s(z: Option<u64>, k: Option<u64>) -> anyhow::Result<Self> {
@@ -135,7 +138,8 @@ impl<'de> Visitor<'de> for ReferenceVisitor {
         formatter.write_str("a ZID")
     }
 
-    fn visit_borrowed_str<E>(self, t: &'de str) -> Result<Self::Value, E>
+    /// Also used for borrowed strings
+    fn visit_str<E>(self, t: &str) -> Result<Self::Value, E>
     where
         E: serde::de::Error,
     {
@@ -175,6 +179,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::unnecessary_literal_unwrap)]
     fn test_from_zid() {
         assert_eq!(Zid::from_zid("Z156").unwrap(), zid!(156));
         assert_eq!(Zid::from_zid("Z30K4").unwrap(), zid!(30, 4),);